@@ -0,0 +1,170 @@
+//! Benchmarks for the different stages of the pipeline on synthetic traces. Run with
+//! `cargo bench`.
+
+use std::{fmt::Write, hint::black_box, io};
+
+use backtracetk::{
+    config::{Config, PartialConfig},
+    filter::{Filters, FrameFilter},
+    model::Backtrace,
+    parse::Parser,
+    partial::Partial,
+};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+/// Generates a log with `traces` backtraces of `frames` frames each, interleaved with some
+/// regular output.
+fn fixture(traces: usize, frames: usize) -> String {
+    let mut s = String::new();
+    for t in 0..traces {
+        writeln!(s, "running step {t}").unwrap();
+        writeln!(s, "thread 'main' panicked at src/main.rs:{}:5:", t + 1).unwrap();
+        writeln!(s, "explicit panic").unwrap();
+        writeln!(s, "stack backtrace:").unwrap();
+        for i in 0..frames {
+            let m = i % 7;
+            writeln!(
+                s,
+                "  {i:>4}: my_crate::module{m}::function_{i}::h0123456789abcdef"
+            )
+            .unwrap();
+            writeln!(
+                s,
+                "             at /nonexistent/src/module{m}.rs:{}:{}",
+                i + 10,
+                i % 40 + 1
+            )
+            .unwrap();
+        }
+        writeln!(
+            s,
+            "note: Some details are omitted, run with `RUST_BACKTRACE=full` for a verbose backtrace."
+        )
+        .unwrap();
+    }
+    s
+}
+
+const SIZES: [(usize, usize); 3] = [(1, 100), (10, 1000), (50, 1000)];
+
+fn parse(input: &str) -> Vec<Backtrace> {
+    let mut parser = Parser::new();
+    for line in input.lines() {
+        parser.parse_line(line);
+    }
+    parser.into_backtraces()
+}
+
+fn config() -> Config {
+    toml::from_str::<PartialConfig>(
+        r#"
+        [[hide]]
+        pattern = "module3"
+
+        [[hide]]
+        begin = "function_10::"
+        end = "function_20::"
+
+        [[hide]]
+        pattern = "std"
+        anchor = "crate"
+        "#,
+    )
+    .unwrap()
+    .into_complete()
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    for (traces, frames) in SIZES {
+        let input = fixture(traces, frames);
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(format!("{traces}x{frames}")),
+            &input,
+            |b, input| b.iter(|| parse(black_box(input))),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("parallel", format!("{traces}x{frames}")),
+            &input,
+            |b, input| {
+                b.iter(|| backtracetk::parse::parse_bytes_parallel(black_box(input.as_bytes())))
+            },
+        );
+    }
+    group.finish();
+}
+
+fn bench_filter(c: &mut Criterion) {
+    let config = config();
+    let mut group = c.benchmark_group("filter");
+    for (traces, frames) in SIZES {
+        let backtraces = parse(&fixture(traces, frames));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(format!("{traces}x{frames}")),
+            &backtraces,
+            |b, backtraces| {
+                b.iter(|| {
+                    let mut hidden = 0;
+                    for backtrace in backtraces {
+                        let mut filters = Filters::from_config(&config);
+                        for frame in backtrace.frames.iter().rev() {
+                            hidden += filters.should_hide(frame) as usize;
+                        }
+                    }
+                    hidden
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn bench_render(c: &mut Criterion) {
+    let config = config();
+    let mut group = c.benchmark_group("render");
+    for (traces, frames) in SIZES {
+        let backtraces = parse(&fixture(traces, frames));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(format!("{traces}x{frames}")),
+            &backtraces,
+            |b, backtraces| {
+                b.iter(|| {
+                    let mut out = io::sink();
+                    for backtrace in backtraces {
+                        let mut filters = Filters::from_config(&config);
+                        backtrace
+                            .render_to(&mut out, &config, &mut filters)
+                            .unwrap();
+                    }
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn bench_pipeline(c: &mut Criterion) {
+    let config = config();
+    let input = fixture(50, 1000);
+    c.bench_function("pipeline/50x1000", |b| {
+        b.iter(|| {
+            let mut out = io::sink();
+            for backtrace in parse(black_box(&input)) {
+                let mut filters = Filters::from_config(&config);
+                backtrace
+                    .render_to(&mut out, &config, &mut filters)
+                    .unwrap();
+            }
+        })
+    });
+}
+
+criterion_group!(
+    benches,
+    bench_parse,
+    bench_filter,
+    bench_render,
+    bench_pipeline
+);
+criterion_main!(benches);
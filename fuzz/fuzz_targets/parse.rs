@@ -0,0 +1,31 @@
+//! Parses and renders arbitrary output, checking the invariants the renderer relies on. Seed it
+//! with the samples in `tests/fixtures`, e.g.,
+//! `cargo fuzz run parse fuzz/corpus/parse tests/fixtures`.
+
+#![no_main]
+
+use std::io;
+
+use backtracetk::{config::Config, filter::Filters, parse::Parser};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let mut parser = Parser::new();
+    parser.parse_bytes(data);
+    let config = Config::default();
+    for backtrace in parser.into_backtraces() {
+        assert!(!backtrace.frames.is_empty());
+        // Frames are innermost first and inlined frames share the number of their frame
+        assert!(backtrace
+            .frames
+            .windows(2)
+            .all(|frames| frames[0].frameno <= frames[1].frameno));
+        let mut filters = Filters::from_config(&config);
+        backtrace
+            .render_to(&mut io::sink(), &config, &mut filters)
+            .unwrap();
+        backtrace
+            .render_html(&mut io::sink(), &config, &mut filters)
+            .unwrap();
+    }
+});
@@ -0,0 +1,27 @@
+/* C bindings for backtracetk, built with the `ffi` feature. See `src/ffi.rs`. */
+
+#ifndef BACKTRACETK_H
+#define BACKTRACETK_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Parses the backtraces in `log` and returns them as a JSON array. */
+char *backtracetk_parse_json(const char *log);
+
+/* Renders the backtraces in `log` as text with ANSI escape codes. `config` has the same format as a
+ * configuration file and may be NULL to use the default configuration. */
+char *backtracetk_render_ansi(const char *log, const char *config);
+
+/* The message of the last error in the current thread, or NULL. Owned by the library. */
+const char *backtracetk_last_error(void);
+
+/* Releases a string returned by `backtracetk_parse_json` or `backtracetk_render_ansi`. */
+void backtracetk_string_free(char *s);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -1,13 +1,13 @@
-use proc_macro2::{Span, TokenStream};
-use quote::quote;
-use syn::{Data, Error, Ident};
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{ext::IdentExt, spanned::Spanned, Data, Error, Fields, Ident, Index, Member, Token};
 use synstructure::Structure;
 
 synstructure::decl_derive!([Partial, attributes(complete)] => partial_derive);
 
 synstructure::decl_derive!([Complete] => complete_derive);
 
-synstructure::decl_derive!([Partialize] => partialize_derive);
+synstructure::decl_derive!([Partialize, attributes(partialize)] => partialize_derive);
 
 fn partial_derive(s: Structure) -> TokenStream {
     partial_derive_inner(s).unwrap_or_else(|err| err.to_compile_error())
@@ -16,36 +16,69 @@ fn partial_derive(s: Structure) -> TokenStream {
 fn partial_derive_inner(s: Structure) -> syn::Result<TokenStream> {
     let data = check_is_struct("Partial", &s)?;
 
-    let complete_ident = find_complete_attr(&s.ast().attrs)?.parse_args::<Ident>()?;
+    let complete_ident = find_complete_attr(s.ast())?.parse_args::<Ident>()?;
 
+    // `other` is merged on top of `self` field by field, so the later layer takes precedence
     let merge_with_body: TokenStream = iter_fields(data)
-        .map(|(f, _)| quote! { #f: other.#f.merge_with(self.#f), })
+        .map(|(f, _)| quote! { #f: self.#f.merge_with(other.#f), })
         .collect();
 
     let into_complete_body: TokenStream = iter_fields(data)
         .map(|(f, _)| quote! { #f: self.#f.into_complete(), })
         .collect();
 
+    let is_set_body: TokenStream = iter_fields(data)
+        .map(|(f, _)| quote! { || self.#f.is_set() })
+        .collect();
+
+    let visit_set_body: TokenStream = iter_fields(data)
+        .map(|(f, _)| {
+            let name = member_name(&f);
+            quote! {
+                path.push(#name);
+                self.#f.visit_set(path, f);
+                path.pop();
+            }
+        })
+        .collect();
+
     Ok(s.gen_impl(quote! {
         gen impl crate::partial::Partial for @Self {
             type Complete = #complete_ident;
 
-            fn merge_with(mut self, other: Self) -> Self {
+            fn merge_with(self, other: Self) -> Self {
                 Self { #merge_with_body }
             }
 
             fn into_complete(self) -> Self::Complete {
                 #complete_ident { #into_complete_body }
             }
+
+            fn is_set(&self) -> bool {
+                false #is_set_body
+            }
+
+            fn visit_set(
+                &self,
+                path: &mut Vec<&'static str>,
+                f: &mut dyn FnMut(&[&'static str]),
+            ) {
+                #visit_set_body
+            }
         }
     }))
 }
 
-fn find_complete_attr(attrs: &[syn::Attribute]) -> syn::Result<&syn::Attribute> {
-    attrs
+fn find_complete_attr(ast: &syn::DeriveInput) -> syn::Result<&syn::Attribute> {
+    ast.attrs
         .iter()
         .find(|attr| attr.path().is_ident("complete"))
-        .ok_or_else(|| Error::new(Span::call_site(), "missing complete attr"))
+        .ok_or_else(|| {
+            Error::new_spanned(
+                &ast.ident,
+                "missing `#[complete(...)]` attribute naming the complete type",
+            )
+        })
 }
 
 fn complete_derive(s: Structure) -> TokenStream {
@@ -67,15 +100,47 @@ fn partialize_derive(s: Structure) -> TokenStream {
 fn partialize_derive_inner(s: Structure) -> syn::Result<TokenStream> {
     let data = check_is_struct("Partialize", &s)?;
 
+    let vis = &s.ast().vis;
     let ident = &s.ast().ident;
-    let partial_ident = Ident::new(&format!("Partial{ident}"), Span::call_site());
-    let partial_fields: TokenStream = iter_fields(data)
-        .map(|(f, ty)| quote! { #f: <#ty as crate::partial::Complete>::Partial, })
+    let partial_ident = format_ident!("Partial{ident}");
+    let extra_derives = parse_partialize_attrs(s.ast())?;
+    let partial_fields: TokenStream = data
+        .fields
+        .iter()
+        .map(|fld| {
+            let vis = &fld.vis;
+            let ty = &fld.ty;
+            let partial_ty = quote! { <#ty as crate::partial::Complete>::Partial };
+            match &fld.ident {
+                Some(f) => quote! { #vis #f: #partial_ty, },
+                None => quote! { #vis #partial_ty, },
+            }
+        })
         .collect();
+    let partial_struct = match &data.fields {
+        Fields::Unnamed(_) => quote! { #vis struct #partial_ident(#partial_fields); },
+        _ => quote! { #vis struct #partial_ident { #partial_fields } },
+    };
     let into_partial_fields: TokenStream = iter_fields(data)
         .map(|(f, ty)| quote! { #f: <#ty as crate::partial::Complete>::into_partial(self.#f), })
         .collect();
 
+    let visit_docs_body: TokenStream = data
+        .fields
+        .iter()
+        .zip(iter_fields(data))
+        .map(|(fld, (f, ty))| {
+            let name = member_name(&f);
+            let call = doc_comment(&fld.attrs).map(|doc| quote! { f(path, #doc); });
+            quote! {
+                path.push(#name);
+                #call
+                <#ty as crate::partial::Complete>::visit_docs(path, f);
+                path.pop();
+            }
+        })
+        .collect();
+
     let complete_impl = s.gen_impl(quote! {
         gen impl crate::partial::Complete for @Self {
             type Partial = #partial_ident;
@@ -83,37 +148,292 @@ fn partialize_derive_inner(s: Structure) -> syn::Result<TokenStream> {
             fn into_partial(self) -> #partial_ident {
                 #partial_ident { #into_partial_fields }
             }
+
+            fn visit_docs(
+                path: &mut Vec<&'static str>,
+                f: &mut dyn FnMut(&[&'static str], &'static str),
+            ) {
+                #visit_docs_body
+            }
         }
     });
 
     Ok(quote! {
-        #[derive(Default, Deserialize, macros::Partial)]
+        #[derive(Default, serde::Deserialize, macros::Partial #(, #extra_derives)*)]
         #[complete(#ident)]
         #[serde(default)]
-        pub struct #partial_ident {
-            #partial_fields
-        }
+        #partial_struct
 
         #complete_impl
     })
 }
 
-fn iter_fields(data: &syn::DataStruct) -> impl Iterator<Item = (Ident, &syn::Type)> {
-    data.fields
+/// Parses `#[partialize(derive(...))]` attributes returning the list of extra traits to derive for
+/// the generated partial struct.
+fn parse_partialize_attrs(ast: &syn::DeriveInput) -> syn::Result<Vec<syn::Path>> {
+    let mut derives = vec![];
+    for attr in &ast.attrs {
+        if !attr.path().is_ident("partialize") {
+            continue;
+        }
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("derive") {
+                let content;
+                syn::parenthesized!(content in meta.input);
+                derives.extend(content.parse_terminated(syn::Path::parse_mod_style, Token![,])?);
+                Ok(())
+            } else {
+                Err(meta.error("unsupported partialize attribute, expected `derive(...)`"))
+            }
+        })?;
+    }
+    Ok(derives)
+}
+
+/// The name of a field as written in configuration files.
+fn member_name(member: &Member) -> String {
+    match member {
+        Member::Named(ident) => ident.unraw().to_string(),
+        Member::Unnamed(index) => index.index.to_string(),
+    }
+}
+
+/// Concatenates the `///` doc comments in `attrs` into a single string, one line per comment.
+fn doc_comment(attrs: &[syn::Attribute]) -> Option<String> {
+    let lines: Vec<String> = attrs
         .iter()
-        .enumerate()
-        .map(|(i, fld)| match fld.ident.as_ref() {
-            Some(ident) => (ident.clone(), &fld.ty),
-            None => (Ident::new(&format!("{i}"), Span::call_site()), &fld.ty),
+        .filter(|attr| attr.path().is_ident("doc"))
+        .filter_map(|attr| match &attr.meta {
+            syn::Meta::NameValue(syn::MetaNameValue {
+                value:
+                    syn::Expr::Lit(syn::ExprLit {
+                        lit: syn::Lit::Str(lit),
+                        ..
+                    }),
+                ..
+            }) => Some(lit.value()),
+            _ => None,
         })
+        .map(|line| {
+            line.strip_prefix(' ')
+                .unwrap_or(&line)
+                .trim_end()
+                .to_string()
+        })
+        .collect();
+    if lines.is_empty() {
+        None
+    } else {
+        Some(lines.join("\n"))
+    }
+}
+
+/// Iterates over the fields of a struct returning the [`Member`] used to access them, i.e., the
+/// field's name for structs with named fields or its index for tuple structs.
+fn iter_fields(data: &syn::DataStruct) -> impl Iterator<Item = (Member, &syn::Type)> {
+    data.fields.iter().enumerate().map(|(i, fld)| {
+        let member = match &fld.ident {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(Index {
+                index: i as u32,
+                span: fld.ty.span(),
+            }),
+        };
+        (member, &fld.ty)
+    })
 }
 
 fn check_is_struct<'a>(trait_: &str, s: &'a Structure) -> syn::Result<&'a syn::DataStruct> {
-    match &s.ast().data {
+    let ast = s.ast();
+    let ident = &ast.ident;
+    match &ast.data {
+        Data::Struct(syn::DataStruct {
+            struct_token,
+            fields: Fields::Unit,
+            ..
+        }) => Err(Error::new_spanned(
+            quote!(#struct_token #ident),
+            format!("{trait_} cannot be derived for unit structs"),
+        )),
         Data::Struct(data) => Ok(data),
-        _ => Err(Error::new_spanned(
-            s.ast(),
+        Data::Enum(syn::DataEnum { enum_token, .. }) => Err(Error::new_spanned(
+            quote!(#enum_token #ident),
+            format!("{trait_} can only be derived for structs"),
+        )),
+        Data::Union(syn::DataUnion { union_token, .. }) => Err(Error::new_spanned(
+            quote!(#union_token #ident),
             format!("{trait_} can only be derived for structs"),
         )),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn complete() {
+        synstructure::test_derive! {
+            complete_derive {
+                enum Style { Short, Full }
+            }
+            expands to {
+                const _: () = {
+                    impl crate::partial::Complete for Style {
+                        type Partial = Option<Self>;
+
+                        fn into_partial(self) -> Option<Self> {
+                            Some(self)
+                        }
+                    }
+                };
+            }
+            no_build
+        }
+    }
+
+    #[test]
+    fn partial() {
+        synstructure::test_derive! {
+            partial_derive {
+                #[complete(Config)]
+                struct PartialConfig {
+                    style: Option<Style>,
+                    hide: Vec<Hide>,
+                }
+            }
+            expands to {
+                const _: () = {
+                    impl crate::partial::Partial for PartialConfig {
+                        type Complete = Config;
+
+                        fn merge_with(self, other: Self) -> Self {
+                            Self {
+                                style: self.style.merge_with(other.style),
+                                hide: self.hide.merge_with(other.hide),
+                            }
+                        }
+
+                        fn into_complete(self) -> Self::Complete {
+                            Config {
+                                style: self.style.into_complete(),
+                                hide: self.hide.into_complete(),
+                            }
+                        }
+
+                        fn is_set(&self) -> bool {
+                            false || self.style.is_set() || self.hide.is_set()
+                        }
+
+                        fn visit_set(
+                            &self,
+                            path: &mut Vec<&'static str>,
+                            f: &mut dyn FnMut(&[&'static str]),
+                        ) {
+                            path.push("style");
+                            self.style.visit_set(path, f);
+                            path.pop();
+                            path.push("hide");
+                            self.hide.visit_set(path, f);
+                            path.pop();
+                        }
+                    }
+                };
+            }
+            no_build
+        }
+    }
+
+    #[test]
+    fn partialize() {
+        synstructure::test_derive! {
+            partialize_derive {
+                pub struct Config {
+                    /// Sets the backtrace detail level.
+                    pub style: Style,
+                    hide: Vec<Hide>,
+                }
+            }
+            expands to {
+                #[derive(Default, serde::Deserialize, macros::Partial)]
+                #[complete(Config)]
+                #[serde(default)]
+                pub struct PartialConfig {
+                    pub style: <Style as crate::partial::Complete>::Partial,
+                    hide: <Vec<Hide> as crate::partial::Complete>::Partial,
+                }
+
+                const _: () = {
+                    impl crate::partial::Complete for Config {
+                        type Partial = PartialConfig;
+
+                        fn into_partial(self) -> PartialConfig {
+                            PartialConfig {
+                                style: <Style as crate::partial::Complete>::into_partial(self.style),
+                                hide: <Vec<Hide> as crate::partial::Complete>::into_partial(self.hide),
+                            }
+                        }
+
+                        fn visit_docs(
+                            path: &mut Vec<&'static str>,
+                            f: &mut dyn FnMut(&[&'static str], &'static str),
+                        ) {
+                            path.push("style");
+                            f(path, "Sets the backtrace detail level.");
+                            <Style as crate::partial::Complete>::visit_docs(path, f);
+                            path.pop();
+                            path.push("hide");
+                            <Vec<Hide> as crate::partial::Complete>::visit_docs(path, f);
+                            path.pop();
+                        }
+                    }
+                };
+            }
+            no_build
+        }
+    }
+
+    #[test]
+    fn partialize_tuple_struct() {
+        synstructure::test_derive! {
+            partialize_derive {
+                struct Env(pub HashMap<String, String>, bool);
+            }
+            expands to {
+                #[derive(Default, serde::Deserialize, macros::Partial)]
+                #[complete(Env)]
+                #[serde(default)]
+                struct PartialEnv(
+                    pub <HashMap<String, String> as crate::partial::Complete>::Partial,
+                    <bool as crate::partial::Complete>::Partial,
+                );
+
+                const _: () = {
+                    impl crate::partial::Complete for Env {
+                        type Partial = PartialEnv;
+
+                        fn into_partial(self) -> PartialEnv {
+                            PartialEnv {
+                                0: <HashMap<String, String> as crate::partial::Complete>::into_partial(self.0),
+                                1: <bool as crate::partial::Complete>::into_partial(self.1),
+                            }
+                        }
+
+                        fn visit_docs(
+                            path: &mut Vec<&'static str>,
+                            f: &mut dyn FnMut(&[&'static str], &'static str),
+                        ) {
+                            path.push("0");
+                            <HashMap<String, String> as crate::partial::Complete>::visit_docs(path, f);
+                            path.pop();
+                            path.push("1");
+                            <bool as crate::partial::Complete>::visit_docs(path, f);
+                            path.pop();
+                        }
+                    }
+                };
+            }
+            no_build
+        }
+    }
+}
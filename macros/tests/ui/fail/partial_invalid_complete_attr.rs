@@ -0,0 +1,10 @@
+#[path = "../../../../src/partial.rs"]
+mod partial;
+
+#[derive(Default, macros::Partial)]
+#[complete("Config")]
+struct PartialConfig {
+    echo: Option<bool>,
+}
+
+fn main() {}
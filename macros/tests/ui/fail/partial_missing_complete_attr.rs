@@ -0,0 +1,13 @@
+#[path = "../../../../src/partial.rs"]
+mod partial;
+
+struct Config {
+    echo: bool,
+}
+
+#[derive(Default, macros::Partial)]
+struct PartialConfig {
+    echo: Option<bool>,
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+#[path = "../../../../src/partial.rs"]
+mod partial;
+
+#[derive(macros::Partialize)]
+enum Config {
+    Short,
+    Full,
+}
+
+fn main() {}
@@ -0,0 +1,7 @@
+#[path = "../../../../src/partial.rs"]
+mod partial;
+
+#[derive(macros::Partialize)]
+struct Config;
+
+fn main() {}
@@ -0,0 +1,10 @@
+#[path = "../../../../src/partial.rs"]
+mod partial;
+
+#[derive(macros::Partialize)]
+#[partialize(serialize)]
+struct Config {
+    echo: bool,
+}
+
+fn main() {}
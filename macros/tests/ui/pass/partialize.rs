@@ -0,0 +1,60 @@
+#[path = "../../../../src/partial.rs"]
+mod partial;
+
+use macros::{Complete, Partialize};
+use partial::{Collection, Complete, Partial};
+
+#[derive(Partialize, Debug, PartialEq)]
+struct Config {
+    style: Style,
+    hyperlinks: HyperLinks,
+    hide: Vec<String>,
+}
+
+#[derive(Partialize, Debug, PartialEq)]
+struct HyperLinks {
+    enabled: bool,
+    url: String,
+}
+
+#[derive(Clone, Copy, Complete, Default, Debug, PartialEq, serde::Deserialize)]
+enum Style {
+    #[default]
+    Short,
+    Full,
+}
+
+fn main() {
+    let base = Config {
+        style: Style::Full,
+        hyperlinks: HyperLinks {
+            enabled: false,
+            url: "file://".to_string(),
+        },
+        hide: vec!["a".to_string()],
+    };
+    let mut layer = PartialConfig::default();
+    assert!(!layer.is_set());
+
+    layer.hyperlinks.enabled = Some(true);
+    layer.hide = Collection(Some(vec![]));
+    assert!(layer.is_set());
+    assert!(layer.hide.is_set());
+
+    layer.hide = Collection(Some(vec!["b".to_string()]));
+    assert!(layer.hyperlinks.is_set());
+    assert!(!layer.hyperlinks.url.is_set());
+
+    let config = base.into_partial().merge_with(layer).into_complete();
+    assert_eq!(
+        config,
+        Config {
+            style: Style::Full,
+            hyperlinks: HyperLinks {
+                enabled: true,
+                url: "file://".to_string(),
+            },
+            hide: vec!["a".to_string(), "b".to_string()],
+        }
+    );
+}
@@ -0,0 +1,23 @@
+#[path = "../../../../src/partial.rs"]
+mod partial;
+
+use macros::Partialize;
+use partial::Collection;
+
+#[derive(Partialize)]
+#[partialize(derive(Debug, Clone, serde::Serialize))]
+struct Config {
+    echo: bool,
+    env: Vec<String>,
+}
+
+fn main() {
+    let partial = PartialConfig {
+        echo: Some(true),
+        env: Collection(Some(vec![])),
+    };
+    assert_eq!(
+        format!("{:?}", partial.clone()),
+        "PartialConfig { echo: Some(true), env: Collection(Some([])) }"
+    );
+}
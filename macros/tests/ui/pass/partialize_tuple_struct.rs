@@ -0,0 +1,18 @@
+#[path = "../../../../src/partial.rs"]
+mod partial;
+
+use macros::Partialize;
+use partial::{Collection, Complete, Partial};
+
+#[derive(Partialize, Debug, PartialEq)]
+struct Limits(usize, Option<String>, Vec<u32>);
+
+fn main() {
+    let base = Limits(10, None, vec![1]);
+    let layer = PartialLimits(None, Some(Some("x".to_string())), Collection(Some(vec![2])));
+    assert!(layer.is_set());
+    assert!(!layer.0.is_set());
+
+    let limits = base.into_partial().merge_with(layer).into_complete();
+    assert_eq!(limits, Limits(10, Some("x".to_string()), vec![1, 2]));
+}
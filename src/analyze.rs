@@ -0,0 +1,256 @@
+//! Aggregate statistics over a pile of crash logs, e.g., the ones collected by a fuzzing campaign:
+//! how many distinct panics there are, and which messages and innermost frames are the most
+//! common.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use backtracetk::{
+    config::Config,
+    model::{Backtrace, Fingerprint},
+};
+use rayon::prelude::*;
+use serde::Serialize;
+
+/// How many entries of each ranking are shown in the text report.
+const TOP: usize = 10;
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum Format {
+    /// A summary with the most common panics, messages, and frames
+    Text,
+    /// The whole report as JSON
+    Json,
+    /// One row per fingerprint, e.g., to import in a spreadsheet
+    Csv,
+}
+
+#[derive(Serialize)]
+pub struct Report {
+    /// Number of log files read
+    logs: usize,
+    /// Number of log files with at least one panic
+    logs_with_panics: usize,
+    /// Number of panics, including duplicates
+    panics: usize,
+    /// Panics grouped by fingerprint, most frequent first
+    fingerprints: Vec<FingerprintStats>,
+    /// First lines of panic messages, most frequent first
+    messages: Vec<Count>,
+    /// Innermost user frames (see [`Backtrace::top_user_frame`]), most frequent first
+    top_frames: Vec<Count>,
+}
+
+#[derive(Serialize)]
+struct FingerprintStats {
+    fingerprint: Fingerprint,
+    occurrences: usize,
+    /// Number of log files the panic appears in
+    logs: usize,
+    /// The first line of the message of the first occurrence
+    message: Option<String>,
+    /// Where the first occurrence panicked
+    location: Option<String>,
+    /// The function of the innermost user frame of the first occurrence
+    top_frame: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Count {
+    text: String,
+    occurrences: usize,
+}
+
+impl Report {
+    /// Reads the logs in `paths`, where directories are searched recursively for files.
+    pub fn read(paths: &[PathBuf], config: &Config) -> anyhow::Result<Report> {
+        let mut files = vec![];
+        let mut visited = HashSet::new();
+        for path in paths {
+            collect_files(path, &mut files, &mut visited)?;
+        }
+        files.sort();
+        let logs = files
+            .par_iter()
+            .map(|file| {
+                let mut backtraces = backtracetk::parse::parse_file_with_config(file, config)
+                    .with_context(|| format!("failed to read `{}`", file.display()))?;
+                crate::prepare(&mut backtraces, config);
+                Ok(crate::dedupe(backtraces, config))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(Report::new(&logs))
+    }
+
+    /// Builds the report for the backtraces found in each log.
+    fn new(logs: &[Vec<Backtrace>]) -> Report {
+        let mut fingerprints: HashMap<Fingerprint, FingerprintStats> = HashMap::new();
+        let mut messages: HashMap<&str, usize> = HashMap::new();
+        let mut top_frames: HashMap<&str, usize> = HashMap::new();
+        let mut panics = 0;
+        for backtraces in logs {
+            let mut seen = vec![];
+            for backtrace in backtraces {
+                let occurrences = backtrace.duplicates + 1;
+                panics += occurrences;
+                let fingerprint = backtrace.fingerprint();
+                let stats = fingerprints
+                    .entry(fingerprint)
+                    .or_insert_with(|| FingerprintStats::new(fingerprint, backtrace));
+                stats.occurrences += occurrences;
+                if !seen.contains(&fingerprint) {
+                    seen.push(fingerprint);
+                    stats.logs += 1;
+                }
+                let panic_info = backtrace.panic_info.as_ref();
+                if let Some(message) = panic_info.and_then(|p| p.message.first()) {
+                    *messages.entry(message).or_default() += occurrences;
+                }
+                if let Some(frame) = backtrace.top_user_frame() {
+                    *top_frames
+                        .entry(backtracetk::model::strip_hash(&frame.function))
+                        .or_default() += occurrences;
+                }
+            }
+        }
+
+        let mut fingerprints: Vec<_> = fingerprints.into_values().collect();
+        // Ties are broken by fingerprint so the report is stable
+        fingerprints.sort_by_key(|stats| (std::cmp::Reverse(stats.occurrences), stats.fingerprint));
+        Report {
+            logs: logs.len(),
+            logs_with_panics: logs.iter().filter(|log| !log.is_empty()).count(),
+            panics,
+            fingerprints,
+            messages: ranking(messages),
+            top_frames: ranking(top_frames),
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "{} panics in {} of {} logs, {} unique fingerprints",
+            self.panics,
+            self.logs_with_panics,
+            self.logs,
+            self.fingerprints.len()
+        );
+        let _ = writeln!(out, "\nMost common panics:");
+        for stats in self.fingerprints.iter().take(TOP) {
+            let message = stats.message.as_deref().unwrap_or("(no message)");
+            let logs = match stats.logs {
+                1 => "1 log".to_string(),
+                n => format!("{n} logs"),
+            };
+            let _ = writeln!(
+                out,
+                "{:>8}  {}  {message} ({logs})",
+                stats.occurrences, stats.fingerprint
+            );
+            if let Some(location) = &stats.location {
+                let _ = writeln!(out, "{:>8}  at {location}", "");
+            }
+        }
+        for (title, counts) in [
+            ("Most common messages", &self.messages),
+            ("Most common top frames", &self.top_frames),
+        ] {
+            let _ = writeln!(out, "\n{title}:");
+            for count in counts.iter().take(TOP) {
+                let _ = writeln!(out, "{:>8}  {}", count.occurrences, count.text);
+            }
+        }
+        out
+    }
+
+    /// One row per fingerprint, with a header.
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from("fingerprint,occurrences,logs,message,location,top_frame\n");
+        for stats in &self.fingerprints {
+            let _ = writeln!(
+                out,
+                "{},{},{},{},{},{}",
+                stats.fingerprint,
+                stats.occurrences,
+                stats.logs,
+                csv_field(stats.message.as_deref().unwrap_or_default()),
+                csv_field(stats.location.as_deref().unwrap_or_default()),
+                csv_field(stats.top_frame.as_deref().unwrap_or_default()),
+            );
+        }
+        out
+    }
+}
+
+impl FingerprintStats {
+    fn new(fingerprint: Fingerprint, backtrace: &Backtrace) -> FingerprintStats {
+        let panic_info = backtrace.panic_info.as_ref();
+        FingerprintStats {
+            fingerprint,
+            occurrences: 0,
+            logs: 0,
+            message: panic_info.and_then(|p| p.message.first()).cloned(),
+            location: panic_info.map(|p| p.at.trim_end_matches(':').to_string()),
+            top_frame: backtrace
+                .top_user_frame()
+                .map(|frame| backtracetk::model::strip_hash(&frame.function).to_string()),
+        }
+    }
+}
+
+/// Sorts `counts` from the most to the least frequent.
+fn ranking(counts: HashMap<&str, usize>) -> Vec<Count> {
+    let mut counts: Vec<_> = counts
+        .into_iter()
+        .map(|(text, occurrences)| Count {
+            text: text.to_string(),
+            occurrences,
+        })
+        .collect();
+    counts.sort_by(|a, b| b.occurrences.cmp(&a.occurrences).then(a.text.cmp(&b.text)));
+    counts
+}
+
+/// Adds `path` to `files` if it's a file, or the files inside it if it's a directory. Symlinks are
+/// followed, but a directory already in `visited` (by its canonical path) isn't searched again, so
+/// symlink loops end.
+fn collect_files(
+    path: &Path,
+    files: &mut Vec<PathBuf>,
+    visited: &mut HashSet<PathBuf>,
+) -> anyhow::Result<()> {
+    let metadata =
+        fs::metadata(path).with_context(|| format!("failed to read `{}`", path.display()))?;
+    if !metadata.is_dir() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+    let canonical = path
+        .canonicalize()
+        .with_context(|| format!("failed to read `{}`", path.display()))?;
+    if !visited.insert(canonical) {
+        return Ok(());
+    }
+    let entries =
+        fs::read_dir(path).with_context(|| format!("failed to read `{}`", path.display()))?;
+    for entry in entries {
+        collect_files(&entry?.path(), files, visited)?;
+    }
+    Ok(())
+}
+
+/// Quotes a CSV field if needed, see RFC 4180.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
@@ -0,0 +1,185 @@
+//! Posting the backtraces found in a CI run as review comments on a GitHub pull request, see
+//! `--annotate-pr`.
+//!
+//! Each panic is posted once, on the line of its innermost frame in the workspace, so it shows up
+//! right on the diff. Comments are posted with the GitHub CLI (`gh api`), which is installed on
+//! GitHub-hosted runners and reads the credentials from `GITHUB_TOKEN`. The pull request is read
+//! from the event that triggered the workflow (`GITHUB_EVENT_PATH`). GitHub rejects review comments
+//! on lines that aren't part of the diff, those backtraces are posted as comments on the pull
+//! request instead.
+
+use std::{
+    env, fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use anyhow::{bail, Context};
+use backtracetk::{
+    config::Config,
+    filter::Filters,
+    model::{Backtrace, Frame},
+};
+use serde::Deserialize;
+
+/// The pull request a workflow runs for.
+pub struct PullRequest {
+    /// The repository as `owner/name`
+    repository: String,
+    number: u64,
+    /// The commit at the head of the pull request, which comments refer to
+    head_sha: String,
+    /// Where the repository is checked out, file paths in comments are relative to it
+    workspace: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct Event {
+    pull_request: Option<EventPullRequest>,
+}
+
+#[derive(Deserialize)]
+struct EventPullRequest {
+    number: u64,
+    head: Head,
+}
+
+#[derive(Deserialize)]
+struct Head {
+    sha: String,
+}
+
+impl PullRequest {
+    /// Reads the pull request from the environment of a GitHub Actions workflow. Returns `None` if
+    /// the workflow wasn't triggered by a pull request, e.g., on a push.
+    pub fn from_env() -> anyhow::Result<Option<PullRequest>> {
+        let (Ok(repository), Ok(event_path)) =
+            (env::var("GITHUB_REPOSITORY"), env::var("GITHUB_EVENT_PATH"))
+        else {
+            bail!(
+                "`--annotate-pr` needs `GITHUB_REPOSITORY` and `GITHUB_EVENT_PATH`, which are set \
+                 in GitHub Actions"
+            );
+        };
+        let contents = fs::read_to_string(&event_path)
+            .with_context(|| format!("failed to read `{event_path}`"))?;
+        let event: Event = serde_json::from_str(&contents)
+            .with_context(|| format!("failed to parse `{event_path}`"))?;
+        let Some(pull_request) = event.pull_request else {
+            return Ok(None);
+        };
+        let workspace = match env::var_os("GITHUB_WORKSPACE") {
+            Some(workspace) => PathBuf::from(workspace),
+            None => env::current_dir()?,
+        };
+        Ok(Some(PullRequest {
+            repository,
+            number: pull_request.number,
+            head_sha: pull_request.head.sha,
+            workspace: workspace.canonicalize().unwrap_or(workspace),
+        }))
+    }
+
+    /// Posts a comment for each distinct panic in `backtraces`.
+    pub fn annotate(&self, backtraces: &[Backtrace], config: &Config) -> anyhow::Result<()> {
+        let mut posted = vec![];
+        for backtrace in backtraces {
+            let fingerprint = backtrace.fingerprint();
+            if posted.contains(&fingerprint) {
+                continue;
+            }
+            posted.push(fingerprint);
+            self.post(backtrace, config)?;
+        }
+        Ok(())
+    }
+
+    fn post(&self, backtrace: &Backtrace, config: &Config) -> anyhow::Result<()> {
+        let body = to_markdown(backtrace, config)?;
+        let located = backtrace
+            .frames
+            .iter()
+            .filter(|frame| frame.is_user_frame())
+            .find_map(|frame| Some((self.relative_path(frame)?, frame)));
+        if let Some((path, frame)) = located {
+            let comment = serde_json::json!({
+                "body": body,
+                "commit_id": self.head_sha,
+                "path": path,
+                "line": frame.source_info.as_ref().map(|source_info| source_info.lineno),
+                "side": "RIGHT",
+            });
+            let endpoint = format!("repos/{}/pulls/{}/comments", self.repository, self.number);
+            if gh_api(&endpoint, &comment).is_ok() {
+                return Ok(());
+            }
+        }
+        let comment = serde_json::json!({ "body": body });
+        let endpoint = format!("repos/{}/issues/{}/comments", self.repository, self.number);
+        gh_api(&endpoint, &comment)
+    }
+
+    /// The path of the frame's file relative to the workspace, if it's inside it.
+    fn relative_path(&self, frame: &Frame) -> Option<String> {
+        let file = Path::new(&*frame.source_info.as_ref()?.file);
+        let file = file.canonicalize().ok()?;
+        let relative = file.strip_prefix(&self.workspace).ok()?;
+        // GitHub expects forward slashes even on Windows
+        let components: Vec<_> = relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect();
+        Some(components.join("/"))
+    }
+}
+
+/// The body of the comment: the panic message followed by the rendered backtrace, collapsed.
+fn to_markdown(backtrace: &Backtrace, config: &Config) -> anyhow::Result<String> {
+    let mut rendered = vec![];
+    backtrace.render_to(&mut rendered, config, &mut Filters::from_config(config))?;
+    let rendered = anstream::adapter::strip_str(&String::from_utf8_lossy(&rendered)).to_string();
+    let rendered = rendered.trim_matches('\n');
+
+    let mut markdown = String::new();
+    if let Some(panic_info) = &backtrace.panic_info {
+        let at = panic_info.at.trim_end_matches(':');
+        markdown.push_str(&format!("**Panicked at `{at}`**\n\n"));
+        let message = panic_info.message.join("\n");
+        let fence = fence(&message);
+        markdown.push_str(&format!("{fence}text\n{message}\n{fence}\n\n"));
+    }
+    let fence = fence(rendered);
+    markdown.push_str(&format!(
+        "<details><summary>Backtrace</summary>\n\n{fence}text\n{rendered}\n{fence}\n\n</details>\n"
+    ));
+    Ok(markdown)
+}
+
+/// A code fence longer than any run of backticks in `text`.
+fn fence(text: &str) -> String {
+    let longest = text.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    "`".repeat(longest.max(2) + 1)
+}
+
+/// Sends `body` to the GitHub API with `gh api`.
+fn gh_api(endpoint: &str, body: &serde_json::Value) -> anyhow::Result<()> {
+    let mut child = Command::new("gh")
+        .args(["api", "--method", "POST", endpoint, "--input", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("failed to run `gh`, the GitHub CLI")?;
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(body.to_string().as_bytes())?;
+    drop(stdin);
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        bail!(
+            "`gh api {endpoint}` failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
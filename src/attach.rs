@@ -0,0 +1,125 @@
+//! Rendering the panics of a process started outside backtracetk, see `backtracetk attach`. We
+//! can't intercept the output of a running process, so it must already go somewhere we can read:
+//! a log file, which is followed like with `backtracetk follow`, or a named pipe, which we become
+//! the reader of. On Linux, the stderr of the process is found in `/proc/<pid>/fd/2`.
+
+use std::{
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    sync::mpsc::RecvTimeoutError,
+};
+
+use anyhow::Context;
+use backtracetk::{
+    config::Config,
+    parse::{LineKind, Parser},
+};
+
+use crate::{capture, follow, output, process_context};
+
+/// Renders the backtraces printed by the process with id `pid` to `from`, or to its stderr if
+/// `from` isn't set, until interrupted, until the pipe is closed, or until the process exits.
+pub fn run(
+    pid: u32,
+    from: Option<&Path>,
+    config: &Config,
+    format: output::Format,
+    out: &mut impl io::Write,
+) -> anyhow::Result<()> {
+    if format.is_report() {
+        anyhow::bail!("a report can't be written when attached to a process, since it never ends");
+    }
+    let path = match from {
+        Some(path) => path.to_path_buf(),
+        None => stderr_path(pid)?,
+    };
+    if is_fifo(&path) {
+        read_pipe(&path, pid, config, format, out)
+    } else {
+        follow::run(&path, false, Some(pid), true, config, format, out)
+    }
+}
+
+/// The path to read the stderr of the process from. The file descriptor itself is followed rather
+/// than the file it points to, so we keep reading what the process writes after logs are rotated.
+#[cfg(target_os = "linux")]
+fn stderr_path(pid: u32) -> anyhow::Result<PathBuf> {
+    let fd = PathBuf::from(format!("/proc/{pid}/fd/2"));
+    let target = fs::read_link(&fd)
+        .with_context(|| format!("failed to inspect the stderr of process {pid}"))?;
+    let target = target.to_string_lossy();
+    // Anonymous pipes and sockets, e.g., `pipe:[48213]`, and terminals can't be read without
+    // taking the output away from whoever reads it
+    if !target.starts_with('/') || target.starts_with("/dev/") {
+        anyhow::bail!(
+            "the stderr of process {pid} is `{target}`, which can't be read; redirect it to a log \
+             file or a named pipe and pass it with `--from`"
+        );
+    }
+    Ok(fd)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn stderr_path(pid: u32) -> anyhow::Result<PathBuf> {
+    anyhow::bail!(
+        "the stderr of process {pid} can only be found on Linux; pass the log file or named pipe \
+         it writes to with `--from`"
+    )
+}
+
+#[cfg(unix)]
+fn is_fifo(path: &Path) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+
+    fs::metadata(path).is_ok_and(|metadata| metadata.file_type().is_fifo())
+}
+
+#[cfg(not(unix))]
+fn is_fifo(_: &Path) -> bool {
+    false
+}
+
+/// Reads the lines written to a named pipe until every writer closes it. Since nobody else sees the
+/// output, lines are echoed to stderr like when running a command. Like when following a file, the
+/// backtrace in progress is rendered once no lines are written for a while.
+fn read_pipe(
+    path: &Path,
+    pid: u32,
+    config: &Config,
+    format: output::Format,
+    out: &mut impl io::Write,
+) -> anyhow::Result<()> {
+    let file = File::open(path).with_context(|| format!("failed to read `{}`", path.display()))?;
+    let lines = capture::capture(file);
+    let mut parser = Parser::from_config(config);
+    // Whether the last line was part of a backtrace that isn't complete yet
+    let mut pending = false;
+    // The state captured when the last panic was detected
+    let mut process = None;
+    loop {
+        let line = match lines.recv_timeout(follow::IDLE_TIMEOUT) {
+            Ok(line) => line?,
+            Err(RecvTimeoutError::Timeout) => {
+                if std::mem::take(&mut pending) {
+                    follow::flush(&mut parser, &mut process, Some(pid), config, format, out)?;
+                }
+                continue;
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        if bool::from(config.echo) {
+            anstream::eprintln!("{line}");
+        }
+        let kind = parser.check_line(&line);
+        pending = !matches!(kind, LineKind::Unrecognized | LineKind::Ignored);
+        if let LineKind::Panic = kind {
+            process = process_context::read(pid);
+        }
+        let mut backtraces = parser.take_backtraces();
+        follow::attach_process(&mut backtraces, &mut process, Some(pid));
+        follow::render(backtraces, config, format, out)?;
+    }
+    follow::flush(&mut parser, &mut process, Some(pid), config, format, out)?;
+    Ok(())
+}
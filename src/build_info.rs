@@ -0,0 +1,80 @@
+//! Extracting [`BuildInfo`] from the binary of the command when it's a local binary, e.g.,
+//! `./target/debug/app`. Commands looked up in `PATH` aren't inspected since they are usually not
+//! the program that panics (e.g., `cargo run`).
+//!
+//! The build ID comes from the GNU build ID note in ELF binaries and the UUID in Mach-O binaries.
+//! The rustc version comes from the `.comment` section, where rustc records it as
+//! `rustc version 1.79.0 (129f3b996 2024-06-10)`. Reading them requires the `symbolize` feature,
+//! without it only the modification time of the binary is known.
+
+use std::{fs::File, path::Path, time::SystemTime};
+
+use backtracetk::model::BuildInfo;
+
+pub struct Binary {
+    pub info: BuildInfo,
+    /// When the binary was last modified. Source files modified later may not match the build.
+    pub modified: SystemTime,
+}
+
+/// Reads the build info of `program`, or returns `None` if it's not a path to a binary.
+pub fn read(program: &str) -> Option<Binary> {
+    let path = Path::new(program);
+    // Programs without a directory are looked up in `PATH`
+    if path.components().count() < 2 {
+        return None;
+    }
+    let file = File::open(path).ok()?;
+    let modified = file.metadata().ok()?.modified().ok()?;
+    let info = read_info(&file)?;
+    Some(Binary { info, modified })
+}
+
+#[cfg(feature = "symbolize")]
+fn read_info(file: &File) -> Option<BuildInfo> {
+    use object::{Object, ObjectSection};
+
+    // SAFETY: the map is only read while extracting the info. If the binary is replaced
+    // concurrently (e.g., by a rebuild) the file we mapped is not modified.
+    let mmap = unsafe { memmap2::Mmap::map(file).ok()? };
+    let object = object::File::parse(&*mmap).ok()?;
+
+    let build_id = match object.build_id() {
+        Ok(Some(build_id)) => Some(hex(build_id)),
+        _ => object.mach_uuid().ok().flatten().map(|uuid| hex(&uuid)),
+    };
+    let rustc = object
+        .section_by_name(".comment")
+        .and_then(|section| section.data().ok())
+        .and_then(rustc_version);
+    Some(BuildInfo { build_id, rustc })
+}
+
+#[cfg(not(feature = "symbolize"))]
+fn read_info(_: &File) -> Option<BuildInfo> {
+    Some(BuildInfo {
+        build_id: None,
+        rustc: None,
+    })
+}
+
+/// Finds the rustc version in the contents of the `.comment` section, which is a list of
+/// nul-terminated strings, one for each tool that contributed to the binary.
+#[cfg(feature = "symbolize")]
+fn rustc_version(comment: &[u8]) -> Option<String> {
+    comment
+        .split(|&b| b == 0)
+        .filter_map(|entry| std::str::from_utf8(entry).ok())
+        .find_map(|entry| entry.strip_prefix("rustc version "))
+        .map(str::to_string)
+}
+
+#[cfg(feature = "symbolize")]
+fn hex(bytes: &[u8]) -> String {
+    use std::fmt::Write;
+
+    bytes.iter().fold(String::new(), |mut s, b| {
+        let _ = write!(s, "{b:02x}");
+        s
+    })
+}
@@ -0,0 +1,257 @@
+//! Constructing backtraces programmatically, e.g., when converting the output of another tool,
+//! while enforcing the invariants the renderer relies on.
+
+use anyhow::bail;
+
+use crate::{Backtrace, Frame, PanicInfo, SourceInfo};
+
+/// Builds a [`Backtrace`] frame by frame. The renderer relies on frames being ordered innermost
+/// first with non-decreasing numbers, where an inlined frame has the number of the frame it's
+/// inlined into. Frames are numbered by the builder unless the input reports their numbers, in which
+/// case the numbers are validated.
+///
+/// Inputs reporting frames outermost first (e.g., the steps of a trace in the order they were
+/// executed) use [`BacktraceBuilder::outermost_first`], frames are reversed and numbered when the
+/// backtrace is built.
+///
+/// ```
+/// # use backtracetk::model::{BacktraceBuilder, SourceInfo};
+/// let mut builder = BacktraceBuilder::new();
+/// let source_info = SourceInfo {
+///     file: "src/main.rs".into(),
+///     lineno: 3,
+///     colno: Some(5),
+/// };
+/// builder.frame("app::parse", None);
+/// builder.inlined_frame("app::parse::inner", None);
+/// builder.frame("app::main", Some(source_info));
+/// assert!(builder.numbered_frame(0, "app::run", None).is_err());
+/// let backtrace = builder.build().unwrap();
+/// assert_eq!(backtrace.frames[2].frameno, 1);
+/// ```
+#[derive(Default)]
+pub struct BacktraceBuilder {
+    frames: Vec<Frame>,
+    outermost_first: bool,
+    panic_info: Option<PanicInfo>,
+    time: Option<String>,
+}
+
+impl BacktraceBuilder {
+    /// A builder for frames added innermost first, as printed by the standard library.
+    pub fn new() -> BacktraceBuilder {
+        BacktraceBuilder::default()
+    }
+
+    /// A builder for frames added outermost first.
+    pub fn outermost_first() -> BacktraceBuilder {
+        BacktraceBuilder {
+            outermost_first: true,
+            ..BacktraceBuilder::default()
+        }
+    }
+
+    /// Adds a frame numbered after the last one.
+    pub fn frame(
+        &mut self,
+        function: impl Into<String>,
+        source_info: Option<SourceInfo>,
+    ) -> &mut Frame {
+        let frameno = self
+            .frames
+            .last()
+            .map_or(0, |frame| frame.frameno.saturating_add(1));
+        self.push(frameno, function.into(), source_info)
+    }
+
+    /// Adds a frame with the number reported by the input, which must not be lower than the number
+    /// of the last frame.
+    pub fn numbered_frame(
+        &mut self,
+        frameno: u32,
+        function: impl Into<String>,
+        source_info: Option<SourceInfo>,
+    ) -> anyhow::Result<&mut Frame> {
+        if self.outermost_first {
+            bail!("frames added outermost first are numbered when the backtrace is built");
+        }
+        if let Some(last) = self.frames.last() {
+            if frameno < last.frameno {
+                bail!("frame {frameno} can't follow frame {}", last.frameno);
+            }
+        }
+        Ok(self.push(frameno, function.into(), source_info))
+    }
+
+    /// Adds a frame with the number printed in a log, which isn't validated since a log is rendered
+    /// as it was printed, e.g., with the numbers restarting when the output of several runs was
+    /// concatenated without a `stack backtrace:` line in between.
+    pub(crate) fn printed_frame(
+        &mut self,
+        frameno: u32,
+        function: impl Into<String>,
+        source_info: Option<SourceInfo>,
+    ) -> &mut Frame {
+        self.push(frameno, function.into(), source_info)
+    }
+
+    /// Adds a frame inlined into the last one, i.e., with the same number. Returns `None` if there
+    /// are no frames yet.
+    pub fn inlined_frame(
+        &mut self,
+        function: impl Into<String>,
+        source_info: Option<SourceInfo>,
+    ) -> Option<&mut Frame> {
+        let frameno = self.frames.last()?.frameno;
+        Some(self.push(frameno, function.into(), source_info))
+    }
+
+    fn push(
+        &mut self,
+        frameno: u32,
+        function: String,
+        source_info: Option<SourceInfo>,
+    ) -> &mut Frame {
+        self.frames.push(Frame {
+            function,
+            frameno,
+            source_info,
+            foreign: false,
+            annotations: vec![],
+            hidden: false,
+            raw: vec![],
+        });
+        self.frames.last_mut().unwrap()
+    }
+
+    /// The last frame added, e.g., to fill in its location when it's reported in a separate line.
+    pub fn last_frame(&self) -> Option<&Frame> {
+        self.frames.last()
+    }
+
+    pub fn last_frame_mut(&mut self) -> Option<&mut Frame> {
+        self.frames.last_mut()
+    }
+
+    /// The number of frames added so far.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn panic_info(&mut self, panic_info: Option<PanicInfo>) -> &mut BacktraceBuilder {
+        self.panic_info = panic_info;
+        self
+    }
+
+    pub fn time(&mut self, time: Option<String>) -> &mut BacktraceBuilder {
+        self.time = time;
+        self
+    }
+
+    /// Builds the backtrace, or returns `None` if there are no frames since there would be nothing
+    /// to render.
+    pub fn build(self) -> Option<Backtrace> {
+        if self.frames.is_empty() {
+            return None;
+        }
+        Some(self.build_frameless())
+    }
+
+    /// Builds the backtrace even if there are no frames, e.g., for an error report printed without
+    /// one, which still has a message and maybe a span trace.
+    pub(crate) fn build_frameless(self) -> Backtrace {
+        let mut frames = self.frames;
+        if self.outermost_first {
+            frames.reverse();
+            for (frameno, frame) in frames.iter_mut().enumerate() {
+                frame.frameno = frameno as u32;
+            }
+        }
+        let mut panic_info = self.panic_info;
+        if let Some(panic_info) = &mut panic_info {
+            panic_info.locate();
+        }
+        Backtrace {
+            frames,
+            panic_info,
+            duplicates: 0,
+            command: None,
+            test: None,
+            time: self.time,
+            built_before: None,
+            build: None,
+            process: None,
+            spans: vec![],
+            span_trace: vec![],
+            diagnostics: vec![],
+            note: None,
+            trailer: vec![],
+            raw_header: vec![],
+            raw_footer: vec![],
+            report_file: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BacktraceBuilder;
+    use crate::parse::Parser;
+
+    fn framenos(builder: BacktraceBuilder) -> Vec<u32> {
+        let backtrace = builder.build().unwrap();
+        backtrace.frames.iter().map(|frame| frame.frameno).collect()
+    }
+
+    #[test]
+    fn numbering() {
+        let mut builder = BacktraceBuilder::new();
+        assert!(builder.inlined_frame("app::inner", None).is_none());
+        builder.frame("app::parse", None);
+        builder.inlined_frame("app::parse::inner", None);
+        builder.numbered_frame(3, "app::run", None).unwrap();
+        builder.frame("app::main", None);
+        assert_eq!(framenos(builder), [0, 0, 3, 4]);
+
+        let mut builder = BacktraceBuilder::new();
+        builder.numbered_frame(2, "app::run", None).unwrap();
+        let err = builder.numbered_frame(1, "app::main", None).err().unwrap();
+        assert_eq!(err.to_string(), "frame 1 can't follow frame 2");
+        assert!(BacktraceBuilder::new().build().is_none());
+    }
+
+    #[test]
+    fn outermost_first() {
+        let mut builder = BacktraceBuilder::outermost_first();
+        builder.frame("app::main", None);
+        builder.frame("app::run", None);
+        assert!(builder.numbered_frame(5, "app::parse", None).is_err());
+        let backtrace = builder.build().unwrap();
+        assert_eq!(backtrace.frames[0].function, "app::run");
+        assert_eq!(backtrace.frames[0].frameno, 0);
+        assert_eq!(backtrace.frames[1].function, "app::main");
+        assert_eq!(backtrace.frames[1].frameno, 1);
+    }
+
+    /// Frames are kept as printed in a log, even when their numbers go backwards.
+    #[test]
+    fn printed_numbers_going_backwards() {
+        let mut parser = Parser::new();
+        for line in [
+            "stack backtrace:",
+            "   0: app::run",
+            "   1: app::main",
+            "   0: app::other",
+        ] {
+            parser.parse_line(line);
+        }
+        let backtraces = parser.into_backtraces();
+        assert_eq!(backtraces.len(), 1);
+        let framenos: Vec<_> = backtraces[0].frames.iter().map(|f| f.frameno).collect();
+        assert_eq!(framenos, [0, 1, 0]);
+    }
+}
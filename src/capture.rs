@@ -0,0 +1,266 @@
+//! Reading the output of the child process.
+
+#[cfg(unix)]
+use std::os::fd::{AsFd, AsRawFd};
+use std::{
+    io::{self, BufRead, BufReader, Read},
+    sync::mpsc::{self, Receiver, SyncSender},
+    thread,
+};
+
+/// Maximum number of lines buffered between the capture thread and the consumer. The child only
+/// blocks on its output if we fall this far behind, which bounds the memory we use.
+pub const BUFFER_LINES: usize = 64 * 1024;
+
+/// Lines longer than this are split, so a program writing without newlines can't make us buffer its
+/// whole output. The parts are echoed as separate lines, except with [`capture_streams`].
+pub const MAX_LINE_BYTES: usize = 1024 * 1024;
+
+/// Reads lines from `reader` on a dedicated thread, so the child can keep writing while we echo and
+/// render. The thread stops when the reader is exhausted or the receiver is dropped.
+///
+/// Lines are decoded lossily: invalid UTF-8 is replaced with `U+FFFD` instead of aborting the
+/// capture, since binary output shouldn't prevent us from collecting backtraces.
+pub fn capture(reader: impl Read + Send + 'static) -> Receiver<io::Result<String>> {
+    let (tx, rx) = mpsc::sync_channel(BUFFER_LINES);
+    spawn_reader(reader, tx, decode_line);
+    rx
+}
+
+/// An output stream of the child.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// A line read by [`capture_streams`], including the line terminator.
+pub struct Line {
+    pub stream: Stream,
+    pub bytes: Vec<u8>,
+}
+
+/// A stream that can be captured by [`capture_streams`]. On Unix, it must be backed by a file
+/// descriptor so it can be polled.
+#[cfg(unix)]
+pub trait Pipe: Read + AsFd + Send + 'static {}
+
+#[cfg(unix)]
+impl<T: Read + AsFd + Send + 'static> Pipe for T {}
+
+/// A stream that can be captured by [`capture_streams`].
+#[cfg(not(unix))]
+pub trait Pipe: Read + Send + 'static {}
+
+#[cfg(not(unix))]
+impl<T: Read + Send + 'static> Pipe for T {}
+
+/// Like [`capture`] but reading several streams at once, e.g., the stdout and stderr of the child,
+/// and sending lines as they were read, including the line terminator, to echo them verbatim. Use
+/// [`decode_line`] to get the text of a line. Lines are sent in the order they were read, so output
+/// spanning both streams (e.g., a banner printed by a test harness on stdout before a panic on
+/// stderr) is echoed in the order it was written.
+///
+/// On Unix, the streams are polled from a single thread, so a line is sent as soon as it's
+/// complete. Elsewhere, each stream is read on its own thread and lines are only ordered as well as
+/// the threads are scheduled.
+pub fn capture_streams(pipes: Vec<(Stream, Box<dyn Pipe>)>) -> Receiver<io::Result<Line>> {
+    let (tx, rx) = mpsc::sync_channel(BUFFER_LINES);
+    #[cfg(unix)]
+    spawn_poller(pipes, tx);
+    #[cfg(not(unix))]
+    for (stream, pipe) in pipes {
+        spawn_reader(pipe, tx.clone(), move |buf| Line {
+            stream,
+            bytes: buf.to_vec(),
+        });
+    }
+    rx
+}
+
+/// Reads `pipes` as they become readable, sending every complete line to `tx`. The thread stops
+/// when every pipe is exhausted or the receiver is dropped.
+#[cfg(unix)]
+fn spawn_poller(mut pipes: Vec<(Stream, Box<dyn Pipe>)>, tx: SyncSender<io::Result<Line>>) {
+    thread::spawn(move || {
+        let send = |stream, bytes| tx.send(Ok(Line { stream, bytes })).is_ok();
+        // The partial line read from each pipe, `None` once the pipe is exhausted
+        let mut pending: Vec<Option<Vec<u8>>> = vec![Some(vec![]); pipes.len()];
+        let mut chunk = vec![0; 64 * 1024];
+        while pending.iter().any(Option::is_some) {
+            let mut fds: Vec<_> = pipes
+                .iter()
+                .zip(&pending)
+                .map(|((_, pipe), pending)| libc::pollfd {
+                    // Negative descriptors are ignored
+                    fd: if pending.is_some() {
+                        pipe.as_fd().as_raw_fd()
+                    } else {
+                        -1
+                    },
+                    events: libc::POLLIN,
+                    revents: 0,
+                })
+                .collect();
+            // SAFETY: `fds` points to `fds.len()` initialized `pollfd`s
+            if unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, -1) } < 0 {
+                let err = io::Error::last_os_error();
+                if err.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                let _ = tx.send(Err(err));
+                return;
+            }
+            for (i, fd) in fds.iter().enumerate() {
+                let Some(buf) = pending[i].as_mut().filter(|_| fd.revents != 0) else {
+                    continue;
+                };
+                let (stream, pipe) = &mut pipes[i];
+                let n = match pipe.read(&mut chunk) {
+                    Ok(n) => n,
+                    Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(err) => {
+                        let _ = tx.send(Err(err));
+                        return;
+                    }
+                };
+                buf.extend_from_slice(&chunk[..n]);
+                while let Some(end) = line_end(buf, n == 0) {
+                    if !send(*stream, buf.drain(..end).collect()) {
+                        return;
+                    }
+                }
+                if n == 0 {
+                    pending[i] = None;
+                }
+            }
+        }
+    });
+}
+
+/// The end of the first line in `buf`, splitting lines longer than [`MAX_LINE_BYTES`]. At the end
+/// of the stream, the rest of `buf` is a line even without a terminator.
+#[cfg(unix)]
+fn line_end(buf: &[u8], eof: bool) -> Option<usize> {
+    let limit = buf.len().min(MAX_LINE_BYTES);
+    match buf[..limit].iter().position(|&b| b == b'\n') {
+        Some(i) => Some(i + 1),
+        None if limit == MAX_LINE_BYTES || (eof && !buf.is_empty()) => Some(limit),
+        None => None,
+    }
+}
+
+fn spawn_reader<T: Send + 'static>(
+    reader: impl Read + Send + 'static,
+    tx: SyncSender<io::Result<T>>,
+    f: impl Fn(&[u8]) -> T + Send + 'static,
+) {
+    thread::spawn(move || {
+        let mut reader = BufReader::new(reader);
+        let mut buf = vec![];
+        loop {
+            buf.clear();
+            let mut limited = (&mut reader).take(MAX_LINE_BYTES as u64);
+            let line = match limited.read_until(b'\n', &mut buf) {
+                Ok(0) => break,
+                Ok(_) => Ok(f(&buf)),
+                Err(err) => Err(err),
+            };
+            let failed = line.is_err();
+            if tx.send(line).is_err() || failed {
+                break;
+            }
+        }
+    });
+}
+
+/// Decodes a line read with [`BufRead::read_until`] stripping the line terminator like
+/// [`BufRead::lines`] does. Every `\r` before the `\n` is stripped, since Windows programs may
+/// write `\r\r\n` when their `\r\n` is translated again. Lines written in UTF-16 (see
+/// [`decode_utf16_line`]) are decoded as such.
+pub fn decode_line(buf: &[u8]) -> String {
+    let line = buf.strip_suffix(b"\n").unwrap_or(buf);
+    if let Some(line) = decode_utf16_line(line) {
+        return line;
+    }
+    let end = line.iter().rposition(|b| *b != b'\r').map_or(0, |i| i + 1);
+    String::from_utf8_lossy(&line[..end]).into_owned()
+}
+
+/// Decodes a line written in UTF-16LE, e.g., by a Windows program whose console output was
+/// redirected, returning `None` if it doesn't look like one, i.e., if it doesn't start with a byte
+/// order mark and fewer than three quarters of its code units have a NUL high byte. UTF-8 lines
+/// that merely contain a NUL byte aren't taken for UTF-16. Splitting the text at `\n` leaves the
+/// NUL byte of the newline at the start of the next line.
+fn decode_utf16_line(line: &[u8]) -> Option<String> {
+    if !line.contains(&0) {
+        return None;
+    }
+    let line = line.strip_prefix(b"\0").unwrap_or(line);
+    let (line, bom) = match line.strip_prefix(b"\xff\xfe") {
+        Some(line) => (line, true),
+        None => (line, false),
+    };
+    if !line.len().is_multiple_of(2) {
+        return None;
+    }
+    let units: Vec<u16> = line
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+        .collect();
+    let latin1 = units.iter().filter(|unit| **unit <= 0xff).count();
+    if !bom && latin1 * 4 < units.len() * 3 {
+        return None;
+    }
+    let line: String = char::decode_utf16(units)
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect();
+    Some(line.trim_end_matches('\r').to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decode_line;
+
+    /// Splits `text` in lines like [`BufRead::read_until`](std::io::BufRead::read_until) and
+    /// decodes them.
+    fn decode_lines(text: &[u8]) -> Vec<String> {
+        text.split_inclusive(|b| *b == b'\n')
+            .map(decode_line)
+            .collect()
+    }
+
+    fn utf16(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+    }
+
+    #[test]
+    fn utf8_with_nul() {
+        assert_eq!(decode_line(b"abc\0\n"), "abc\0");
+        assert_eq!(decode_line(b"\0abc\n"), "\0abc");
+        assert_eq!(decode_line(b"key=\0value\0\n"), "key=\0value\0");
+    }
+
+    #[test]
+    fn line_terminators() {
+        assert_eq!(decode_lines(b"abc\r\ndef\n"), ["abc", "def"]);
+        assert_eq!(decode_lines(b"abc\r\r\ndef"), ["abc", "def"]);
+        assert_eq!(decode_line(b"\r\n"), "");
+    }
+
+    #[test]
+    fn utf16_split_at_newline() {
+        let text = utf16("thread 'main' panicked at src/main.rs:1:5:\r\nboom\r\n");
+        assert_eq!(
+            decode_lines(&text),
+            ["thread 'main' panicked at src/main.rs:1:5:", "boom", ""]
+        );
+    }
+
+    #[test]
+    fn utf16_with_bom() {
+        let mut text = b"\xff\xfe".to_vec();
+        text.extend(utf16("путь\r\nnext\r\n"));
+        assert_eq!(decode_lines(&text), ["путь", "next", ""]);
+    }
+}
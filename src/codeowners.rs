@@ -0,0 +1,125 @@
+//! Reading `CODEOWNERS` files to find who owns the code where a panic happened.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use regex::Regex;
+
+/// The rules in a `CODEOWNERS` file. Patterns follow the `.gitignore` syntax and the last matching
+/// rule takes precedence.
+pub struct CodeOwners {
+    /// The root of the repository, paths are matched relative to it.
+    root: PathBuf,
+    rules: Vec<Rule>,
+}
+
+struct Rule {
+    pattern: Regex,
+    owners: Vec<String>,
+}
+
+impl CodeOwners {
+    /// Finds the `CODEOWNERS` file of the repository containing `dir`. Looks in the `.github/`
+    /// and `docs/` directories and at the root of every ancestor of `dir`.
+    pub fn find(dir: &Path) -> Option<PathBuf> {
+        dir.ancestors().find_map(|ancestor| {
+            ["CODEOWNERS", ".github/CODEOWNERS", "docs/CODEOWNERS"]
+                .iter()
+                .map(|candidate| ancestor.join(candidate))
+                .find(|path| path.exists())
+        })
+    }
+
+    /// Reads the `CODEOWNERS` file at `path`. The root of the repository is the directory
+    /// containing the file, or its parent if the file is inside `.github/` or `docs/`. It's
+    /// canonicalized, so absolute paths are matched even if `path` is relative or goes through a
+    /// symlink.
+    pub fn read(path: &Path) -> anyhow::Result<CodeOwners> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("failed to read `{}`", path.display()))?;
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        let mut root = path.parent().unwrap_or(Path::new("."));
+        if root.ends_with(".github") || root.ends_with("docs") {
+            root = root.parent().unwrap_or(root);
+        }
+        CodeOwners::parse(root, &contents)
+    }
+
+    pub fn parse(root: &Path, contents: &str) -> anyhow::Result<CodeOwners> {
+        let mut rules = vec![];
+        for line in contents.lines() {
+            let line = line.split_once('#').map_or(line, |(line, _)| line).trim();
+            let mut words = line.split_whitespace();
+            let Some(pattern) = words.next() else {
+                continue;
+            };
+            rules.push(Rule {
+                pattern: Regex::new(&glob_to_regex(pattern))
+                    .with_context(|| format!("invalid pattern `{pattern}`"))?,
+                owners: words.map(str::to_string).collect(),
+            });
+        }
+        Ok(CodeOwners {
+            root: root.to_path_buf(),
+            rules,
+        })
+    }
+
+    /// The owners of the file at `path`, which can be absolute or relative to the root of the
+    /// repository. An absolute path outside the root is canonicalized, in case it goes through a
+    /// symlink. Returns an empty slice if there are no owners.
+    pub fn owners(&self, path: &Path) -> &[String] {
+        let canonical;
+        let path = match path.strip_prefix(&self.root) {
+            Ok(relative) => relative,
+            Err(_) if path.is_absolute() => {
+                canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+                canonical.strip_prefix(&self.root).unwrap_or(path)
+            }
+            Err(_) => path,
+        };
+        let path = path.strip_prefix("./").unwrap_or(path);
+        let path = path.to_string_lossy().replace('\\', "/");
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| rule.pattern.is_match(&path))
+            .map(|rule| &rule.owners[..])
+            .unwrap_or_default()
+    }
+}
+
+/// Converts a `.gitignore` style pattern into a regex matching paths relative to the root.
+fn glob_to_regex(pattern: &str) -> String {
+    let dir_only = pattern.ends_with('/');
+    let pattern = pattern.trim_end_matches('/');
+    // A pattern with a slash at the beginning or middle is relative to the root, otherwise it
+    // matches at any depth.
+    let anchored = pattern.contains('/');
+    let pattern = pattern.trim_start_matches('/');
+
+    let mut regex = String::from(if anchored { "^" } else { "^(?:.*/)?" });
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    regex.push_str("(?:.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    // A pattern matches the path itself and, if it's a directory, everything inside it.
+    regex.push_str(if dir_only { "/.*$" } else { "(?:/.*)?$" });
+    regex
+}
@@ -1,57 +1,312 @@
-use core::fmt;
+#[cfg(feature = "config-files")]
+mod edit;
+#[cfg(feature = "config-files")]
+mod file;
+#[cfg(feature = "config-files")]
+mod migrate;
+mod template;
 
-use std::{
-    collections::HashMap,
-    fs,
-    io::Read,
-    path::{Path, PathBuf},
-};
+use std::{collections::HashMap, time::Duration};
 
+#[cfg(feature = "config-files")]
 use macros::{Complete, Partialize};
-use regex::Regex;
+use regex::{Regex, RegexBuilder};
 use serde::{ser::SerializeMap, Deserialize, Serialize};
 
-use crate::partial::{Complete, Partial};
+use crate::fingerprint::Fingerprint;
 
-#[derive(Serialize, Partialize, Debug)]
+#[cfg(feature = "config-files")]
+pub use edit::ConfigDocument;
+#[cfg(feature = "config-files")]
+pub use file::{migrate_file, set_known_panics, Provenance, Source};
+#[cfg(feature = "config-files")]
+pub use migrate::Deprecation;
+pub use template::{FrameVars, HeaderVars, PanicVars, Template, Vars};
+
+#[derive(Serialize, Debug)]
+#[cfg_attr(
+    feature = "config-files",
+    derive(Partialize),
+    partialize(derive(Debug, Clone, Serialize))
+)]
 pub struct Config {
+    /// `style` sets the backtrace detail level.
+    /// Options:
+    /// - "short" (default): Sets `RUST_BACKTRACE=1`
+    /// - "full": Sets `RUST_BACKTRACE=full`
     pub style: BacktraceStyle,
+    /// `echo` controls whether backtracetk echoes captured lines.
+    /// - true (default): Captured lines are printed as they are read
+    /// - false: Suppresses output until the program exits
     pub echo: Echo,
+    /// `max_echo_rate` limits how many lines per second are echoed, e.g., 1000 for commands that
+    /// print so much that echoing slows them down. Lines that are part of a panic or a backtrace are
+    /// always echoed, and a note says how many lines were skipped. Also set with `--max-echo-rate`.
+    /// Ignored with `--raw`, which echoes every line.
+    pub max_echo_rate: Option<u32>,
+    /// `capture` sets which output streams of the command are read looking for backtraces, e.g.,
+    /// for test harnesses or panic hooks printing them to stdout. Also set with `--capture`.
+    /// - "stderr" (default): Only stderr is read
+    /// - "stdout": Only stdout is read, stderr is left alone
+    /// - "both": Both are read, and lines are echoed to their stream in the order they were written
+    pub capture: Capture,
+    /// `log_prefix` is a regex matching the prefix that a logger adds to every line, for formats
+    /// that aren't recognized out of the box like those of `env_logger` and `tracing-subscriber`.
+    /// The prefix must start at the beginning of the line and it's stripped before parsing the
+    /// rest. A capture group named `time` is used as the time of the backtrace, e.g.,
+    /// '^\S+ \| (?P<time>[\d:.]+) ' for `web-1 | 12:00:02.1 thread 'main' panicked at ...`.
+    pub log_prefix: Option<LogPrefix>,
+    /// `dedupe_adjacent` controls whether adjacent backtraces that are near-identical (e.g., when
+    /// both a custom panic hook and the default one print the trace) are rendered only once.
+    /// - true (default): Duplicates are merged and a note says how many times the trace was printed
+    /// - false: Every backtrace is rendered
+    pub dedupe_adjacent: bool,
+    /// `sort` sets the order of the backtraces rendered when the command exits. Backtraces
+    /// rendered as soon as they're captured (e.g., with `rate_limit`) keep the order they were
+    /// captured in. Backtraces are only grouped by thread when kept in capture order. Also set
+    /// with `--sort`.
+    /// - "capture" (default): The order they were captured in
+    /// - "time": The time they were printed (see `timestamps`), those without a time last
+    /// - "fingerprint": By fingerprint, so the occurrences of a panic are next to each other
+    /// - "message": Alphabetically by panic message
+    /// - "user-frames": The backtraces with the most frames in user code first
+    pub sort: Sort,
+    /// `timestamps` controls whether echoed lines are prefixed with the time elapsed since the
+    /// command started. The time is also shown in the header of backtraces.
+    /// - true: Lines are prefixed with the time, e.g., `[00:02:13.4]`
+    /// - false (default): Lines are echoed as they are
+    pub timestamps: bool,
+    /// `resource_usage` controls whether the peak memory usage and the CPU time of the command are
+    /// printed when it exits, since panics often happen under memory pressure. Only on Unix.
+    /// - true: A line like `(backtracetk: max RSS 212.4 MiB, user 1.32s, sys 0.08s)` is printed
+    /// - false (default): Nothing is printed
+    pub resource_usage: bool,
+    /// `process_context` controls whether the state of the command is captured from `/proc` as
+    /// soon as it panics, i.e., its command line, working directory, number of open file descriptors,
+    /// and a summary of its memory mappings. It's included in the JSON output, e.g., for postmortem
+    /// analysis. Only on Linux.
+    /// - true: The state is captured
+    /// - false (default): Nothing is captured
+    pub process_context: bool,
+    /// `human_panic_reports` controls whether the backtrace of a program using `human-panic` is read
+    /// from the crash report file it mentions, e.g., `We have generated a report file at "..."`.
+    /// - true: The backtrace is read from the report, as long as the file still exists
+    /// - false (default): Only the line mentioning the report is shown
+    pub human_panic_reports: bool,
+    /// `history` controls whether captured panics are recorded in `.backtracetk/history.jsonl`,
+    /// in the closest parent directory that has a `.backtracetk` directory or the current one.
+    /// Use `backtracetk history` to see when each panic was first and last seen.
+    /// - true: Panics are recorded
+    /// - false (default): Nothing is recorded
+    pub history: bool,
+    /// `known_panics` lists the fingerprints of expected panics, e.g., of flaky tests, so
+    /// `--fail-on-panic` only fails on new ones. `backtracetk baseline update` sets it to the
+    /// fingerprints of the panics in a set of logs.
+    pub known_panics: Vec<Fingerprint>,
+    /// `foreign_frames` controls how frames from code in other languages (e.g., C or C++ libraries
+    /// called through FFI) are rendered. C++ symbols are demangled.
+    /// - "collapse" (default): Consecutive foreign frames are rendered as a single row
+    /// - "show": Foreign frames are rendered like any other frame
+    /// - "hide": Foreign frames are hidden
+    pub foreign_frames: ForeignFrames,
+    /// `no_user_frames` controls how backtraces without frames in user code (e.g., a failed
+    /// allocation, where every frame is in the standard library) are rendered, since the `hide`
+    /// rules would otherwise leave nothing useful.
+    /// - "show" (default): Every frame is rendered, ignoring the `hide` rules
+    /// - "collapse": Frames are collapsed into a single row, only the panic message is rendered
+    /// - "suppress": The backtrace is replaced by a one-line notice with the panic location
+    pub no_user_frames: NoUserFrames,
+    /// `compact` controls whether backtraces are rendered with one line per frame, e.g., for
+    /// copy-pasting them into a chat. Frames are still filtered. Also enabled with `--compact`.
+    /// - true: Frames are rendered as `#N function (file:line)` without code snippets
+    /// - false (default): Frames are rendered with their code snippets
+    pub compact: bool,
+    /// `group_by_crate` controls whether consecutive frames from the same crate are grouped under
+    /// a line with the name of the crate, which makes long chains of frames in dependencies easier
+    /// to scan. Nothing is hidden. Ignored when `compact` is enabled.
+    /// - true: Frames are indented under the name of their crate
+    /// - false (default): Frames are rendered one after the other
+    pub group_by_crate: bool,
+    /// `detailed_frames` limits how many frames are rendered with a code snippet and a hyperlink,
+    /// counting the frames that aren't hidden from the innermost one. Reading source files and
+    /// resolving paths is most of the work for very deep backtraces (e.g., with `style = "full"`),
+    /// while frames far from the panic are rarely read. Frames past the limit are rendered with
+    /// their plain location.
+    pub detailed_frames: usize,
+    /// `frame_format` is a template replacing the lines with the function and location of each
+    /// frame, e.g., "{frameno}: {function} @ {file}:{line}". Available variables are `{frameno}`,
+    /// `{function}`, `{file}`, `{line}`, and `{column}`.
+    pub frame_format: Option<Template<FrameVars>>,
+    /// `header_format` is a template replacing the line at the start of each backtrace, e.g.,
+    /// "=== {fingerprint} ===". Available variables are `{command}`, `{test}`, `{time}`, `{rustc}`,
+    /// and `{fingerprint}`.
+    pub header_format: Option<Template<HeaderVars>>,
+    /// `panic_format` is a template replacing the panic message at the end of each backtrace,
+    /// e.g., "{location}: {message}". Available variables are `{thread}`, `{location}`, and
+    /// `{message}`.
+    pub panic_format: Option<Template<PanicVars>>,
+    /// `diagnostics` controls whether the compiler warnings and errors printed before a backtrace
+    /// (e.g., when running `cargo run`) are shown with it, since they are often a hint of why the
+    /// program panicked. Only the last few diagnostics pointing at a location are shown.
+    /// - true (default): Diagnostics are shown in a section after the header
+    /// - false: Diagnostics are not shown
+    pub diagnostics: bool,
+    /// `notify` configures actions triggered as soon as a panic is detected, e.g., to get notified
+    /// of panics in long running processes.
+    pub notify: Notify,
+    /// `on_backtrace` configures a command that receives every backtrace as it's detected, e.g.,
+    /// to integrate with other tools.
+    pub on_backtrace: OnBacktrace,
+    /// `plugin` configures a command that can hide, rename, or annotate the frames of every
+    /// backtrace before it's rendered, e.g., to implement rules specific to an organization.
+    pub plugin: Plugin,
+    /// `rate_limit` limits how often the same backtrace is rendered, e.g., when a long running
+    /// process is in a crash loop. When enabled, backtraces are rendered as soon as they are
+    /// detected instead of when the process exits.
+    pub rate_limit: RateLimit,
+    /// `hyperlinks` configures the emission of hyperlinks for file paths in the backtrace output.
     pub hyperlinks: HyperLinks,
+    /// `env` allows specifying additional environment variables for the child process.
     pub env: HashMap<String, String>,
+    /// `hide` sections define rules to exclude specific frames from the backtrace output.
+    /// Frames can be hidden based on a regex `pattern` or a range between a `begin` pattern and
+    /// an optional `end` pattern (if omitted, all subsequent frames are hidden), matched against
+    /// the function's path, or based on where the code lives with a regex matched against the
+    /// `file` of the frame (e.g., `"^/rustc/"`) or the whole name of its `crate`.
+    /// Patterns are matched ignoring case with `case_insensitive = true`, and `anchor = "start"`
+    /// or `anchor = "crate"` forces them to match the start of the path or the whole crate name.
+    /// The rule below is used by default when there's no global configuration file.
     pub hide: Vec<Hide>,
+    /// `show` sections define rules for the only frames to show, e.g., the frames of your own
+    /// crates. They take the same keys as `hide` sections, and when there's at least one, the
+    /// frames that no rule matches are hidden as if a `hide` rule matched them. `--only` adds a
+    /// rule with a `pattern`.
+    pub show: Vec<Hide>,
+    /// `redact` sections define rules replacing the matches of a regex `pattern` in panic messages
+    /// and code snippets before they are rendered or exported (e.g., by `on_backtrace` or
+    /// `--annotate-pr`), so backtraces containing secrets can be shared. The `replacement` may
+    /// refer to capture groups, e.g., `"$1"`, and defaults to `"[REDACTED]"`.
+    pub redact: Vec<Redact>,
 }
 
-impl Config {
-    pub fn read() -> anyhow::Result<Config> {
-        PartialConfig::read().map(PartialConfig::into_complete)
-    }
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "config-files", derive(Complete))]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum Capture {
+    #[default]
+    Stderr,
+    Stdout,
+    Both,
 }
 
-impl fmt::Display for Config {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", toml::to_string_pretty(self).unwrap())
+impl Capture {
+    pub fn stdout(self) -> bool {
+        matches!(self, Capture::Stdout | Capture::Both)
+    }
+
+    pub fn stderr(self) -> bool {
+        matches!(self, Capture::Stderr | Capture::Both)
     }
 }
 
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "config-files", derive(Complete))]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "kebab-case")]
+pub enum Sort {
+    #[default]
+    Capture,
+    Time,
+    Fingerprint,
+    Message,
+    UserFrames,
+}
+
+/// How frames from code in other languages are rendered, see [`Config::foreign_frames`].
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "config-files", derive(Complete))]
+#[serde(rename_all = "lowercase")]
+pub enum ForeignFrames {
+    /// Consecutive foreign frames are rendered as a single row with their count.
+    #[default]
+    Collapse,
+    /// Foreign frames are rendered like any other frame.
+    Show,
+    /// Foreign frames are hidden, like frames matched by a `hide` rule.
+    Hide,
+}
+
+/// How backtraces without frames in user code are rendered, see [`Config::no_user_frames`].
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "config-files", derive(Complete))]
+#[serde(rename_all = "lowercase")]
+pub enum NoUserFrames {
+    /// Every frame is rendered, ignoring the `hide` rules.
+    #[default]
+    Show,
+    /// The frames are collapsed into a single row, so only the panic message is rendered.
+    Collapse,
+    /// The backtrace is replaced by a one-line notice with the location of the panic.
+    Suppress,
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
             style: Default::default(),
             hide: vec![Hide::Range {
-                begin: Regex::new("core::panicking::panic_explicit").unwrap(),
+                begin: FramePattern::new(
+                    "core::panicking::panic_explicit",
+                    MatchOptions::default(),
+                )
+                .unwrap(),
                 end: None,
             }],
+            show: vec![],
+            redact: vec![],
             env: Default::default(),
             echo: Default::default(),
+            max_echo_rate: None,
+            capture: Capture::Stderr,
+            log_prefix: None,
+            dedupe_adjacent: true,
+            sort: Sort::Capture,
+            timestamps: false,
+            resource_usage: false,
+            process_context: false,
+            human_panic_reports: false,
+            history: false,
+            known_panics: vec![],
+            foreign_frames: ForeignFrames::Collapse,
+            no_user_frames: NoUserFrames::Show,
+            compact: false,
+            group_by_crate: false,
+            detailed_frames: 100,
+            frame_format: None,
+            header_format: None,
+            panic_format: None,
+            diagnostics: true,
+            notify: Default::default(),
+            on_backtrace: Default::default(),
+            plugin: Default::default(),
+            rate_limit: Default::default(),
             hyperlinks: Default::default(),
         }
     }
 }
 
-#[derive(Serialize, Partialize, Debug)]
+#[derive(Serialize, Debug)]
+#[cfg_attr(
+    feature = "config-files",
+    derive(Partialize),
+    partialize(derive(Debug, Clone, Serialize))
+)]
 pub struct HyperLinks {
+    /// Enable or disable hyperlinking.
     pub enabled: bool,
+    /// Template for generating file links. `${FILE_PATH}`, `${LINE}`, and `${COLUMN}` are
+    /// replaced with the location of the frame, e.g., "vscode://file${FILE_PATH}:${LINE}:${COLUMN}"
     pub url: String,
 }
 
@@ -73,7 +328,112 @@ impl Default for HyperLinks {
     }
 }
 
-#[derive(Clone, Copy, Serialize, Deserialize, Complete, Default, Debug)]
+#[derive(Serialize, Default, Debug)]
+#[cfg_attr(
+    feature = "config-files",
+    derive(Partialize),
+    partialize(derive(Debug, Clone, Serialize))
+)]
+pub struct Notify {
+    /// A shell command to run for every panic. The panic message and the location of the top user
+    /// frame are passed as arguments (`$1` and `$2`) and in the `BACKTRACETK_MESSAGE` and
+    /// `BACKTRACETK_LOCATION` environment variables, e.g., "notify-send panic \"$1\"".
+    pub command: Option<String>,
+    /// Show a desktop notification for every panic. Requires backtracetk to be built with the
+    /// `desktop-notifications` feature.
+    pub desktop: bool,
+    /// Ask the terminal to show a notification for every panic with an escape sequence, which
+    /// works without a notification daemon in terminals supporting it: "osc9" (e.g., iTerm2,
+    /// Windows Terminal) or "osc777" (e.g., foot, rxvt-unicode). Only emitted if stderr is a
+    /// terminal.
+    pub terminal: TerminalNotification,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "config-files", derive(Complete))]
+#[serde(rename_all = "lowercase")]
+pub enum TerminalNotification {
+    #[default]
+    Off,
+    Osc9,
+    Osc777,
+}
+
+#[derive(Serialize, Default, Debug)]
+#[cfg_attr(
+    feature = "config-files",
+    derive(Partialize),
+    partialize(derive(Debug, Clone, Serialize))
+)]
+pub struct OnBacktrace {
+    /// A shell command to run for every backtrace. The backtrace is written to its stdin as JSON,
+    /// e.g., "cat >> backtraces.jsonl".
+    pub command: Option<String>,
+    /// Wait for the command to finish before continuing, otherwise it runs in the background.
+    pub blocking: bool,
+}
+
+#[derive(Serialize, Debug)]
+#[cfg_attr(
+    feature = "config-files",
+    derive(Partialize),
+    partialize(derive(Debug, Clone, Serialize))
+)]
+pub struct Plugin {
+    /// A shell command to run for every backtrace, e.g., "python3 plugin.py". The backtrace is
+    /// written to its stdin as JSON, and the changes to make are read from its stdout as JSON.
+    pub command: Option<String>,
+    /// How many seconds to wait for the command. After that, it's killed and the backtrace is left
+    /// unchanged.
+    pub timeout: u64,
+}
+
+impl Default for Plugin {
+    fn default() -> Self {
+        Self {
+            command: None,
+            timeout: 5,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[cfg_attr(
+    feature = "config-files",
+    derive(Partialize),
+    partialize(derive(Debug, Clone, Serialize))
+)]
+pub struct RateLimit {
+    /// Enable or disable rate limiting.
+    pub enabled: bool,
+    /// Only the first occurrence of a backtrace in this many minutes is rendered. The number of
+    /// suppressed occurrences is printed when the window ends. A window of 0 minutes disables rate
+    /// limiting.
+    pub window: u64,
+}
+
+impl RateLimit {
+    /// Whether rate limiting is enabled with a window long enough to suppress something.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled && self.window > 0
+    }
+
+    pub fn window(&self) -> Duration {
+        Duration::from_secs(self.window * 60)
+    }
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            window: 5,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Default, Debug)]
+#[cfg_attr(feature = "config-files", derive(Complete))]
 #[serde(from = "bool")]
 #[serde(into = "bool")]
 pub enum Echo {
@@ -92,16 +452,17 @@ impl From<bool> for Echo {
     }
 }
 
-impl Into<bool> for Echo {
-    fn into(self) -> bool {
-        match self {
+impl From<Echo> for bool {
+    fn from(echo: Echo) -> Self {
+        match echo {
             Echo::True => true,
             Echo::False => false,
         }
     }
 }
 
-#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, Complete)]
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "config-files", derive(Complete))]
 #[serde(rename_all = "lowercase")]
 pub enum BacktraceStyle {
     #[default]
@@ -116,17 +477,119 @@ impl BacktraceStyle {
             BacktraceStyle::Full => "full",
         }
     }
+
+    /// The style for a value of `RUST_BACKTRACE`, or `None` if it disables backtraces.
+    pub fn from_env_var(value: &str) -> Option<BacktraceStyle> {
+        match value {
+            "0" => None,
+            "full" => Some(BacktraceStyle::Full),
+            _ => Some(BacktraceStyle::Short),
+        }
+    }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum Hide {
-    Pattern { pattern: Regex },
-    Range { begin: Regex, end: Option<Regex> },
+    Pattern {
+        pattern: FramePattern,
+    },
+    /// Hides the frames whose source file matches `file`, e.g., `^/rustc/` for the standard
+    /// library. Frames without a location are never hidden.
+    File {
+        file: FramePattern,
+    },
+    /// Hides the frames of the crates whose whole name matches `name`, like [`Anchor::Crate`].
+    Crate {
+        name: FramePattern,
+    },
+    Range {
+        begin: FramePattern,
+        end: Option<FramePattern>,
+    },
+}
+
+impl Hide {
+    fn options(&self) -> MatchOptions {
+        match self {
+            Hide::Pattern { pattern } => pattern.options,
+            Hide::File { file } => file.options,
+            Hide::Crate { name } => name.options,
+            Hide::Range { begin, .. } => begin.options,
+        }
+    }
+}
+
+/// A regex matched against a frame's function name or file, compiled according to some
+/// [`MatchOptions`].
+#[derive(Clone, Debug)]
+pub struct FramePattern {
+    source: String,
+    regex: Regex,
+    options: MatchOptions,
+}
+
+impl FramePattern {
+    pub fn new(source: &str, options: MatchOptions) -> Result<FramePattern, regex::Error> {
+        let anchored = match options.anchor {
+            None => source.to_string(),
+            Some(Anchor::Start) => format!("^(?:{source})"),
+            Some(Anchor::Crate) => format!("^(?:{source})$"),
+        };
+        let regex = RegexBuilder::new(&anchored)
+            .case_insensitive(options.case_insensitive)
+            .build()?;
+        Ok(FramePattern {
+            source: source.to_string(),
+            regex,
+            options,
+        })
+    }
+
+    pub fn is_match(&self, function: &str) -> bool {
+        match self.options.anchor {
+            Some(Anchor::Crate) => self.regex.is_match(crate::model::crate_name(function)),
+            _ => self.regex.is_match(function),
+        }
+    }
+
+    /// The pattern as written in the configuration.
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+}
+
+/// Options controlling how the patterns in a `hide` rule are matched.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MatchOptions {
+    pub case_insensitive: bool,
+    pub anchor: Option<Anchor>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Anchor {
+    /// The pattern must match at the start of the function's name.
+    Start,
+    /// The pattern must match the whole name of the function's crate, e.g., `tokio` matches
+    /// `tokio::runtime::park` but not `my_tokio_helpers::spawn`.
+    Crate,
 }
 
 const PATTERN: &str = "pattern";
+const FILE: &str = "file";
+const CRATE: &str = "crate";
 const BEGIN: &str = "begin";
 const END: &str = "end";
+const CASE_INSENSITIVE: &str = "case_insensitive";
+const ANCHOR: &str = "anchor";
+const FIELDS: &[&str] = &[PATTERN, FILE, CRATE, BEGIN, END, CASE_INSENSITIVE, ANCHOR];
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum HideValue {
+    String(String),
+    Bool(bool),
+}
 
 // Unfortunately we have to implement our own deserializer.
 // See https://github.com/toml-rs/toml/issues/748 and https://github.com/toml-rs/toml/issues/535
@@ -144,7 +607,7 @@ impl<'de> Deserialize<'de> for Hide {
             fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
                 write!(
                     f,
-                    "a map with wither the field `{PATTERN}`, or the fields `{BEGIN}` and optionally `{END}`"
+                    "a map with either the field `{PATTERN}`, `{FILE}`, or `{CRATE}`, or the fields `{BEGIN}` and optionally `{END}`"
                 )
             }
 
@@ -152,29 +615,91 @@ impl<'de> Deserialize<'de> for Hide {
             where
                 A: serde::de::MapAccess<'de>,
             {
-                let re = |s: &str| Regex::new(s).map_err(|e| Error::custom(&e.to_string()));
-                let mut entries = HashMap::<String, String>::default();
-                while let Some((k, v)) = map.next_entry::<String, String>()? {
-                    entries.insert(k, v);
+                let mut strings = HashMap::<String, String>::default();
+                let mut bools = HashMap::<String, bool>::default();
+                while let Some((k, v)) = map.next_entry::<String, HideValue>()? {
+                    match v {
+                        HideValue::String(v) => {
+                            strings.insert(k, v);
+                        }
+                        HideValue::Bool(v) => {
+                            bools.insert(k, v);
+                        }
+                    }
                 }
 
-                if entries.contains_key(PATTERN) && entries.contains_key(BEGIN) {
+                let anchor = match strings.remove(ANCHOR).as_deref() {
+                    None => None,
+                    Some("start") => Some(Anchor::Start),
+                    Some("crate") => Some(Anchor::Crate),
+                    Some(anchor) => {
+                        return Err(Error::custom(format!(
+                            "invalid `{ANCHOR}` `{anchor}`, expected `start` or `crate`"
+                        )))
+                    }
+                };
+                let options = MatchOptions {
+                    case_insensitive: bools.remove(CASE_INSENSITIVE).unwrap_or(false),
+                    anchor,
+                };
+                let re = |s: &str| FramePattern::new(s, options).map_err(Error::custom);
+
+                let keys = [PATTERN, FILE, CRATE, BEGIN];
+                let mut used = keys.iter().filter(|key| strings.contains_key(**key));
+                if let (Some(first), Some(second)) = (used.next(), used.next()) {
+                    return Err(Error::custom(format!(
+                        "cannot use `{first}` and `{second}` together"
+                    )));
+                }
+                let located = strings.contains_key(FILE) || strings.contains_key(CRATE);
+                if options.anchor.is_some() && located {
                     return Err(Error::custom(format!(
-                        "cannot use `{PATTERN}` and `{BEGIN}` toghether"
+                        "`{ANCHOR}` can only be used with `{PATTERN}`, `{BEGIN}`, and `{END}`"
                     )));
                 }
-                if let Some(pattern) = entries.remove(PATTERN) {
+                let hide = if let Some(pattern) = strings.remove(PATTERN) {
                     let pattern = re(&pattern)?;
-                    Ok(Hide::Pattern { pattern })
-                } else if let Some(begin) = entries.remove(BEGIN) {
+                    Hide::Pattern { pattern }
+                } else if let Some(file) = strings.remove(FILE) {
+                    let file = re(&file)?;
+                    Hide::File { file }
+                } else if let Some(name) = strings.remove(CRATE) {
+                    let options = MatchOptions {
+                        anchor: Some(Anchor::Crate),
+                        ..options
+                    };
+                    let name = FramePattern::new(&name, options).map_err(Error::custom)?;
+                    Hide::Crate { name }
+                } else if let Some(begin) = strings.remove(BEGIN) {
                     let begin = re(&begin)?;
-                    let end = entries.remove(END).as_deref().map(re).transpose()?;
-                    Ok(Hide::Range { begin, end })
+                    let end = strings.remove(END).as_deref().map(re).transpose()?;
+                    Hide::Range { begin, end }
                 } else {
-                    Err(Error::custom(format!(
-                        "missing field `{PATTERN}` or `{BEGIN}`"
-                    )))
+                    return Err(Error::custom(format!(
+                        "missing field `{PATTERN}`, `{FILE}`, `{CRATE}`, or `{BEGIN}`"
+                    )));
+                };
+
+                // Every other key is unknown, has a value of the wrong type, or doesn't go with
+                // the keys used
+                if let Some(key) = strings.keys().min() {
+                    return Err(match key.as_str() {
+                        CASE_INSENSITIVE => {
+                            Error::custom(format!("`{CASE_INSENSITIVE}` must be a boolean"))
+                        }
+                        END => Error::custom(format!("`{END}` can only be used with `{BEGIN}`")),
+                        key => Error::unknown_field(key, FIELDS),
+                    });
                 }
+                if let Some(key) = bools.keys().min() {
+                    return Err(match key.as_str() {
+                        key if FIELDS.contains(&key) => {
+                            Error::custom(format!("`{key}` must be a string"))
+                        }
+                        key => Error::unknown_field(key, FIELDS),
+                    });
+                }
+                Ok(hide)
             }
         }
         deserializer.deserialize_map(Visitor)
@@ -189,6 +714,8 @@ impl Serialize for Hide {
         let mut m = serializer.serialize_map(None)?;
         match self {
             Hide::Pattern { pattern } => m.serialize_entry(PATTERN, pattern.as_str())?,
+            Hide::File { file } => m.serialize_entry(FILE, file.as_str())?,
+            Hide::Crate { name } => m.serialize_entry(CRATE, name.as_str())?,
             Hide::Range { begin, end } => {
                 m.serialize_entry(BEGIN, begin.as_str())?;
                 if let Some(end) = end {
@@ -196,54 +723,127 @@ impl Serialize for Hide {
                 }
             }
         }
+        let options = self.options();
+        if options.case_insensitive {
+            m.serialize_entry(CASE_INSENSITIVE, &true)?;
+        }
+        // Crate rules are always anchored to the crate name
+        if let (Some(anchor), false) = (options.anchor, matches!(self, Hide::Crate { .. })) {
+            m.serialize_entry(ANCHOR, &anchor)?;
+        }
         m.end()
     }
 }
 
-impl PartialConfig {
-    fn read() -> anyhow::Result<PartialConfig> {
-        let config = PartialConfig::find_home_file()
-            .map(PartialConfig::parse_file)
-            .transpose()?
-            .unwrap_or_else(|| Config::default().into_partial());
-        let Some(local_path) = PartialConfig::find_local_file() else {
-            return Ok(config);
+/// A rule replacing the matches of `pattern` with `replacement`, see [`Config::redact`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Redact {
+    #[serde(with = "regex_str")]
+    pub pattern: Regex,
+    #[serde(default = "default_replacement")]
+    pub replacement: String,
+}
+
+fn default_replacement() -> String {
+    "[REDACTED]".to_string()
+}
+
+/// A regex matching the prefix added to lines by a logger, see `log_prefix`.
+#[derive(Clone, Debug)]
+pub struct LogPrefix(pub Regex);
+
+impl Serialize for LogPrefix {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        regex_str::serialize(&self.0, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for LogPrefix {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        regex_str::deserialize(deserializer).map(LogPrefix)
+    }
+}
+
+/// (De)serializes a regex as the string it was compiled from.
+mod regex_str {
+    use regex::Regex;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(regex: &Regex, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(regex.as_str())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Regex, D::Error> {
+        let source = String::deserialize(deserializer)?;
+        Regex::new(&source).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Anchor, FramePattern, Hide, MatchOptions, RateLimit};
+
+    fn pattern(source: &str, case_insensitive: bool, anchor: Option<Anchor>) -> FramePattern {
+        let options = MatchOptions {
+            case_insensitive,
+            anchor,
         };
-        Ok(config.merge_with(PartialConfig::parse_file(local_path)?))
+        FramePattern::new(source, options).unwrap()
     }
 
-    fn parse_file(path: PathBuf) -> anyhow::Result<PartialConfig> {
-        let mut contents = String::new();
-        let mut file = fs::File::open(path)?;
-        file.read_to_string(&mut contents)?;
-        let config = toml::from_str(&contents)?;
-        Ok(config)
+    #[test]
+    fn unanchored() {
+        let tokio = pattern("tokio", false, None);
+        assert!(tokio.is_match("tokio::runtime::park"));
+        assert!(tokio.is_match("my_tokio_helpers::spawn"));
     }
 
-    fn find_home_file() -> Option<PathBuf> {
-        let home_dir = home::home_dir()?;
-        PartialConfig::find_file_in(&home_dir)
+    #[test]
+    fn anchor_crate() {
+        let tokio = pattern("tokio", false, Some(Anchor::Crate));
+        assert!(tokio.is_match("tokio::runtime::park"));
+        assert!(tokio.is_match("<tokio::runtime::Runtime as core::ops::Drop>::drop"));
+        assert!(!tokio.is_match("my_tokio_helpers::spawn"));
+        assert!(!tokio.is_match("tokio_util::codec::decode"));
     }
 
-    fn find_local_file() -> Option<PathBuf> {
-        let mut path = std::env::current_dir().unwrap();
-        loop {
-            if let Some(file) = PartialConfig::find_file_in(&path) {
-                return Some(file);
-            }
-            if !path.pop() {
-                return None;
-            }
-        }
+    #[test]
+    fn anchor_start() {
+        let tokio = pattern("tokio", false, Some(Anchor::Start));
+        assert!(tokio.is_match("tokio::runtime::park"));
+        assert!(tokio.is_match("tokio_util::codec::decode"));
+        assert!(!tokio.is_match("my_tokio_helpers::spawn"));
     }
 
-    fn find_file_in(dir: &Path) -> Option<PathBuf> {
-        for name in ["backtracetk.toml", ".backtracetk.toml"] {
-            let file = dir.join(name);
-            if file.exists() {
-                return Some(file);
-            }
-        }
-        None
+    #[test]
+    fn case_insensitive() {
+        assert!(!pattern("MyCrate", false, None).is_match("mycrate::main"));
+        assert!(pattern("MyCrate", true, None).is_match("mycrate::main"));
+        assert!(pattern("MyCrate", true, Some(Anchor::Crate)).is_match("mycrate::main"));
+    }
+
+    /// The options of a rule apply to all of its patterns.
+    #[test]
+    fn hide_options() {
+        let hide: Hide = toml::from_str(
+            "begin = 'Tokio'\nend = 'Futures'\ncase_insensitive = true\nanchor = 'crate'",
+        )
+        .unwrap();
+        let Hide::Range { begin, end } = hide else {
+            panic!("expected a range");
+        };
+        let end = end.unwrap();
+        assert!(begin.is_match("tokio::spawn"));
+        assert!(!begin.is_match("my_tokio_helpers::spawn"));
+        assert!(end.is_match("futures::executor::block_on"));
+        assert!(!end.is_match("futures_util::stream::next"));
+    }
+
+    #[test]
+    fn rate_limit_without_window() {
+        let rate_limit = |enabled, window| RateLimit { enabled, window };
+        assert!(rate_limit(true, 5).is_enabled());
+        assert!(!rate_limit(false, 5).is_enabled());
+        assert!(!rate_limit(true, 0).is_enabled());
     }
 }
@@ -0,0 +1,180 @@
+//! Programmatic edits of configuration files.
+//!
+//! Features that update a configuration file must go through [`ConfigDocument`] instead of
+//! re-serializing a [`Config`] so that the user's comments and formatting are preserved.
+//!
+//! [`Config`]: super::Config
+
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use toml_edit::{ArrayOfTables, DocumentMut, Item, Table, TableLike, Value};
+
+use super::{migrate, Deprecation, Hide};
+
+/// A configuration file loaded for editing.
+pub struct ConfigDocument {
+    path: PathBuf,
+    doc: DocumentMut,
+}
+
+impl ConfigDocument {
+    /// Loads the file at `path`. A file that doesn't exist yet is treated as empty, and it will be
+    /// created when saving.
+    pub fn open(path: impl Into<PathBuf>) -> anyhow::Result<ConfigDocument> {
+        let path = path.into();
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(ConfigDocument {
+            doc: contents.parse()?,
+            path,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Sets the value at `path`, creating the tables leading to it if necessary. If the key
+    /// already exists, its trailing comment is kept.
+    pub fn set(&mut self, path: &[&str], value: impl Into<Value>) -> anyhow::Result<()> {
+        let Some((last, prefix)) = path.split_last() else {
+            anyhow::bail!("empty path")
+        };
+        let mut table = self.doc.as_table_mut() as &mut dyn TableLike;
+        for key in prefix {
+            table = table
+                .entry(key)
+                .or_insert_with(|| {
+                    let mut table = Table::new();
+                    table.set_implicit(true);
+                    Item::Table(table)
+                })
+                .as_table_like_mut()
+                .ok_or_else(|| anyhow::anyhow!("`{key}` is not a table"))?;
+        }
+        let mut value = value.into();
+        match table.get_mut(last) {
+            Some(item) => {
+                if let Some(old) = item.as_value() {
+                    *value.decor_mut() = old.decor().clone();
+                }
+                *item = Item::Value(value);
+            }
+            None => {
+                table.insert(last, Item::Value(value));
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes the value at `path`. Returns whether there was a value to remove.
+    pub fn remove(&mut self, path: &[&str]) -> bool {
+        let Some((last, prefix)) = path.split_last() else {
+            return false;
+        };
+        let mut table = self.doc.as_table_mut() as &mut dyn TableLike;
+        for key in prefix {
+            match table.get_mut(key).and_then(Item::as_table_like_mut) {
+                Some(inner) => table = inner,
+                None => return false,
+            }
+        }
+        table.remove(last).is_some()
+    }
+
+    /// Appends a `[[hide]]` rule at the end of the file.
+    pub fn push_hide(&mut self, hide: &Hide) -> anyhow::Result<()> {
+        let rule: DocumentMut = toml::to_string(hide)?.parse()?;
+        let hides = self
+            .doc
+            .entry("hide")
+            .or_insert_with(|| Item::ArrayOfTables(ArrayOfTables::new()));
+        match hides.as_array_of_tables_mut() {
+            Some(hides) => hides.push(rule.as_table().clone()),
+            None => anyhow::bail!("`hide` must be an array of tables"),
+        }
+        Ok(())
+    }
+
+    /// Rewrites deprecated keys to their replacements. Returns the keys that were migrated.
+    pub fn migrate(&mut self) -> anyhow::Result<Vec<Deprecation>> {
+        let (doc, deprecations) = migrate::migrate(&self.doc.to_string())?;
+        self.doc = doc;
+        Ok(deprecations)
+    }
+
+    /// Writes the document back to the file it was loaded from.
+    pub fn save(&self) -> anyhow::Result<()> {
+        fs::write(&self.path, self.doc.to_string())?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for ConfigDocument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.doc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ConfigDocument;
+    use crate::config::{FramePattern, Hide, MatchOptions};
+
+    /// Editing a file keeps its comments, formatting, and the order of its keys.
+    #[test]
+    fn round_trip() {
+        let path =
+            std::env::temp_dir().join(format!("backtracetk-edit-{}.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"# Settings for the whole team
+style = "full"   # the short style hides too much
+echo = true
+
+# Hide the test harness
+[[hide]]
+begin = "test::run_test"
+
+[hyperlinks]
+enabled = false # not supported by our terminals
+"#,
+        )
+        .unwrap();
+        let mut doc = ConfigDocument::open(&path).unwrap();
+        doc.set(&["style"], "short").unwrap();
+        doc.set(&["hyperlinks", "enabled"], true).unwrap();
+        doc.set(&["notify", "desktop"], true).unwrap();
+        assert!(doc.remove(&["echo"]));
+        let pattern = FramePattern::new("tokio::runtime", MatchOptions::default()).unwrap();
+        doc.push_hide(&Hide::Pattern { pattern }).unwrap();
+        doc.save().unwrap();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            contents,
+            r#"# Settings for the whole team
+style = "short"   # the short style hides too much
+
+# Hide the test harness
+[[hide]]
+begin = "test::run_test"
+
+[[hide]]
+pattern = "tokio::runtime"
+
+[hyperlinks]
+enabled = true # not supported by our terminals
+
+[notify]
+desktop = true
+"#
+        );
+    }
+}
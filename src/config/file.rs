@@ -0,0 +1,620 @@
+//! Reading the configuration from `backtracetk.toml` files, enabled with the `config-files`
+//! feature. Files are parsed into [`PartialConfig`] layers that are merged in order, see
+//! [`Config::provenance`].
+
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+use toml_edit::{DocumentMut, Item};
+
+use super::{migrate, Config, ConfigDocument, Deprecation, PartialConfig};
+use crate::{
+    fingerprint::Fingerprint,
+    partial::{Complete, Partial},
+};
+
+impl Config {
+    /// Reads the configuration that applies when running `cmd`.
+    pub fn read(cmd: &[String]) -> anyhow::Result<Config> {
+        PartialConfig::read(cmd).map(PartialConfig::into_complete)
+    }
+
+    /// Parses a configuration given as a TOML string, e.g., when embedding backtracetk. Options not
+    /// set in `contents` take their default value, and `[command."..."]` sections are ignored.
+    pub fn from_toml(contents: &str) -> anyhow::Result<Config> {
+        let file: ConfigFile = toml::from_str(contents)?;
+        Ok(Config::default()
+            .into_partial()
+            .merge_with(file.config)
+            .into_complete())
+    }
+
+    /// Renders the configuration as TOML with every option annotated with its documentation.
+    /// Options without a value (i.e., unset optional ones) are written as commented-out entries
+    /// with the first example in their documentation, see [`doc_example`].
+    pub fn to_commented_toml(&self) -> String {
+        let mut doc: DocumentMut = self.to_string().parse().unwrap();
+        // The commented-out entries waiting for the next option of the same table, by table
+        let mut unset: Vec<(Vec<&'static str>, String)> = vec![];
+        Config::visit_docs(&mut vec![], &mut |path, docs| {
+            let Some((last, prefix)) = path.split_last() else {
+                return;
+            };
+            let before: String = unset
+                .iter()
+                .filter(|(table, _)| table == prefix)
+                .map(|(_, entry)| entry.as_str())
+                .collect();
+            if set_comment(doc.as_table_mut(), path, docs, &before) {
+                unset.retain(|(table, _)| table != prefix);
+            } else {
+                let value = doc_example(docs).unwrap_or("...");
+                let entry = format!("{docs}\n{last} = {value}");
+                unset.push((prefix.to_vec(), format!("\n{}", comment(&entry))));
+            }
+        });
+        // Entries after the last option of their table go before its first one instead
+        for (table, entry) in unset {
+            prepend_to_first_value(doc.as_table_mut(), &table, &entry);
+        }
+        doc.to_string().trim_start().to_string()
+    }
+
+    /// Like [`Config::read`], also returning where each value in the configuration comes from. The
+    /// files are only read once, so their warnings are only printed once.
+    pub fn read_with_provenance(cmd: &[String]) -> anyhow::Result<(Config, Provenance)> {
+        let layers = PartialConfig::read_layers(cmd)?;
+        let mut provenance = Provenance::default();
+        for (source, layer) in &layers {
+            layer.visit_set(&mut vec![], &mut |path| {
+                provenance.record(path.join("."), source.clone());
+            });
+        }
+        let config = PartialConfig::merge_layers(layers).into_complete();
+        Ok((config, provenance))
+    }
+
+    /// The configuration files that would be read: the global one in the home directory followed
+    /// by the local one, if they exist.
+    pub fn files() -> Vec<PathBuf> {
+        let home = PartialConfig::find_home_file();
+        let local = PartialConfig::find_local_file().filter(|local| Some(local) != home.as_ref());
+        home.into_iter().chain(local).collect()
+    }
+
+    /// The local configuration file, i.e., the closest one in the current directory or its
+    /// parents, if any.
+    pub fn local_file() -> Option<PathBuf> {
+        PartialConfig::find_local_file()
+    }
+}
+
+/// Rewrites deprecated keys in the configuration file at `path`. The file is only written if it
+/// contains deprecated keys. Returns the keys that were migrated.
+pub fn migrate_file(path: &Path) -> anyhow::Result<Vec<Deprecation>> {
+    let mut doc = ConfigDocument::open(path)?;
+    let deprecations = doc.migrate()?;
+    if !deprecations.is_empty() {
+        doc.save()?;
+    }
+    Ok(deprecations)
+}
+
+/// Sets `known_panics` in the configuration file at `path` to `fingerprints`, creating the file if
+/// it doesn't exist.
+pub fn set_known_panics(path: &Path, fingerprints: &[Fingerprint]) -> anyhow::Result<()> {
+    let mut doc = ConfigDocument::open(path)?;
+    let fingerprints: toml_edit::Array = fingerprints.iter().map(|fp| fp.to_string()).collect();
+    doc.set(&["known_panics"], fingerprints)?;
+    doc.save()
+}
+
+impl fmt::Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", toml::to_string_pretty(self).unwrap())
+    }
+}
+
+/// Sets `docs` as the comment preceding the value at `path` in `table`.
+/// Sets `docs` as the comment of the option at `path`, preceded by `before` if it's a value.
+/// Returns whether the option is in `table`.
+fn set_comment(table: &mut toml_edit::Table, path: &[&str], docs: &str, before: &str) -> bool {
+    let Some((last, prefix)) = path.split_last() else {
+        return false;
+    };
+    let Some(table) = find_table(table, prefix) else {
+        return false;
+    };
+    let comment = comment(docs);
+    match table.get_mut(last) {
+        Some(Item::Table(inner)) => inner.decor_mut().set_prefix(format!("\n{comment}")),
+        Some(Item::ArrayOfTables(array)) => {
+            if let Some(first) = array.get_mut(0) {
+                first.decor_mut().set_prefix(format!("\n{comment}"));
+            }
+        }
+        Some(Item::Value(_)) => {
+            if let Some(mut key) = table.key_mut(last) {
+                key.leaf_decor_mut()
+                    .set_prefix(format!("{before}\n{comment}"));
+            }
+        }
+        _ => return false,
+    }
+    true
+}
+
+/// Adds `entry` before the first value of the table at `path`, if any.
+fn prepend_to_first_value(table: &mut toml_edit::Table, path: &[&str], entry: &str) {
+    let Some(table) = find_table(table, path) else {
+        return;
+    };
+    let Some(first) = table
+        .iter()
+        .find(|(_, item)| item.is_value())
+        .map(|(key, _)| key.to_string())
+    else {
+        return;
+    };
+    if let Some(mut key) = table.key_mut(&first) {
+        let prefix = key.leaf_decor().prefix().and_then(|prefix| prefix.as_str());
+        let prefix = format!("{entry}{}", prefix.unwrap_or_default());
+        key.leaf_decor_mut().set_prefix(prefix);
+    }
+}
+
+fn find_table<'a>(
+    mut table: &'a mut toml_edit::Table,
+    path: &[&str],
+) -> Option<&'a mut toml_edit::Table> {
+    for key in path {
+        table = table.get_mut(key)?.as_table_mut()?;
+    }
+    Some(table)
+}
+
+/// Turns every line of `text` into a TOML comment.
+fn comment(text: &str) -> String {
+    text.lines()
+        .map(|line| format!("# {line}").trim_end().to_string() + "\n")
+        .collect()
+}
+
+/// The first example value in the documentation of an option, i.e., the first string (quoted like
+/// in TOML) or integer after "e.g.,".
+fn doc_example(docs: &str) -> Option<&str> {
+    docs.match_indices("e.g.,")
+        .chain(docs.match_indices("E.g.,"))
+        .filter_map(|(i, prefix)| {
+            let rest = docs[i + prefix.len()..].trim_start();
+            let len = match rest.chars().next()? {
+                '\'' => rest[1..].find(['\'', '\n'])? + 2,
+                '"' => {
+                    let end = rest[1..]
+                        .match_indices(['"', '\n'])
+                        .find(|(j, _)| !rest[..*j + 1].ends_with('\\'))?
+                        .0;
+                    end + 2
+                }
+                c if c.is_ascii_digit() => rest.find(|c: char| !c.is_ascii_digit())?,
+                _ => return None,
+            };
+            let value = &rest[..len];
+            (!value.ends_with('\n')).then_some((i, value))
+        })
+        .min_by_key(|(i, _)| *i)
+        .map(|(_, value)| value)
+}
+/// Where a configuration value comes from.
+#[derive(Clone, Debug)]
+pub enum Source {
+    /// The built-in default configuration, used when there's no global configuration file.
+    Default,
+    /// A configuration file, either at the top level or inside a `[command."..."]` section.
+    File {
+        path: PathBuf,
+        command: Option<String>,
+    },
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Default => write!(f, "default"),
+            Source::File {
+                path,
+                command: None,
+            } => write!(f, "{}", path.display()),
+            Source::File {
+                path,
+                command: Some(command),
+            } => write!(f, "{} [command.\"{command}\"]", path.display()),
+        }
+    }
+}
+
+/// The sources that set each configuration value, in the order they were merged. For values that
+/// are replaced when merging, the last source is the one that provided the final value. For lists
+/// and maps (e.g., `hide` or `env`) every source contributes entries.
+#[derive(Debug, Default)]
+pub struct Provenance {
+    values: Vec<(String, Vec<Source>)>,
+}
+
+impl Provenance {
+    /// The sources for the value at the dotted `path`, e.g., `hyperlinks.enabled`.
+    pub fn get(&self, path: &str) -> &[Source] {
+        self.values
+            .iter()
+            .find(|(p, _)| p == path)
+            .map(|(_, sources)| &sources[..])
+            .unwrap_or_default()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &[Source])> {
+        self.values
+            .iter()
+            .map(|(path, sources)| (&path[..], &sources[..]))
+    }
+
+    fn record(&mut self, path: String, source: Source) {
+        match self.values.iter_mut().find(|(p, _)| *p == path) {
+            Some((_, sources)) => sources.push(source),
+            None => self.values.push((path, vec![source])),
+        }
+    }
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self.values.iter().map(|(path, _)| path.len()).max();
+        for (path, sources) in &self.values {
+            let sources = sources.iter().map(ToString::to_string).collect::<Vec<_>>();
+            writeln!(
+                f,
+                "{path:width$}  {}",
+                sources.join(", "),
+                width = width.unwrap_or(0)
+            )?;
+        }
+        Ok(())
+    }
+}
+/// A configuration file is a regular configuration plus a set of `[command."..."]` sections
+/// holding settings that only apply when the wrapped command matches the section's name.
+#[derive(Deserialize)]
+struct ConfigFile {
+    #[serde(flatten)]
+    config: PartialConfig,
+    /// In the order they appear in the file
+    #[serde(default, deserialize_with = "in_file_order")]
+    command: Vec<(String, PartialConfig)>,
+}
+
+/// Deserializes a table as its entries in the order they appear in the file.
+fn in_file_order<'de, D, T>(deserializer: D) -> Result<Vec<(String, T)>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    struct Visitor<T>(std::marker::PhantomData<T>);
+
+    impl<'de, T: Deserialize<'de>> serde::de::Visitor<'de> for Visitor<T> {
+        type Value = Vec<(String, T)>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a table")
+        }
+
+        fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+        where
+            A: serde::de::MapAccess<'de>,
+        {
+            let mut entries = vec![];
+            while let Some(entry) = map.next_entry()? {
+                entries.push(entry);
+            }
+            Ok(entries)
+        }
+    }
+
+    deserializer.deserialize_map(Visitor(std::marker::PhantomData))
+}
+
+impl ConfigFile {
+    /// Returns the layers in this file that apply to `cmd`: the top-level configuration followed by
+    /// the matching command sections.
+    fn into_layers(self, path: &Path, cmd: &[String]) -> Vec<(Source, PartialConfig)> {
+        let mut sections = self
+            .command
+            .into_iter()
+            .filter(|(pattern, _)| command_matches(pattern, cmd))
+            .collect::<Vec<_>>();
+        // Apply more specific sections last so they take precedence. The sort is stable, so among
+        // sections as specific, the last one in the file does.
+        sections.sort_by_key(|(pattern, _)| pattern.split_whitespace().count());
+
+        let source = |command| Source::File {
+            path: path.to_path_buf(),
+            command,
+        };
+        let mut layers = vec![(source(None), self.config)];
+        for (pattern, section) in sections {
+            layers.push((source(Some(pattern)), section));
+        }
+        layers
+    }
+}
+
+/// Whether the command section named `pattern` applies to `cmd`. A section matches if its words
+/// are a prefix of the command's arguments, e.g., `"cargo test"` matches `cargo test --release`.
+/// The program itself is also matched against its file name, so `"my-server"` matches
+/// `./target/debug/my-server`.
+fn command_matches(pattern: &str, cmd: &[String]) -> bool {
+    let mut words = pattern.split_whitespace().peekable();
+    if words.peek().is_none() {
+        return false;
+    }
+    let mut args = cmd.iter();
+    if let Some(program) = args.next() {
+        let name = Path::new(program)
+            .file_name()
+            .and_then(|name| name.to_str());
+        match words.next() {
+            Some(word) if word == program || Some(word) == name => {}
+            _ => return false,
+        }
+    }
+    for word in words {
+        if args.next().map(String::as_str) != Some(word) {
+            return false;
+        }
+    }
+    true
+}
+
+impl PartialConfig {
+    fn read(cmd: &[String]) -> anyhow::Result<PartialConfig> {
+        Ok(PartialConfig::merge_layers(PartialConfig::read_layers(
+            cmd,
+        )?))
+    }
+
+    /// Reads the configuration files that apply when running `cmd` without filling in any
+    /// defaults. This makes it possible to tell whether a value was set explicitly using
+    /// [`Partial::is_set`], e.g., `config.hyperlinks.enabled.is_set()`.
+    pub fn read_explicit(cmd: &[String]) -> anyhow::Result<PartialConfig> {
+        Ok(PartialConfig::merge_layers(
+            PartialConfig::read_file_layers(cmd)?,
+        ))
+    }
+
+    fn merge_layers(layers: Vec<(Source, PartialConfig)>) -> PartialConfig {
+        layers
+            .into_iter()
+            .fold(PartialConfig::default(), |config, (_, layer)| {
+                config.merge_with(layer)
+            })
+    }
+
+    /// All the layers that make up the configuration for `cmd` in the order they are merged.
+    fn read_layers(cmd: &[String]) -> anyhow::Result<Vec<(Source, PartialConfig)>> {
+        let mut layers = vec![];
+        // The default configuration is only used when there's no global configuration file.
+        if PartialConfig::find_home_file().is_none() {
+            layers.push((Source::Default, Config::default().into_partial()));
+        }
+        layers.extend(PartialConfig::read_file_layers(cmd)?);
+        Ok(layers)
+    }
+
+    fn read_file_layers(cmd: &[String]) -> anyhow::Result<Vec<(Source, PartialConfig)>> {
+        let mut layers = vec![];
+        for file in Config::files() {
+            layers.extend(PartialConfig::parse_file(&file)?.into_layers(&file, cmd));
+        }
+        Ok(layers)
+    }
+
+    fn parse_file(path: &Path) -> anyhow::Result<ConfigFile> {
+        let mut contents = fs::read_to_string(path)?;
+        let (doc, deprecations) = migrate::migrate(&contents)?;
+        if !deprecations.is_empty() {
+            for deprecation in &deprecations {
+                eprintln!(
+                    "Warning: {}:{}: {deprecation}",
+                    path.display(),
+                    deprecation.line
+                );
+            }
+            eprintln!("Run `backtracetk config migrate` to update the configuration file");
+            contents = doc.to_string();
+        }
+        Ok(toml::from_str(&contents)?)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn find_home_file() -> Option<PathBuf> {
+        let home_dir = home::home_dir()?;
+        PartialConfig::find_file_in(&home_dir)
+    }
+
+    /// There's no home directory when running in the browser
+    #[cfg(target_arch = "wasm32")]
+    fn find_home_file() -> Option<PathBuf> {
+        None
+    }
+
+    fn find_local_file() -> Option<PathBuf> {
+        let mut path = std::env::current_dir().unwrap();
+        loop {
+            if let Some(file) = PartialConfig::find_file_in(&path) {
+                return Some(file);
+            }
+            if !path.pop() {
+                return None;
+            }
+        }
+    }
+
+    fn find_file_in(dir: &Path) -> Option<PathBuf> {
+        for name in ["backtracetk.toml", ".backtracetk.toml"] {
+            let file = dir.join(name);
+            if file.exists() {
+                return Some(file);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::ConfigFile;
+    use crate::config::{BacktraceStyle, Config, Hide, PartialConfig};
+    use crate::partial::Partial;
+
+    /// Merges the files in `files` in order, for running `cmd`.
+    fn merge(files: &[&str], cmd: &str) -> Config {
+        let cmd: Vec<String> = cmd.split_whitespace().map(str::to_string).collect();
+        let layers = files
+            .iter()
+            .flat_map(|contents| {
+                let file: ConfigFile = toml::from_str(contents).unwrap();
+                file.into_layers(Path::new("backtracetk.toml"), &cmd)
+            })
+            .collect();
+        PartialConfig::merge_layers(layers).into_complete()
+    }
+
+    #[test]
+    fn later_files_take_precedence() {
+        let global = "style = \"full\"\ndetailed_frames = 7\n[notify]\ndesktop = true";
+        let local = "detailed_frames = 1\n[notify]\ndesktop = false";
+        let config = merge(&[global, local], "cargo test");
+        assert_eq!(config.style, BacktraceStyle::Full);
+        assert_eq!(config.detailed_frames, 1);
+        assert!(!config.notify.desktop);
+    }
+
+    /// `merge_with` puts `other` on top of `self`: its values take precedence over the ones in
+    /// `self`, values it leaves unset are kept, and its rules come after the ones in `self`.
+    #[test]
+    fn merge_with_precedence() {
+        let parse = |contents| toml::from_str::<ConfigFile>(contents).unwrap().config;
+        let global = parse("compact = true\ndetailed_frames = 7\n[[hide]]\npattern = \"global\"");
+        let local = parse("detailed_frames = 1\n[[hide]]\npattern = \"local\"");
+        let config = global.merge_with(local).into_complete();
+        assert!(config.compact);
+        assert_eq!(config.detailed_frames, 1);
+        let patterns: Vec<&str> = config
+            .hide
+            .iter()
+            .map(|hide| match hide {
+                Hide::Pattern { pattern } => pattern.as_str(),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(patterns, ["global", "local"]);
+    }
+
+    /// A collection is set if its key is present, even if it's empty, so an empty collection in
+    /// a file is attributed to that file.
+    #[test]
+    fn empty_collections_are_set() {
+        let file: ConfigFile = toml::from_str("hide = []\nenv = {}").unwrap();
+        let mut set = vec![];
+        file.config
+            .visit_set(&mut vec![], &mut |path| set.push(path.join(".")));
+        assert_eq!(set, ["env", "hide"]);
+        let config = PartialConfig::default().merge_with(file.config);
+        assert!(config.hide.is_set());
+        assert!(!config.show.is_set());
+    }
+
+    #[test]
+    fn command_sections_take_precedence() {
+        let file = r#"
+            detailed_frames = 1
+            [command."cargo test"]
+            detailed_frames = 3
+            [command."cargo"]
+            detailed_frames = 2
+        "#;
+        assert_eq!(merge(&[file], "cargo test --release").detailed_frames, 3);
+        assert_eq!(merge(&[file], "cargo run").detailed_frames, 2);
+        assert_eq!(merge(&[file], "make").detailed_frames, 1);
+    }
+
+    /// Among sections as specific, the last one in the file takes precedence.
+    #[test]
+    fn command_sections_in_file_order() {
+        let by_name = "[command.\"srv\"]\ndetailed_frames = 1\n";
+        let by_path = "[command.\"./bin/srv\"]\ndetailed_frames = 2\n";
+        let config = merge(&[&format!("{by_name}{by_path}")], "./bin/srv");
+        assert_eq!(config.detailed_frames, 2);
+        let config = merge(&[&format!("{by_path}{by_name}")], "./bin/srv");
+        assert_eq!(config.detailed_frames, 1);
+    }
+
+    /// The commented-out entries of unset options are valid once uncommented.
+    #[test]
+    fn commented_out_entries() {
+        let commented = Config::default().to_commented_toml();
+        let uncommented: String = commented
+            .lines()
+            .map(|line| match line.strip_prefix("# ") {
+                Some(entry) if is_entry(entry) => entry,
+                _ => line,
+            })
+            .map(|line| format!("{line}\n"))
+            .collect();
+        let config = Config::from_toml(&uncommented).unwrap();
+        assert_eq!(config.max_echo_rate, Some(1000));
+        assert!(config.log_prefix.is_some());
+        assert!(config.frame_format.is_some());
+        assert!(config.header_format.is_some());
+        assert!(config.panic_format.is_some());
+        assert!(config.notify.command.is_some());
+        assert!(config.on_backtrace.command.is_some());
+        assert!(config.plugin.command.is_some());
+    }
+
+    #[test]
+    fn hide_rejects_leftover_keys() {
+        let hide = |rule: &str| Config::from_toml(&format!("[[hide]]\n{rule}"));
+        assert!(hide("pattern = 'a'\ncase_insensitive = true").is_ok());
+        for (rule, error) in [
+            ("pattern = 'a'\npatern = 'b'", "unknown field `patern`"),
+            ("pattern = 'a'\nenabled = false", "unknown field `enabled`"),
+            ("pattern = 'a'\ncase_insensitive = 1", "did not match"),
+            ("pattern = 'a'\ncase_insensitive = ''", "a boolean"),
+            ("pattern = 'a'\nend = 'b'", "can only be used with `begin`"),
+            ("begin = 'a'\nend = true", "`end` must be a string"),
+        ] {
+            let err = hide(rule).unwrap_err().to_string();
+            assert!(err.contains(error), "{rule}: {err}");
+        }
+    }
+
+    #[test]
+    fn known_panics_are_fingerprints() {
+        let config = Config::from_toml("known_panics = ['00c0ffee12345678']").unwrap();
+        assert_eq!(config.known_panics[0].to_string(), "00c0ffee12345678");
+        for fingerprint in ["c0ffee", "00c0ffee1234567g"] {
+            let toml = format!("known_panics = ['{fingerprint}']");
+            let err = Config::from_toml(&toml).unwrap_err().to_string();
+            assert!(err.contains("invalid fingerprint"), "{fingerprint}: {err}");
+        }
+    }
+
+    fn is_entry(line: &str) -> bool {
+        line.split_once(" = ")
+            .is_some_and(|(key, _)| key.chars().all(|c| c.is_ascii_lowercase() || c == '_'))
+    }
+}
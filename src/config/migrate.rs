@@ -0,0 +1,233 @@
+//! Support for configuration keys that have been renamed or replaced.
+//!
+//! Deprecated keys are still accepted when reading a configuration file, but a warning is
+//! reported for each of them. The `config migrate` subcommand rewrites the files in place through
+//! [`ConfigDocument::migrate`].
+//!
+//! [`ConfigDocument::migrate`]: super::ConfigDocument::migrate
+
+use std::fmt;
+
+use toml_edit::{DocumentMut, ImDocument, Item, Key, TableLike, Value};
+
+/// A configuration key that has been replaced by a different one.
+struct Rename {
+    old: &'static str,
+    new: &'static str,
+    /// Converts the value of the old key into a value for the new key. Returns `None` if the
+    /// value has the wrong type, in which case the old key is left untouched.
+    convert: fn(&Value) -> Option<Value>,
+}
+
+const RENAMES: &[Rename] = &[Rename {
+    old: "hide_output",
+    new: "echo",
+    convert: negate,
+}];
+
+fn negate(value: &Value) -> Option<Value> {
+    value.as_bool().map(|b| Value::from(!b))
+}
+
+/// A use of a deprecated key in a configuration file.
+pub struct Deprecation {
+    /// The `[command."..."]` section containing the key or `None` if it's at the top level.
+    pub command: Option<String>,
+    pub old: &'static str,
+    pub new: &'static str,
+    /// The (1-based) line where the deprecated key appears.
+    pub line: usize,
+}
+
+impl fmt::Display for Deprecation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let prefix = match &self.command {
+            Some(command) => format!("command.\"{command}\"."),
+            None => String::new(),
+        };
+        write!(
+            f,
+            "`{prefix}{}` is deprecated, use `{prefix}{}` instead",
+            self.old, self.new
+        )
+    }
+}
+
+/// Rewrites all deprecated keys in `contents` to their replacements, preserving comments and
+/// formatting. Returns the rewritten document together with the deprecated keys that were found.
+pub fn migrate(contents: &str) -> anyhow::Result<(DocumentMut, Vec<Deprecation>)> {
+    let doc = ImDocument::parse(contents)?;
+
+    let mut deprecations = vec![];
+    find_deprecations(contents, doc.as_table(), None, &mut deprecations);
+    if let Some(commands) = doc.get("command").and_then(|item| item.as_table_like()) {
+        for (command, item) in commands.iter() {
+            if let Some(table) = item.as_table_like() {
+                find_deprecations(contents, table, Some(command), &mut deprecations);
+            }
+        }
+    }
+
+    let mut doc = doc.into_mut();
+    for deprecation in &deprecations {
+        let table = match &deprecation.command {
+            Some(command) => doc["command"][command].as_table_like_mut(),
+            None => Some(doc.as_table_mut() as &mut dyn TableLike),
+        };
+        if let Some(table) = table {
+            apply_rename(table, deprecation);
+        }
+    }
+    Ok((doc, deprecations))
+}
+
+fn find_deprecations(
+    contents: &str,
+    table: &dyn TableLike,
+    command: Option<&str>,
+    deprecations: &mut Vec<Deprecation>,
+) {
+    for rename in RENAMES {
+        let Some((key, item)) = table.get_key_value(rename.old) else {
+            continue;
+        };
+        if item.as_value().and_then(rename.convert).is_none() {
+            continue;
+        }
+        let offset = key.span().map(|span| span.start).unwrap_or(0);
+        deprecations.push(Deprecation {
+            command: command.map(str::to_string),
+            old: rename.old,
+            new: rename.new,
+            line: contents[..offset].matches('\n').count() + 1,
+        });
+    }
+}
+
+fn apply_rename(table: &mut dyn TableLike, deprecation: &Deprecation) {
+    let Some(rename) = RENAMES.iter().find(|rename| rename.old == deprecation.old) else {
+        return;
+    };
+    let decor = table
+        .key(rename.old)
+        .map(|key| key.leaf_decor().clone())
+        .unwrap_or_default();
+    let after: Vec<String> = table
+        .iter()
+        .map(|(key, _)| key.to_string())
+        .skip_while(|key| key != rename.old)
+        .skip(1)
+        .collect();
+    let Some(item) = table.remove(rename.old) else {
+        return;
+    };
+    let Some(old_value) = item.as_value() else {
+        return;
+    };
+    let Some(mut new_value) = (rename.convert)(old_value) else {
+        return;
+    };
+    *new_value.decor_mut() = old_value.decor().clone();
+    // If the new key is also present, it takes precedence and the old one is simply dropped.
+    table
+        .entry_format(&Key::new(rename.new).with_leaf_decor(decor))
+        .or_insert(Item::Value(new_value));
+    // The new key is inserted last, so the keys that followed the old one are moved after it to
+    // keep the order of the file
+    for key in after {
+        let formatted = table.key(&key).cloned();
+        if let (Some(formatted), Some(item)) = (formatted, table.remove(&key)) {
+            table.entry_format(&formatted).or_insert(item);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::migrate;
+    use crate::config::migrate_file;
+
+    const CONFIG: &str = r#"# Don't clutter the output
+hide_output = true # the command is noisy
+style = "full"
+
+[command."cargo test"]
+hide_output = false
+"#;
+
+    #[test]
+    fn renames_deprecated_keys() {
+        let (doc, deprecations) = migrate(CONFIG).unwrap();
+        assert_eq!(
+            doc.to_string(),
+            r#"# Don't clutter the output
+echo = false # the command is noisy
+style = "full"
+
+[command."cargo test"]
+echo = true
+"#
+        );
+        let found: Vec<_> = deprecations
+            .iter()
+            .map(|d| (d.command.as_deref(), d.old, d.new))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (None, "hide_output", "echo"),
+                (Some("cargo test"), "hide_output", "echo")
+            ]
+        );
+    }
+
+    /// The warnings point to the line of the deprecated key and name its section.
+    #[test]
+    fn warnings() {
+        let (_, deprecations) = migrate(CONFIG).unwrap();
+        let warnings: Vec<_> = deprecations
+            .iter()
+            .map(|d| format!("{}: {d}", d.line))
+            .collect();
+        assert_eq!(
+            warnings,
+            [
+                "2: `hide_output` is deprecated, use `echo` instead",
+                "6: `command.\"cargo test\".hide_output` is deprecated, use \
+                 `command.\"cargo test\".echo` instead",
+            ]
+        );
+    }
+
+    /// If the new key is also present, the old one is dropped.
+    #[test]
+    fn new_key_takes_precedence() {
+        let (doc, deprecations) = migrate("echo = true\nhide_output = true\n").unwrap();
+        assert_eq!(doc.to_string(), "echo = true\n");
+        assert_eq!(deprecations.len(), 1);
+    }
+
+    /// A value of the wrong type isn't migrated, so it's reported when deserializing instead.
+    #[test]
+    fn wrong_type() {
+        let (doc, deprecations) = migrate("hide_output = \"yes\"\n").unwrap();
+        assert_eq!(doc.to_string(), "hide_output = \"yes\"\n");
+        assert!(deprecations.is_empty());
+    }
+
+    #[test]
+    fn migrates_file() {
+        let path =
+            std::env::temp_dir().join(format!("backtracetk-migrate-{}.toml", std::process::id()));
+        std::fs::write(&path, CONFIG).unwrap();
+        let deprecations = migrate_file(&path).unwrap();
+        let migrated = std::fs::read_to_string(&path).unwrap();
+        // A migrated file has nothing left to migrate
+        let again = migrate_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(deprecations.len(), 2);
+        assert_eq!(migrated, migrate(CONFIG).unwrap().0.to_string());
+        assert!(!migrated.contains("hide_output"));
+        assert!(again.is_empty());
+    }
+}
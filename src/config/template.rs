@@ -0,0 +1,151 @@
+//! Templates reshaping parts of the rendered output, e.g.,
+//! ```toml
+//! frame_format = "{frameno}: {function} @ {file}:{line}"
+//! ```
+//! A template is a string where variables are written in braces. Each template has its own set of
+//! variables, see [`FrameVars`], [`HeaderVars`], and [`PanicVars`]. `{{` and `}}` are literal
+//! braces. Templates are validated when the configuration is read, so a misspelled variable is
+//! reported right away instead of when a backtrace is rendered.
+
+use std::{fmt, marker::PhantomData};
+
+use serde::{de::Error, Deserialize, Serialize};
+
+/// The variables available in a kind of template.
+pub trait Vars {
+    const NAMES: &'static [&'static str];
+}
+
+/// Variables for `frame_format`: `frameno`, `function`, `file`, `line`, and `column`. The location
+/// variables are empty if the frame has no location.
+#[derive(Clone, Debug)]
+pub enum FrameVars {}
+
+impl Vars for FrameVars {
+    const NAMES: &'static [&'static str] = &["frameno", "function", "file", "line", "column"];
+}
+
+/// Variables for `header_format`: `command`, `test`, `time`, `rustc`, and `fingerprint`. All but
+/// `fingerprint` are empty if unknown.
+#[derive(Clone, Debug)]
+pub enum HeaderVars {}
+
+impl Vars for HeaderVars {
+    const NAMES: &'static [&'static str] = &["command", "test", "time", "rustc", "fingerprint"];
+}
+
+/// Variables for `panic_format`: `thread`, `location`, and `message`. `thread` is empty if the
+/// format doesn't report it.
+#[derive(Clone, Debug)]
+pub enum PanicVars {}
+
+impl Vars for PanicVars {
+    const NAMES: &'static [&'static str] = &["thread", "location", "message"];
+}
+
+#[derive(Clone, Debug)]
+pub struct Template<V> {
+    source: String,
+    parts: Vec<Part>,
+    _vars: PhantomData<V>,
+}
+
+#[derive(Clone, Debug)]
+enum Part {
+    Literal(String),
+    Var(&'static str),
+}
+
+impl<V: Vars> Template<V> {
+    pub fn new(source: &str) -> Result<Template<V>, String> {
+        let mut parts = vec![];
+        let mut literal = String::new();
+        let mut chars = source.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let rest = chars.as_str();
+                    let Some((name, after)) = rest.split_once('}') else {
+                        return Err(format!("unclosed `{{` in template `{source}`"));
+                    };
+                    let Some(&var) = V::NAMES.iter().find(|&&var| var == name) else {
+                        return Err(format!(
+                            "unknown variable `{{{name}}}` in template `{source}`, expected one of {}",
+                            V::NAMES
+                                .iter()
+                                .map(|var| format!("`{{{var}}}`"))
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        ));
+                    };
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Var(var));
+                    chars = after.chars();
+                }
+                '}' => return Err(format!("unmatched `}}` in template `{source}`")),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Ok(Template {
+            source: source.to_string(),
+            parts,
+            _vars: PhantomData,
+        })
+    }
+}
+
+impl<V> Template<V> {
+    /// Renders the template taking the value of each variable from `vars`. Variables missing from
+    /// `vars` are rendered as empty.
+    pub fn render(&self, vars: &[(&str, &dyn fmt::Display)]) -> String {
+        let mut s = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(literal) => s.push_str(literal),
+                Part::Var(var) => {
+                    if let Some((_, value)) = vars.iter().find(|(name, _)| name == var) {
+                        s.push_str(&value.to_string());
+                    }
+                }
+            }
+        }
+        s
+    }
+
+    /// The template as written in the configuration.
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+}
+
+impl<V> Serialize for Template<V> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.source)
+    }
+}
+
+impl<'de, V: Vars> Deserialize<'de> for Template<V> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let source = String::deserialize(deserializer)?;
+        Template::new(&source).map_err(D::Error::custom)
+    }
+}
@@ -0,0 +1,175 @@
+//! Converting backtraces written as JSON, e.g., with `--format json`, between versions of the
+//! schema, see [`backtracetk::model::SCHEMA_VERSION`] and `backtracetk convert`. Each version is
+//! converted to the next or the previous one in turn, so a conversion only needs to know about the
+//! change between two consecutive versions.
+
+use std::io::{self, BufRead};
+
+use anyhow::Context;
+use backtracetk::model::SCHEMA_VERSION;
+use serde_json::{json, Map, Value};
+
+type Object = Map<String, Value>;
+
+/// The fields of a backtrace that may be missing in JSON written before versioning, with the value
+/// they had when they weren't printed.
+fn unversioned_defaults() -> [(&'static str, Value); 11] {
+    [
+        ("duplicates", json!(0)),
+        ("command", Value::Null),
+        ("test", Value::Null),
+        ("time", Value::Null),
+        ("build", Value::Null),
+        ("process", Value::Null),
+        ("spans", json!([])),
+        ("span_trace", json!([])),
+        ("diagnostics", json!([])),
+        ("note", Value::Null),
+        ("trailer", json!([])),
+    ]
+}
+
+/// `UPGRADES[n]` converts version `n` to `n + 1`.
+const UPGRADES: &[fn(&mut Object)] = &[upgrade_unversioned];
+
+/// `DOWNGRADES[n]` converts version `n + 1` to `n`.
+const DOWNGRADES: &[fn(&mut Object)] = &[downgrade_to_unversioned];
+
+/// Converts every backtrace in `input`, one per line, to version `to`, writing them to `out` in the
+/// same format.
+pub fn run(input: impl BufRead, to: u32, out: &mut impl io::Write) -> anyhow::Result<()> {
+    if to > SCHEMA_VERSION {
+        anyhow::bail!("unknown schema version {to}, the latest is {SCHEMA_VERSION}");
+    }
+    for (i, line) in input.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut value: Value =
+            serde_json::from_str(&line).with_context(|| format!("line {}: invalid JSON", i + 1))?;
+        let Some(backtrace) = value.as_object_mut() else {
+            anyhow::bail!("line {}: expected a backtrace", i + 1);
+        };
+        convert(backtrace, to).with_context(|| format!("line {}", i + 1))?;
+        serde_json::to_writer(&mut *out, &value)?;
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+fn convert(backtrace: &mut Object, to: u32) -> anyhow::Result<()> {
+    let version = match backtrace.get("schema_version") {
+        None => 0,
+        Some(version) => match version.as_u64() {
+            Some(version) => version,
+            None => anyhow::bail!("invalid `schema_version` `{version}`"),
+        },
+    };
+    // Compared before narrowing it, so a huge version isn't taken for a known one
+    let Some(mut version) = u32::try_from(version)
+        .ok()
+        .filter(|version| *version <= SCHEMA_VERSION)
+    else {
+        anyhow::bail!(
+            "schema version {version} was written by a newer version of backtracetk, update it to \
+             convert it"
+        );
+    };
+    while version < to {
+        UPGRADES[version as usize](backtrace);
+        version += 1;
+    }
+    while version > to {
+        DOWNGRADES[version as usize - 1](backtrace);
+        version -= 1;
+    }
+    Ok(())
+}
+
+/// Fills in the fields older versions didn't print, so consumers of version 1 can rely on them.
+fn upgrade_unversioned(backtrace: &mut Object) {
+    for (field, default) in unversioned_defaults() {
+        backtrace.entry(field).or_insert(default);
+    }
+    if let Some(Value::Array(frames)) = backtrace.get_mut("frames") {
+        for frame in frames.iter_mut().filter_map(Value::as_object_mut) {
+            frame.entry("foreign").or_insert(json!(false));
+            frame.entry("annotations").or_insert(json!([]));
+        }
+    }
+    backtrace.insert("schema_version".to_string(), json!(1));
+}
+
+fn downgrade_to_unversioned(backtrace: &mut Object) {
+    backtrace.remove("schema_version");
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{convert, run};
+
+    #[test]
+    fn upgrade_unversioned() {
+        let mut backtrace = json!({
+            "frames": [{ "function": "app::main", "frameno": 0, "source_info": null }],
+            "panic_info": null,
+        });
+        convert(backtrace.as_object_mut().unwrap(), 1).unwrap();
+        assert_eq!(backtrace["schema_version"], 1);
+        assert_eq!(backtrace["duplicates"], 0);
+        assert_eq!(backtrace["spans"], json!([]));
+        assert_eq!(backtrace["frames"][0]["foreign"], false);
+        assert_eq!(backtrace["frames"][0]["annotations"], json!([]));
+    }
+
+    /// Upgrading and then downgrading gives back the original backtrace, and the other way around
+    /// for backtraces written by this version.
+    #[test]
+    fn round_trip() {
+        let unversioned = json!({
+            "frames": [{ "function": "app::main", "frameno": 0, "source_info": null }],
+            "panic_info": null,
+        });
+        let mut backtrace = unversioned.clone();
+        convert(backtrace.as_object_mut().unwrap(), 1).unwrap();
+        let upgraded = backtrace.clone();
+        convert(backtrace.as_object_mut().unwrap(), 0).unwrap();
+        assert!(backtrace.get("schema_version").is_none());
+        convert(backtrace.as_object_mut().unwrap(), 1).unwrap();
+        assert_eq!(backtrace, upgraded);
+
+        let mut parser = backtracetk::parse::Parser::new();
+        parser.parse_bytes(include_bytes!("../tests/fixtures/short.txt"));
+        let mut written = vec![];
+        for backtrace in parser.into_backtraces() {
+            serde_json::to_writer(&mut written, &backtrace).unwrap();
+            written.push(b'\n');
+        }
+        let mut downgraded = vec![];
+        run(&written[..], 0, &mut downgraded).unwrap();
+        let mut upgraded = vec![];
+        run(&downgraded[..], 1, &mut upgraded).unwrap();
+        // Compared as values since fields aren't written in the same order
+        let values = |json: &[u8]| {
+            serde_json::Deserializer::from_slice(json)
+                .into_iter()
+                .collect::<Result<Vec<serde_json::Value>, _>>()
+                .unwrap()
+        };
+        assert_eq!(values(&upgraded), values(&written));
+    }
+
+    #[test]
+    fn invalid_version() {
+        let newer = json!({ "schema_version": 2, "frames": [] });
+        // Would be version 1 if it was truncated to 32 bits
+        let huge = json!({ "schema_version": (1u64 << 32) + 1, "frames": [] });
+        let negative = json!({ "schema_version": -1, "frames": [] });
+        for mut backtrace in [newer, huge, negative] {
+            assert!(convert(backtrace.as_object_mut().unwrap(), 1).is_err());
+        }
+    }
+}
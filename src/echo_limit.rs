@@ -0,0 +1,55 @@
+//! Limiting how many lines are echoed per second, see the `max_echo_rate` option. Lines are still
+//! parsed at full speed, so no backtrace is missed.
+
+use std::time::{Duration, Instant};
+
+const WINDOW: Duration = Duration::from_secs(1);
+
+/// Lets through up to a number of lines in each one second window and counts the rest. The current
+/// time is passed explicitly, like for [`RateLimiter`](crate::rate_limit::RateLimiter).
+pub struct EchoLimiter {
+    max_lines: u32,
+    start: Instant,
+    echoed: u32,
+    skipped: usize,
+}
+
+impl EchoLimiter {
+    pub fn new(max_lines: u32, now: Instant) -> EchoLimiter {
+        EchoLimiter {
+            max_lines,
+            start: now,
+            echoed: 0,
+            skipped: 0,
+        }
+    }
+
+    /// Whether a line should be echoed in the current window, see [`EchoLimiter::expired`]. Lines
+    /// that are part of a backtrace are always echoed but still count towards the limit.
+    pub fn check(&mut self, backtrace_line: bool) -> bool {
+        if self.echoed < self.max_lines || backtrace_line {
+            self.echoed = self.echoed.saturating_add(1);
+            true
+        } else {
+            self.skipped += 1;
+            false
+        }
+    }
+
+    /// Starts a new window if the current one is over at `now`, returning how many lines were
+    /// skipped in it, if any.
+    pub fn expired(&mut self, now: Instant) -> Option<usize> {
+        if now.duration_since(self.start) < WINDOW {
+            return None;
+        }
+        self.start = now;
+        self.echoed = 0;
+        self.take_skipped()
+    }
+
+    /// How many lines were skipped in the current window, if any, e.g., when the command exits.
+    pub fn take_skipped(&mut self) -> Option<usize> {
+        let skipped = std::mem::take(&mut self.skipped);
+        (skipped > 0).then_some(skipped)
+    }
+}
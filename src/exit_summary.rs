@@ -0,0 +1,106 @@
+//! A machine-readable summary of a run, written when the command exits so scripts don't need to
+//! parse the rendered output. The summary is a JSON object like
+//! ```json
+//! {
+//!   "command": ["cargo", "test"],
+//!   "exit_code": 101,
+//!   "signal": null,
+//!   "duration_secs": 3.2,
+//!   "panics": 2,
+//!   "backtraces": [
+//!     {"fingerprint": "3b802e1b96ed75a5", "occurrences": 2, "message": "explicit panic", "location": "src/main.rs:2:5"}
+//!   ]
+//! }
+//! ```
+//! `exit_code` is null if the command was killed by a signal, in which case `signal` is set.
+//! When running several commands with `--then`, the command and its exit status are the ones of the
+//! last command that ran, while the backtraces are the ones of every command.
+
+use std::{fs::File, io::Write, path::Path, process::ExitStatus, time::Duration};
+
+use anyhow::Context;
+use backtracetk::model::{Backtrace, Fingerprint};
+use serde::Serialize;
+
+#[derive(Serialize)]
+pub struct ExitSummary<'a> {
+    command: &'a [String],
+    exit_code: Option<i32>,
+    signal: Option<i32>,
+    duration_secs: f64,
+    /// Number of panics, including duplicates
+    panics: usize,
+    /// Backtraces grouped by fingerprint in the order they were first seen
+    backtraces: Vec<Group>,
+}
+
+#[derive(Serialize)]
+pub struct Group {
+    pub fingerprint: Fingerprint,
+    pub occurrences: usize,
+    pub message: Option<String>,
+    pub location: Option<String>,
+}
+
+/// Groups `backtraces` by fingerprint in the order they were first seen.
+pub fn groups(backtraces: &[Backtrace]) -> Vec<Group> {
+    let mut groups: Vec<Group> = vec![];
+    for backtrace in backtraces {
+        let fingerprint = backtrace.fingerprint();
+        let occurrences = backtrace.duplicates + 1;
+        if let Some(group) = groups.iter_mut().find(|g| g.fingerprint == fingerprint) {
+            group.occurrences += occurrences;
+            continue;
+        }
+        let panic_info = backtrace.panic_info.as_ref();
+        groups.push(Group {
+            fingerprint,
+            occurrences,
+            message: panic_info.and_then(|p| p.message.first()).cloned(),
+            location: panic_info
+                .map(|p| p.at.trim_end_matches(':').to_string())
+                .filter(|at| !at.is_empty()),
+        });
+    }
+    groups
+}
+
+impl<'a> ExitSummary<'a> {
+    pub fn new(
+        command: &'a [String],
+        status: ExitStatus,
+        duration: Duration,
+        backtraces: &[Backtrace],
+    ) -> ExitSummary<'a> {
+        let groups = groups(backtraces);
+        ExitSummary {
+            command,
+            exit_code: status.code(),
+            signal: signal(status),
+            duration_secs: duration.as_secs_f64(),
+            panics: groups.iter().map(|g| g.occurrences).sum(),
+            backtraces: groups,
+        }
+    }
+
+    pub fn write(&self, mut file: File) -> anyhow::Result<()> {
+        serde_json::to_writer(&mut file, self)?;
+        writeln!(file)?;
+        Ok(())
+    }
+}
+
+pub fn create_file(path: &Path) -> anyhow::Result<File> {
+    File::create(path).with_context(|| format!("failed to create `{}`", path.display()))
+}
+
+#[cfg(unix)]
+fn signal(status: ExitStatus) -> Option<i32> {
+    use std::os::unix::process::ExitStatusExt;
+    status.signal()
+}
+
+#[cfg(not(unix))]
+fn signal(_: ExitStatus) -> Option<i32> {
+    None
+}
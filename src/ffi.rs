@@ -0,0 +1,106 @@
+//! C bindings for the parser and renderer, enabled with the `ffi` feature. They let tools written
+//! in other languages (e.g., editor plugins) reuse the parser instead of reimplementing it. See
+//! `include/backtracetk.h` for the C declarations.
+//!
+//! Strings returned by these functions are owned by the caller and must be released with
+//! [`backtracetk_string_free`]. On error, functions return null and the error can be retrieved
+//! with [`backtracetk_last_error`].
+
+use std::{
+    cell::RefCell,
+    ffi::{c_char, CStr, CString},
+    ptr,
+};
+
+use crate::{config::Config, filter::Filters, parse::Parser, Backtrace};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Parses the backtraces in `log` and returns them as a JSON array.
+///
+/// # Safety
+///
+/// `log` must be a valid pointer to a null-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn backtracetk_parse_json(log: *const c_char) -> *mut c_char {
+    let backtraces = parse(CStr::from_ptr(log), Parser::new());
+    into_c_string(serde_json::to_string(&backtraces).map_err(anyhow::Error::from))
+}
+
+/// Renders the backtraces in `log` as text with ANSI escape codes, like in a terminal. `config`
+/// has the same format as a configuration file and may be null to use the default configuration.
+///
+/// # Safety
+///
+/// `log` must be a valid pointer to a null-terminated string, and `config` must be either null or
+/// a valid pointer to a null-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn backtracetk_render_ansi(
+    log: *const c_char,
+    config: *const c_char,
+) -> *mut c_char {
+    let config = if config.is_null() {
+        Ok(Config::default())
+    } else {
+        Config::from_toml(&CStr::from_ptr(config).to_string_lossy())
+    };
+    into_c_string(config.and_then(|config| {
+        let mut backtraces = parse(CStr::from_ptr(log), Parser::from_config(&config));
+        for backtrace in &mut backtraces {
+            backtrace.redact(&config.redact);
+        }
+        if config.dedupe_adjacent {
+            backtraces = crate::parse::dedupe_adjacent(backtraces);
+        }
+        let mut out = vec![];
+        for backtrace in &backtraces {
+            backtrace.render_to(&mut out, &config, &mut Filters::from_config(&config))?;
+        }
+        Ok(String::from_utf8_lossy(&out).into_owned())
+    }))
+}
+
+/// Returns the message of the last error that occurred in the current thread, or null if there
+/// was none. The string is owned by the library and is valid until the next call to a function in
+/// this module.
+#[no_mangle]
+pub extern "C" fn backtracetk_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |err| err.as_ptr())
+    })
+}
+
+/// Releases a string returned by one of the functions in this module.
+///
+/// # Safety
+///
+/// `s` must be null or a string returned by this library that hasn't been released yet.
+#[no_mangle]
+pub unsafe extern "C" fn backtracetk_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+fn parse(log: &CStr, mut parser: Parser) -> Vec<Backtrace> {
+    parser.parse_bytes(log.to_bytes());
+    parser.into_backtraces()
+}
+
+/// Converts the result into a string owned by the caller, recording the error if there's one.
+fn into_c_string(result: anyhow::Result<String>) -> *mut c_char {
+    // Strings can't contain interior nulls when passed to C
+    let result = result.and_then(|s| Ok(CString::new(s)?));
+    LAST_ERROR.with(|last| {
+        let (s, err) = match result {
+            Ok(s) => (s.into_raw(), None),
+            Err(err) => (ptr::null_mut(), CString::new(format!("{err:#}")).ok()),
+        };
+        *last.borrow_mut() = err;
+        s
+    })
+}
@@ -0,0 +1,112 @@
+//! Filters deciding which frames are hidden when rendering a backtrace.
+//!
+//! A filter is stateful: range filters remember whether they are inside a range, so a fresh set of
+//! filters must be used for every backtrace.
+
+use crate::{
+    config::{self, Config, FramePattern},
+    model::Annotation,
+    Frame,
+};
+
+/// Combines all the filters in the configuration, hiding a frame if any of the `hide` filters
+/// matches or, if there are `show` filters, none of them does.
+pub struct Filters<'a> {
+    filters: Vec<Filter<'a>>,
+    show: Vec<Filter<'a>>,
+}
+
+impl<'a> Filters<'a> {
+    /// The filters defined by the `hide` and `show` rules in `config`.
+    pub fn from_config(config: &'a Config) -> Self {
+        let mut filters = vec![];
+        for filter in &config.hide {
+            filters.push(filter.into())
+        }
+        let show = config.show.iter().map(Filter::from).collect();
+        Self { filters, show }
+    }
+}
+
+impl FrameFilter for Filters<'_> {
+    fn should_hide(&mut self, frame: &Frame) -> bool {
+        // Checked last, so range filters still see the frame
+        let hidden = self.filters.iter_mut().any(|filter| filter.do_match(frame)) || frame.hidden;
+        let shown =
+            self.show.is_empty() || self.show.iter_mut().any(|filter| filter.do_match(frame));
+        hidden || !shown
+    }
+}
+
+enum Filter<'a> {
+    Pattern(&'a FramePattern),
+    File(&'a FramePattern),
+    Range {
+        begin: &'a FramePattern,
+        end: Option<&'a FramePattern>,
+        inside: bool,
+    },
+}
+
+impl Filter<'_> {
+    fn do_match(&mut self, frame: &Frame) -> bool {
+        let s = &frame.function;
+        match self {
+            Filter::Pattern(regex) => regex.is_match(s),
+            Filter::File(regex) => frame
+                .source_info
+                .as_ref()
+                .is_some_and(|source_info| regex.is_match(&source_info.file)),
+            Filter::Range { begin, end, inside } => {
+                if *inside {
+                    let Some(end) = end else { return true };
+                    *inside = !end.is_match(s);
+                    true
+                } else {
+                    *inside = begin.is_match(s);
+                    *inside
+                }
+            }
+        }
+    }
+}
+
+impl<'a> From<&'a config::Hide> for Filter<'a> {
+    fn from(value: &'a config::Hide) -> Self {
+        match value {
+            config::Hide::Pattern { pattern } => Filter::Pattern(pattern),
+            config::Hide::File { file } => Filter::File(file),
+            config::Hide::Crate { name } => Filter::Pattern(name),
+            config::Hide::Range { begin, end } => Filter::Range {
+                begin,
+                end: end.as_ref(),
+                inside: false,
+            },
+        }
+    }
+}
+
+/// Decides which frames are hidden when rendering a backtrace, see [`Filters`].
+pub trait FrameFilter {
+    fn should_hide(&mut self, frame: &Frame) -> bool;
+}
+
+/// Attaches extra information to frames before rendering. The renderer displays the annotations
+/// after the function's name.
+pub trait FrameAnnotator {
+    fn annotate(&mut self, frame: &Frame) -> Vec<Annotation>;
+}
+
+impl<A: FrameAnnotator + ?Sized> FrameAnnotator for Box<A> {
+    fn annotate(&mut self, frame: &Frame) -> Vec<Annotation> {
+        (**self).annotate(frame)
+    }
+}
+
+impl<A: FrameAnnotator> FrameAnnotator for [A] {
+    fn annotate(&mut self, frame: &Frame) -> Vec<Annotation> {
+        self.iter_mut()
+            .flat_map(|annotator| annotator.annotate(frame))
+            .collect()
+    }
+}
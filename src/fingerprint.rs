@@ -0,0 +1,91 @@
+//! Stable identifiers for backtraces, used to group occurrences of the same panic across runs.
+
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{model::strip_hash, Backtrace};
+
+/// A stable identifier of a backtrace. Two backtraces have the same fingerprint if they panicked
+/// in the same file and their innermost user frame (see [`Backtrace::top_user_frame`]) is in the
+/// same function, i.e., different callers hitting the same bug are grouped together. If there are
+/// no user frames, the innermost frame is used instead.
+///
+/// Line numbers, panic messages, and symbol hashes are not included since they change between runs
+/// or when unrelated code is edited. The hash is computed with FNV-1a so it doesn't change across
+/// versions of Rust.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub struct Fingerprint(u64);
+
+impl Backtrace {
+    pub fn fingerprint(&self) -> Fingerprint {
+        let mut hasher = Fnv1a::new();
+        if let Some(panic_info) = &self.panic_info {
+            let file = panic_info.at.split(':').next().unwrap_or_default();
+            hasher.write(file);
+        }
+        if let Some(frame) = self.top_user_frame().or(self.frames.first()) {
+            hasher.write(strip_hash(&frame.function));
+        }
+        Fingerprint(hasher.finish())
+    }
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+impl FromStr for Fingerprint {
+    type Err = String;
+
+    /// Parses a fingerprint written like [`Fingerprint`]'s `Display`, i.e., 16 hex digits.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() != 16 || !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(format!("invalid fingerprint `{s}`, expected 16 hex digits"));
+        }
+        u64::from_str_radix(s, 16)
+            .map(Fingerprint)
+            .map_err(|err| err.to_string())
+    }
+}
+
+impl Serialize for Fingerprint {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Fingerprint {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Fnv1a {
+        Fnv1a(0xcbf29ce484222325)
+    }
+
+    /// Hashes a string followed by a separator, so `("ab", "c")` and `("a", "bc")` differ.
+    fn write(&mut self, s: &str) {
+        for b in s.bytes().chain([0xff]) {
+            self.0 ^= b as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    /// Mixes the bits of the hash so similar inputs don't produce similar fingerprints.
+    fn finish(&self) -> u64 {
+        let mut h = self.0;
+        h ^= h >> 33;
+        h = h.wrapping_mul(0xff51afd7ed558ccd);
+        h ^= h >> 33;
+        h
+    }
+}
@@ -0,0 +1,163 @@
+//! Following a log file as it grows, like `tail -f`, see `backtracetk follow`. Backtraces are
+//! rendered as soon as they're complete, so it can be left running against the log of a long
+//! running service.
+
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom},
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use backtracetk::{
+    config::Config,
+    model::{Backtrace, ProcessContext},
+    parse::{LineKind, Parser},
+};
+
+use crate::{capture, output, process_context};
+
+/// How often the file is checked for new lines once we've read everything.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// How long the file must stay unchanged before the backtrace in progress is considered complete.
+/// A backtrace only ends when a line that isn't part of it is written, which may never happen,
+/// e.g., if the service exits after panicking.
+pub const IDLE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Follows `path` until interrupted, rendering the backtraces in the lines appended to it. Only
+/// new lines are read, unless `from_start` is set. If the file is truncated or replaced, e.g., when
+/// logs are rotated, it's read again from the start. With `pid`, the state of that process is
+/// captured when a panic is detected, see [`Backtrace::process`], and with `until_exit` we stop
+/// once the process exits and everything it wrote was read.
+pub fn run(
+    path: &Path,
+    from_start: bool,
+    pid: Option<u32>,
+    until_exit: bool,
+    config: &Config,
+    format: output::Format,
+    out: &mut impl io::Write,
+) -> anyhow::Result<()> {
+    if format.is_report() {
+        anyhow::bail!("a report can't be written when following a file, since it never ends");
+    }
+    let open = || File::open(path).with_context(|| format!("failed to read `{}`", path.display()));
+    let mut reader = BufReader::new(open()?);
+    if !from_start {
+        reader.seek(SeekFrom::End(0))?;
+    }
+    let mut parser = Parser::from_config(config);
+    // Whether the last line was part of a backtrace that isn't complete yet
+    let mut pending = false;
+    // The state captured when the last panic was detected
+    let mut process = None;
+    let mut last_read = Instant::now();
+    let mut buf = vec![];
+    loop {
+        let mut limited = (&mut reader).take(capture::MAX_LINE_BYTES as u64);
+        let n = limited.read_until(b'\n', &mut buf)?;
+        // An incomplete line is kept in `buf` until the rest of it is written
+        if n > 0 && (buf.ends_with(b"\n") || buf.len() >= capture::MAX_LINE_BYTES) {
+            let line = capture::decode_line(&buf);
+            buf.clear();
+            let kind = parser.check_line(&line);
+            pending = !matches!(kind, LineKind::Unrecognized | LineKind::Ignored);
+            if let (LineKind::Panic, Some(pid)) = (kind, pid) {
+                process = process_context::read(pid);
+            }
+            let mut backtraces = parser.take_backtraces();
+            attach_process(&mut backtraces, &mut process, pid);
+            render(backtraces, config, format, out)?;
+        }
+        if n > 0 {
+            last_read = Instant::now();
+            continue;
+        }
+        if pending && last_read.elapsed() >= IDLE_TIMEOUT {
+            pending = false;
+            flush(&mut parser, &mut process, pid, config, format, out)?;
+        }
+        if let (true, Some(pid)) = (until_exit, pid) {
+            if !process_context::is_running(pid) {
+                flush(&mut parser, &mut process, Some(pid), config, format, out)?;
+                return Ok(());
+            }
+        }
+        if replaced(path, reader.get_ref())? {
+            reader = BufReader::new(open()?);
+            buf.clear();
+        } else if truncated(path, reader.stream_position()?) {
+            reader.seek(SeekFrom::Start(0))?;
+            buf.clear();
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Sets [`Backtrace::process`] to the state captured when the panic was detected, or reads it now
+/// if there's none, like when running a command.
+pub fn attach_process(
+    backtraces: &mut [Backtrace],
+    process: &mut Option<ProcessContext>,
+    pid: Option<u32>,
+) {
+    for backtrace in backtraces {
+        backtrace.process = process
+            .take()
+            .or_else(|| pid.and_then(process_context::read));
+    }
+}
+
+/// Renders the backtrace in progress, if any, starting over with a new parser. Used when no more
+/// lines are coming for a while, see [`IDLE_TIMEOUT`].
+pub fn flush(
+    parser: &mut Parser,
+    process: &mut Option<ProcessContext>,
+    pid: Option<u32>,
+    config: &Config,
+    format: output::Format,
+    out: &mut impl io::Write,
+) -> io::Result<()> {
+    let finished = std::mem::replace(parser, Parser::from_config(config));
+    let mut backtraces = finished.into_backtraces();
+    attach_process(&mut backtraces, process, pid);
+    render(backtraces, config, format, out)
+}
+
+pub fn render(
+    mut backtraces: Vec<Backtrace>,
+    config: &Config,
+    format: output::Format,
+    out: &mut impl io::Write,
+) -> io::Result<()> {
+    crate::prepare(&mut backtraces, config);
+    crate::render_backtraces(out, &backtraces, config, format)?;
+    out.flush()
+}
+
+/// Whether `path` now refers to another file than the one we're reading. While the file is
+/// missing, e.g., between moving the old log and creating the new one, we keep reading the old one.
+#[cfg(unix)]
+fn replaced(path: &Path, file: &File) -> io::Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+
+    let Ok(current) = fs::metadata(path) else {
+        return Ok(false);
+    };
+    let ours = file.metadata()?;
+    Ok((current.dev(), current.ino()) != (ours.dev(), ours.ino()))
+}
+
+/// Rotation is only detected when the file is truncated elsewhere.
+#[cfg(not(unix))]
+fn replaced(_: &Path, _: &File) -> io::Result<bool> {
+    Ok(false)
+}
+
+/// Whether the file at `path` is shorter than what we've read, e.g., with `copytruncate` rotation.
+fn truncated(path: &Path, position: u64) -> bool {
+    fs::metadata(path).is_ok_and(|metadata| metadata.len() < position)
+}
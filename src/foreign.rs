@@ -0,0 +1,57 @@
+//! Frames from code in other languages, e.g., C or C++ libraries called through FFI. Their symbols
+//! may be missing (`<unknown>`) or mangled with the C++ scheme, which the standard library doesn't
+//! demangle, e.g., `_ZN3foo3barEv` instead of `foo::bar()`.
+
+use std::{borrow::Cow, path::Path};
+
+/// Demangles `function` if it's a C++ symbol. Returns whether the frame is foreign.
+pub(crate) fn demangle(function: &str) -> (Cow<'_, str>, bool) {
+    if function == "<unknown>" {
+        return (Cow::Borrowed(function), true);
+    }
+    if !function.starts_with("_Z") || is_legacy_rust_symbol(function) {
+        return (Cow::Borrowed(function), false);
+    }
+    match demangle_cpp(function) {
+        Some(demangled) => (Cow::Owned(demangled), true),
+        None => (Cow::Borrowed(function), true),
+    }
+}
+
+#[cfg(feature = "symbolize")]
+fn demangle_cpp(symbol: &str) -> Option<String> {
+    cpp_demangle::Symbol::new(symbol)
+        .ok()?
+        .demangle(&Default::default())
+        .ok()
+}
+
+/// Without the `symbolize` feature, C++ symbols are left mangled.
+#[cfg(not(feature = "symbolize"))]
+fn demangle_cpp(_: &str) -> Option<String> {
+    None
+}
+
+/// Whether `file` is a C or C++ source file.
+pub(crate) fn is_foreign_source(file: &str) -> bool {
+    let extension = Path::new(file).extension().and_then(|ext| ext.to_str());
+    matches!(
+        extension,
+        Some("c" | "cc" | "cpp" | "cxx" | "h" | "hh" | "hpp" | "hxx")
+    )
+}
+
+/// Rust's legacy mangling scheme is based on the C++ one, but symbols end with a hash, e.g.,
+/// `_ZN4core9panicking5panic17h0123456789abcdefE`. These are usually demangled already, but not if
+/// the symbol couldn't be resolved properly.
+fn is_legacy_rust_symbol(symbol: &str) -> bool {
+    let Some(symbol) = symbol.strip_suffix('E') else {
+        return false;
+    };
+    let hash = symbol
+        .len()
+        .checked_sub(19)
+        .and_then(|start| symbol.get(start..));
+    hash.and_then(|hash| hash.strip_prefix("17h"))
+        .is_some_and(|hash| hash.bytes().all(|b| b.is_ascii_hexdigit()))
+}
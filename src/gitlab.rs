@@ -0,0 +1,116 @@
+//! Reporting panics as a GitLab Code Quality report, see `--format gitlab`. Uploaded as the
+//! `codequality` report of a CI job, e.g.,
+//! ```yaml
+//! test:
+//!   script: backtracetk --format gitlab --render-to gl-code-quality.json cargo test
+//!   artifacts:
+//!     reports:
+//!       codequality: gl-code-quality.json
+//! ```
+//! the panics are shown in the merge request widget and on the lines of the diff they happened
+//! at. The report is a JSON array with an issue for each distinct panic, e.g.,
+//! ```json
+//! [{"description": "panicked: explicit panic", "check_name": "panic", "fingerprint": "3b802e1b96ed75a5", "severity": "critical", "location": {"path": "src/main.rs", "lines": {"begin": 2}}}]
+//! ```
+//! The fingerprint of the backtrace identifies the issue, so GitLab can tell which panics are new
+//! in a merge request. JUnit reports aren't written: test failures are already reported by the
+//! test harness, e.g., `cargo nextest` with `junit` in its profile.
+
+use std::{
+    env, io,
+    path::{Path, PathBuf},
+};
+
+use backtracetk::model::{Backtrace, Frame};
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct Issue {
+    description: String,
+    check_name: &'static str,
+    fingerprint: String,
+    severity: &'static str,
+    location: Location,
+}
+
+#[derive(Serialize)]
+struct Location {
+    path: String,
+    lines: Lines,
+}
+
+#[derive(Serialize)]
+struct Lines {
+    begin: usize,
+}
+
+/// Writes the report for `backtraces`, with an issue for each fingerprint.
+pub fn write_report(out: &mut impl io::Write, backtraces: &[Backtrace]) -> io::Result<()> {
+    let project_dir = project_dir();
+    let mut issues: Vec<Issue> = vec![];
+    for backtrace in backtraces {
+        let fingerprint = backtrace.fingerprint().to_string();
+        if issues.iter().any(|issue| issue.fingerprint == fingerprint) {
+            continue;
+        }
+        let Some(location) = locate(backtrace, project_dir.as_deref()) else {
+            continue;
+        };
+        let message = backtrace
+            .panic_info
+            .as_ref()
+            .and_then(|panic_info| panic_info.message.first());
+        let description = match message {
+            Some(message) => format!("panicked: {message}"),
+            None => "panicked".to_string(),
+        };
+        issues.push(Issue {
+            description,
+            check_name: "panic",
+            fingerprint,
+            severity: "critical",
+            location,
+        });
+    }
+    serde_json::to_writer(&mut *out, &issues)?;
+    writeln!(out)
+}
+
+/// Where the repository is checked out, paths in the report are relative to it.
+fn project_dir() -> Option<PathBuf> {
+    let dir = match env::var_os("CI_PROJECT_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => env::current_dir().ok()?,
+    };
+    Some(dir.canonicalize().unwrap_or(dir))
+}
+
+/// The innermost user frame in the project. GitLab can only show issues on files of the
+/// repository, so panics without a frame in the project (e.g., a backtrace without debug info, or
+/// only with frames in `/rustc/...`) aren't reported.
+fn locate(backtrace: &Backtrace, project_dir: Option<&Path>) -> Option<Location> {
+    let project_dir = project_dir?;
+    let (path, frame) = backtrace
+        .frames
+        .iter()
+        .filter(|frame| frame.is_user_frame())
+        .find_map(|frame| Some((relative_path(frame, project_dir)?, frame)))?;
+    Some(Location {
+        path,
+        lines: Lines {
+            begin: frame.source_info.as_ref()?.lineno,
+        },
+    })
+}
+
+/// The path of the frame's file relative to `project_dir`, if it's inside it.
+fn relative_path(frame: &Frame, project_dir: &Path) -> Option<String> {
+    let file = Path::new(&*frame.source_info.as_ref()?.file);
+    let file = file.canonicalize().ok()?;
+    let relative = file.strip_prefix(project_dir).ok()?;
+    let components: Vec<_> = relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect();
+    Some(components.join("/"))
+}
@@ -0,0 +1,245 @@
+//! A local record of captured panics, to tell apart new panics from long-standing ones. The
+//! history is stored as JSON lines, one per occurrence, in `.backtracetk/history.jsonl`.
+
+use std::{
+    collections::HashMap,
+    fs::{self, OpenOptions},
+    io::{self, BufRead, Write},
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context;
+use backtracetk::model::{Backtrace, Frame};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+const DIR: &str = ".backtracetk";
+const FILE: &str = "history.jsonl";
+
+#[derive(Serialize, Deserialize)]
+struct Occurrence {
+    fingerprint: String,
+    /// Seconds since the Unix epoch
+    timestamp: u64,
+    message: Option<String>,
+    location: Option<String>,
+    command: String,
+    /// Occurrences recorded by older versions don't have frames
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    frames: Vec<RecordedFrame>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RecordedFrame {
+    function: String,
+    file: Option<String>,
+}
+
+impl From<&Frame> for RecordedFrame {
+    fn from(frame: &Frame) -> Self {
+        RecordedFrame {
+            function: frame.function.to_string(),
+            file: frame.source_info.as_ref().map(|s| s.file.to_string()),
+        }
+    }
+}
+
+impl Occurrence {
+    /// Whether `regex` matches the message, the location, or the function or file of a frame.
+    fn is_match(&self, regex: &Regex) -> bool {
+        let frames = self
+            .frames
+            .iter()
+            .flat_map(|frame| std::iter::once(&frame.function).chain(&frame.file));
+        self.message
+            .iter()
+            .chain(&self.location)
+            .chain(frames)
+            .any(|text| regex.is_match(text))
+    }
+}
+
+/// The aggregated occurrences of a fingerprint.
+struct Entry {
+    first_seen: u64,
+    last_seen: u64,
+    count: usize,
+    /// The message and location of the last occurrence
+    message: Option<String>,
+    location: Option<String>,
+}
+
+pub struct History {
+    path: PathBuf,
+}
+
+impl History {
+    /// The history in the closest parent of the current directory with a `.backtracetk`
+    /// directory, or in the current directory if there's none.
+    pub fn open() -> anyhow::Result<History> {
+        let cwd = std::env::current_dir()?;
+        let dir = cwd
+            .ancestors()
+            .find(|dir| dir.join(DIR).is_dir())
+            .unwrap_or(&cwd);
+        Ok(History {
+            path: dir.join(DIR).join(FILE),
+        })
+    }
+
+    /// Appends an occurrence of each backtrace, produced while running `cmd`.
+    pub fn record(&self, backtraces: &[Backtrace], cmd: &[String]) -> anyhow::Result<()> {
+        if backtraces.is_empty() {
+            return Ok(());
+        }
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("failed to open `{}`", self.path.display()))?;
+        let timestamp = now();
+        let mut buf = vec![];
+        for backtrace in backtraces {
+            let panic_info = backtrace.panic_info.as_ref();
+            let occurrence = Occurrence {
+                fingerprint: backtrace.fingerprint().to_string(),
+                timestamp,
+                message: panic_info.and_then(|p| p.message.first()).cloned(),
+                location: panic_info.map(|p| p.at.trim_end_matches(':').to_string()),
+                command: cmd.join(" "),
+                frames: backtrace.frames.iter().map(RecordedFrame::from).collect(),
+            };
+            for _ in 0..=backtrace.duplicates {
+                serde_json::to_writer(&mut buf, &occurrence)?;
+                buf.push(b'\n');
+            }
+        }
+        // A single write so concurrent runs don't interleave their lines
+        file.write_all(&buf)?;
+        Ok(())
+    }
+
+    /// Prints every fingerprint in the history, most recently seen first. If `fingerprint` is
+    /// given, prints each of its occurrences instead.
+    pub fn print(&self, fingerprint: Option<&str>) -> anyhow::Result<()> {
+        let occurrences = self.read()?;
+        if let Some(fingerprint) = fingerprint {
+            for occurrence in occurrences.iter().filter(|o| o.fingerprint == fingerprint) {
+                println!(
+                    "{}  {}",
+                    format_timestamp(occurrence.timestamp),
+                    occurrence.command
+                );
+            }
+            return Ok(());
+        }
+        print_entries(&occurrences);
+        Ok(())
+    }
+
+    /// Prints the fingerprints with occurrences where `regex` matches the message, the location,
+    /// or the function or file of a frame, most recently seen first. Only the matching occurrences
+    /// are counted.
+    pub fn grep(&self, regex: &Regex) -> anyhow::Result<()> {
+        let occurrences = self.read()?;
+        let matches: Vec<_> = occurrences
+            .into_iter()
+            .filter(|occurrence| occurrence.is_match(regex))
+            .collect();
+        print_entries(&matches);
+        Ok(())
+    }
+
+    fn read(&self) -> anyhow::Result<Vec<Occurrence>> {
+        let file = match fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("failed to open `{}`", self.path.display()))
+            }
+        };
+        let mut occurrences = vec![];
+        for (i, line) in io::BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            occurrences.push(serde_json::from_str(&line).with_context(|| {
+                format!("invalid entry at `{}:{}`", self.path.display(), i + 1)
+            })?);
+        }
+        Ok(occurrences)
+    }
+}
+
+/// Prints the fingerprints of `occurrences` with how many times and when they were seen, most
+/// recently seen first.
+fn print_entries(occurrences: &[Occurrence]) {
+    let mut entries: HashMap<&str, Entry> = HashMap::new();
+    for occurrence in occurrences {
+        let entry = entries
+            .entry(&occurrence.fingerprint)
+            .or_insert_with(|| Entry {
+                first_seen: occurrence.timestamp,
+                last_seen: occurrence.timestamp,
+                count: 0,
+                message: None,
+                location: None,
+            });
+        entry.first_seen = entry.first_seen.min(occurrence.timestamp);
+        entry.last_seen = entry.last_seen.max(occurrence.timestamp);
+        entry.count += 1;
+        entry.message.clone_from(&occurrence.message);
+        entry.location.clone_from(&occurrence.location);
+    }
+    let mut entries: Vec<_> = entries.into_iter().collect();
+    entries.sort_by_key(|(_, entry)| std::cmp::Reverse(entry.last_seen));
+
+    for (fingerprint, entry) in entries {
+        println!(
+            "{fingerprint}  {} times, first seen {}, last seen {}",
+            entry.count,
+            format_timestamp(entry.first_seen),
+            format_timestamp(entry.last_seen)
+        );
+        if let Some(location) = &entry.location {
+            println!("    at {location}");
+        }
+        if let Some(message) = &entry.message {
+            println!("    {message}");
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Formats seconds since the Unix epoch as a UTC date and time.
+fn format_timestamp(timestamp: u64) -> String {
+    let (days, secs) = (timestamp / 86400, timestamp % 86400);
+    // Converts days since the epoch to a civil date, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02} UTC",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
@@ -0,0 +1,161 @@
+//! Running the actions in the `[notify]` and `[on_backtrace]` configurations when a backtrace is
+//! detected.
+
+use std::{
+    io::{self, IsTerminal, Write},
+    process::{Command, Stdio},
+    thread,
+};
+
+use backtracetk::{
+    config::{Notify, OnBacktrace, TerminalNotification},
+    model::Backtrace,
+};
+
+/// Runs the `[on_backtrace]` command with `backtrace` written to its stdin as JSON.
+pub fn on_backtrace(config: &OnBacktrace, backtrace: &Backtrace) {
+    let Some(command) = &config.command else {
+        return;
+    };
+    let json = match serde_json::to_vec(backtrace) {
+        Ok(json) => json,
+        Err(err) => {
+            eprintln!("Warning: failed to serialize backtrace: {err}");
+            return;
+        }
+    };
+    let child = shell(command).stdin(Stdio::piped()).spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(err) => {
+            eprintln!("Warning: failed to run on_backtrace command `{command}`: {err}");
+            return;
+        }
+    };
+    let mut stdin = child.stdin.take().unwrap();
+    let feed = move || {
+        // The command may exit without reading its input, so a broken pipe isn't an error
+        let _ = stdin.write_all(&json);
+        drop(stdin);
+        child.wait()
+    };
+    if config.blocking {
+        let _ = feed();
+    } else {
+        thread::spawn(feed);
+    }
+}
+
+pub fn on_panic(config: &Notify, backtrace: &Backtrace) {
+    if config.command.is_none() && !config.desktop && config.terminal == TerminalNotification::Off {
+        return;
+    }
+    let message = backtrace
+        .panic_info
+        .as_ref()
+        .map(|panic_info| panic_info.message.join("\n"))
+        .unwrap_or_default();
+    let location = backtrace
+        .top_user_frame()
+        .and_then(|frame| frame.source_info.as_ref())
+        .map(|source_info| source_info.to_string())
+        .or_else(|| {
+            let panic_info = backtrace.panic_info.as_ref()?;
+            Some(panic_info.at.trim_end_matches(':').to_string())
+        })
+        .unwrap_or_default();
+
+    if let Some(command) = &config.command {
+        run_command(command, &message, &location);
+    }
+    if config.desktop {
+        show_desktop_notification(&message, &location);
+    }
+    if config.terminal != TerminalNotification::Off {
+        show_terminal_notification(config.terminal, &message, &location);
+    }
+}
+
+/// Runs `command` in a shell without waiting for it, so a slow command doesn't block capturing
+/// the output of the process.
+fn run_command(command: &str, message: &str, location: &str) {
+    let mut cmd = shell(command);
+    if !cfg!(windows) {
+        // The first argument after the script is `$0`
+        cmd.args(["backtracetk", message, location]);
+    }
+    let child = cmd
+        .env("BACKTRACETK_MESSAGE", message)
+        .env("BACKTRACETK_LOCATION", location)
+        .stdin(Stdio::null())
+        .spawn();
+    match child {
+        Ok(mut child) => {
+            thread::spawn(move || child.wait());
+        }
+        Err(err) => eprintln!("Warning: failed to run notify command `{command}`: {err}"),
+    }
+}
+
+pub fn shell(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C").arg(command);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(command);
+        cmd
+    }
+}
+
+/// Writes an escape sequence asking the terminal to show a notification. Terminals that don't
+/// support the sequence ignore it, but it would end up in the file if stderr is redirected.
+fn show_terminal_notification(kind: TerminalNotification, message: &str, location: &str) {
+    let mut stderr = io::stderr();
+    if !stderr.is_terminal() {
+        return;
+    }
+    // Control characters would end the sequence early
+    let sanitize = |s: &str| s.replace(|c: char| c.is_control(), " ");
+    let body = if location.is_empty() {
+        sanitize(message)
+    } else {
+        format!("{} at {}", sanitize(message), sanitize(location))
+    };
+    let _ = match kind {
+        TerminalNotification::Off => return,
+        TerminalNotification::Osc9 => write!(stderr, "\x1b]9;Panic detected: {body}\x07"),
+        TerminalNotification::Osc777 => {
+            write!(stderr, "\x1b]777;notify;Panic detected;{body}\x07")
+        }
+    };
+}
+
+#[cfg(feature = "desktop-notifications")]
+fn show_desktop_notification(message: &str, location: &str) {
+    let body = if location.is_empty() {
+        message.to_string()
+    } else {
+        format!("{message}\nat {location}")
+    };
+    if let Err(err) = notify_rust::Notification::new()
+        .summary("Panic detected")
+        .body(&body)
+        .show()
+    {
+        eprintln!("Warning: failed to show desktop notification: {err}");
+    }
+}
+
+#[cfg(not(feature = "desktop-notifications"))]
+fn show_desktop_notification(_: &str, _: &str) {
+    use std::sync::Once;
+    static WARN: Once = Once::new();
+    WARN.call_once(|| {
+        eprintln!(
+            "Warning: desktop notifications are not supported, build backtracetk with the \
+             `desktop-notifications` feature"
+        )
+    });
+}
@@ -0,0 +1,59 @@
+//! Reading the crash reports written by [`human-panic`](https://crates.io/crates/human-panic). The
+//! parser doesn't read them, it only records the path in [`Backtrace::report_file`].
+
+use std::{fs, path::Path};
+
+use serde::Deserialize;
+
+use crate::{parse::Parser, Backtrace, PanicInfo};
+
+/// The fields we care about in a report. The report also contains the name and version of the
+/// crate, the operating system, etc.
+#[derive(Deserialize)]
+struct Report {
+    explanation: Option<String>,
+    cause: Option<String>,
+    backtrace: Option<String>,
+}
+
+/// Reads the backtrace in the report at `path`. The backtrace in the report looks like
+/// ```ignore
+///    0:     0x55d5f6a1b2c3 - my_crate::main::h0123456789abcdef
+///                              at src/main.rs:10
+///    1: ...
+/// ```
+/// which we can parse like a regular backtrace. Returns `None` if the report can't be read or it
+/// doesn't contain a backtrace.
+pub fn read_report(path: &Path) -> Option<Backtrace> {
+    let report: Report = toml::from_str(&fs::read_to_string(path).ok()?).ok()?;
+    let mut parser = Parser::new();
+    for line in report.backtrace?.lines() {
+        parser.parse_line(line);
+    }
+    let mut backtrace = parser.into_backtraces().pop()?;
+    let mut panic_info = PanicInfo {
+        thread: None,
+        at: report
+            .explanation
+            .as_deref()
+            .and_then(parse_explanation)
+            .unwrap_or_else(|| path.display().to_string()),
+        message: report.cause.into_iter().collect(),
+        location: None,
+    };
+    panic_info.locate();
+    backtrace.panic_info = Some(panic_info);
+    backtrace.report_file = Some(path.to_path_buf());
+    Some(backtrace)
+}
+
+/// Extracts the location of the panic from the explanation in the report, e.g.,
+/// ```ignore
+/// Panic occurred in file 'src/main.rs' at line 10
+/// ```
+fn parse_explanation(explanation: &str) -> Option<String> {
+    let rest = explanation.split_once("in file '")?.1;
+    let (file, rest) = rest.split_once('\'')?;
+    let line = rest.split_once("at line ")?.1.trim();
+    Some(format!("{file}:{line}"))
+}
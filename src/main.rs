@@ -1,53 +1,759 @@
-use std::io::{BufRead, BufReader};
-use std::process::{Command, Stdio};
+mod analyze;
+mod annotate_pr;
+mod attach;
+mod build_info;
+mod capture;
+mod codeowners;
+mod convert;
+mod echo_limit;
+mod exit_summary;
+mod follow;
+mod gitlab;
+mod history;
+mod hooks;
+mod nextest;
+#[cfg(unix)]
+mod notify;
+mod ntstatus;
+mod oom;
+mod output;
+mod plugin;
+mod process_context;
+#[cfg(unix)]
+mod pty;
+mod rate_limit;
+mod rerun;
+mod resource_usage;
+mod serve;
+#[cfg(unix)]
+mod signals;
+mod teamcity;
+mod triage;
 
-use backtracetk::config::{self, Config, Echo};
-use backtracetk::{Frame, FrameFilter};
-use clap::Parser;
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use annotate_pr::PullRequest;
+use anyhow::Context;
+use backtracetk::config::{self, Config};
+use backtracetk::filter::Filters;
+use backtracetk::model::{Backtrace, BuildInfo, ProcessContext};
+use backtracetk::parse::LineKind;
+use capture::{Line, Stream};
+use clap::{CommandFactory, Parser};
+use echo_limit::EchoLimiter;
+use exit_summary::{ExitSummary, Group};
+use rate_limit::RateLimiter;
 use regex::Regex;
 
 /// Print colorized Rust backtraces by capturing the output of an external process.
 #[derive(clap::Parser)]
-#[command(max_term_width = 110, arg_required_else_help = true)]
+#[command(max_term_width = 110, args_conflicts_with_subcommands = true)]
 struct Args {
-    #[arg(trailing_var_arg(true))]
-    cmd: Vec<String>,
+    #[command(subcommand)]
+    command: Option<Commands>,
 
     /// Print the current detected configuration
     #[arg(long)]
     print_config: bool,
 
-    /// Print the default configuration used when no configuratoin files are detected
+    /// Print the default configuration, documenting every available option, and exit
     #[arg(long)]
     print_default_config: bool,
+
+    /// Print how every line of a log is interpreted by the parser, reporting the lines it failed
+    /// to recognize, and exit
+    #[arg(long, value_name = "FILE")]
+    check_parser: Option<PathBuf>,
+
+    /// Running a command without a subcommand is the same as `backtracetk run`
+    #[command(flatten)]
+    run: RunArgs,
+}
+
+/// Flags shared by the subcommands that render backtraces.
+#[derive(clap::Args)]
+struct RenderArgs {
+    /// Render backtraces with one line per frame, see the `compact` option
+    #[arg(long)]
+    compact: bool,
+
+    /// Read the backtraces of programs using `human-panic` from the crash reports they mention, see
+    /// the `human_panic_reports` option
+    #[arg(long)]
+    human_panic_reports: bool,
+
+    /// The order of the backtraces rendered at the end, see the `sort` option
+    #[arg(long, value_enum, value_name = "ORDER")]
+    sort: Option<config::Sort>,
+
+    /// Only show the frames whose function matches this regex, hiding the rest. Can be repeated,
+    /// see the `show` option
+    #[arg(long, value_name = "REGEX", value_parser = parse_pattern)]
+    only: Vec<config::FramePattern>,
+
+    /// Hide the frames whose function matches this regex, like a `hide` rule with a `pattern`. Can
+    /// be repeated
+    #[arg(long, value_name = "REGEX", value_parser = parse_pattern)]
+    hide: Vec<config::FramePattern>,
+
+    /// Hide the frames from the first one whose function matches this regex on, like a `hide` rule
+    /// with `begin`. Can be repeated
+    #[arg(long, value_name = "REGEX", value_parser = parse_pattern)]
+    hide_begin: Vec<config::FramePattern>,
+
+    /// Stop hiding frames after one whose function matches this regex, like a `hide` rule with
+    /// `end`. The first `--hide-end` ends the range of the first `--hide-begin`, and so on
+    #[arg(long, value_name = "REGEX", value_parser = parse_pattern)]
+    hide_end: Vec<config::FramePattern>,
+}
+
+impl RenderArgs {
+    /// Overrides the options in `config` that are set on the command line.
+    fn apply(&self, config: &mut Config) -> anyhow::Result<()> {
+        if self.hide_end.len() > self.hide_begin.len() {
+            anyhow::bail!("every `--hide-end` must have a `--hide-begin` before it");
+        }
+        config.compact |= self.compact;
+        config.human_panic_reports |= self.human_panic_reports;
+        let pattern = |pattern: &config::FramePattern| config::Hide::Pattern {
+            pattern: pattern.clone(),
+        };
+        config.show.extend(self.only.iter().map(pattern));
+        config.hide.extend(self.hide.iter().map(pattern));
+        let mut ends = self.hide_end.iter();
+        for begin in &self.hide_begin {
+            config.hide.push(config::Hide::Range {
+                begin: begin.clone(),
+                end: ends.next().cloned(),
+            });
+        }
+        if let Some(sort) = self.sort {
+            config.sort = sort;
+        }
+        Ok(())
+    }
+}
+
+/// Parses a regex matched against the function of frames, e.g., for `--hide`.
+fn parse_pattern(pattern: &str) -> Result<config::FramePattern, regex::Error> {
+    config::FramePattern::new(pattern, Default::default())
+}
+
+#[derive(clap::Args)]
+struct RunArgs {
+    /// The command to run. Separate several commands with `--then` to run them one after the
+    /// other, stopping at the first that fails, e.g., `cargo build --then ./target/debug/app`
+    #[arg(trailing_var_arg(true))]
+    cmd: Vec<String>,
+
+    #[command(flatten)]
+    render_args: RenderArgs,
+
+    /// Read the output from stdin instead of running a command, e.g., `my-server 2>&1 |
+    /// backtracetk`. This is the default when there's no command and stdin isn't a terminal
+    #[arg(long, conflicts_with = "cmd")]
+    stdin: bool,
+
+    /// Send a JSON event with the location of each panic to the Unix socket at this path, e.g., to
+    /// make an editor jump to it
+    #[cfg(unix)]
+    #[arg(long, value_name = "SOCKET")]
+    notify: Option<PathBuf>,
+
+    /// Write a JSON summary of the run (exit status, panics, and duration) to this file descriptor
+    /// when the command exits, e.g., `--summary-fd 3 3>summary.json`
+    #[cfg(unix)]
+    #[arg(long, value_name = "FD", conflicts_with = "summary_file")]
+    summary_fd: Option<i32>,
+
+    /// Write a JSON summary of the run (exit status, panics, and duration) to this file when the
+    /// command exits
+    #[arg(long, value_name = "PATH")]
+    summary_file: Option<PathBuf>,
+
+    /// Which output streams of the command are read looking for backtraces, see the `capture`
+    /// option
+    #[arg(long, value_enum, value_name = "STREAMS")]
+    capture: Option<config::Capture>,
+
+    /// Echo at most this many lines per second, always including the lines of panics and
+    /// backtraces, see the `max_echo_rate` option
+    #[arg(long, value_name = "LINES", conflicts_with = "raw")]
+    max_echo_rate: Option<u32>,
+
+    /// Run the command in a pseudo-terminal, so programs that check whether their output is a
+    /// terminal keep their colors and line buffering. The captured streams are merged (Unix only)
+    #[arg(long, conflicts_with = "stdin")]
+    pty: bool,
+
+    /// Echo the output of the command byte for byte, e.g., to use backtracetk inside a pipeline.
+    /// Combine with `--render-to` to keep rendered backtraces out of the output.
+    #[arg(long)]
+    raw: bool,
+
+    /// Where to write rendered backtraces: `stderr`, `stdout`, `tty` (the terminal, even if stderr
+    /// is redirected), `fd:N` (an open file descriptor), or the path of a file
+    #[arg(long, value_name = "DEST", default_value = "stderr")]
+    render_to: output::Destination,
+
+    /// How to write backtraces: rendered by backtracetk (`pretty`), in the text format of the
+    /// standard library without the hidden frames (`raw-filtered`), e.g., for tools that expect
+    /// `RUST_BACKTRACE` output, as one JSON object per line (`json`), as a GitLab Code Quality
+    /// report (`gitlab`), or as TeamCity service messages (`teamcity`)
+    #[arg(long, alias = "output", value_enum, default_value = "pretty")]
+    format: output::Format,
+
+    /// When running in GitHub Actions for a pull request, post each panic as a review comment on
+    /// the line of its innermost frame in the workspace. Uses the GitHub CLI (`gh`), which reads
+    /// the credentials from `GITHUB_TOKEN`
+    #[arg(long)]
+    annotate_pr: bool,
+
+    /// Exit with code 1 if the command panicked, except for the panics listed in the
+    /// `known_panics` option, e.g., to fail a CI job only on new panics. If it only panicked with
+    /// known panics, exit with code 0 even if the command failed
+    #[arg(long)]
+    fail_on_panic: bool,
+}
+
+#[derive(clap::Subcommand)]
+enum Commands {
+    /// Run a command and render the backtraces in its output. This is the default, e.g.,
+    /// `backtracetk cargo run` is the same as `backtracetk run cargo run`
+    #[command(arg_required_else_help = true)]
+    Run(RunArgs),
+    /// Manage configuration files
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    /// Render the backtraces found in a saved log file
+    Parse {
+        /// The log file to parse
+        file: PathBuf,
+
+        /// How to write backtraces, see `run --format`
+        #[arg(long, alias = "output", value_enum, default_value = "pretty")]
+        format: output::Format,
+
+        /// Where to write backtraces, see `run --render-to`
+        #[arg(long, value_name = "DEST", default_value = "stderr")]
+        render_to: output::Destination,
+
+        #[command(flatten)]
+        render_args: RenderArgs,
+    },
+    /// Render the panics of a process started outside backtracetk, without restarting it. Its
+    /// stderr is followed if it's redirected to a file (only on Linux), otherwise redirect it to a
+    /// log file or a named pipe and pass it with `--from`
+    Attach {
+        /// The id of the process
+        pid: u32,
+
+        /// Read the output of the process from this log file or named pipe instead of its stderr
+        #[arg(long, value_name = "PATH")]
+        from: Option<PathBuf>,
+
+        /// How to write backtraces, see `run --format`
+        #[arg(long, alias = "output", value_enum, default_value = "pretty")]
+        format: output::Format,
+
+        /// Where to write backtraces, see `run --render-to`
+        #[arg(long, value_name = "DEST", default_value = "stderr")]
+        render_to: output::Destination,
+
+        #[command(flatten)]
+        render_args: RenderArgs,
+    },
+    /// Follow a log file as it grows, like `tail -f`, rendering backtraces as soon as they're
+    /// complete
+    Follow {
+        /// The log file to follow
+        file: PathBuf,
+
+        /// Also render the backtraces already in the file
+        #[arg(long)]
+        from_start: bool,
+
+        /// Capture the state of this process from `/proc` when it panics, e.g., the service writing
+        /// the log, see the `process_context` option. Only on Linux
+        #[arg(long)]
+        pid: Option<u32>,
+
+        /// How to write backtraces, see `run --format`
+        #[arg(long, alias = "output", value_enum, default_value = "pretty")]
+        format: output::Format,
+
+        /// Where to write backtraces, see `run --render-to`
+        #[arg(long, value_name = "DEST", default_value = "stderr")]
+        render_to: output::Destination,
+
+        #[command(flatten)]
+        render_args: RenderArgs,
+    },
+    /// Convert backtraces written with `--format json` to another version of the JSON schema, e.g.,
+    /// for tools that only understand an older version. Reads one backtrace per line
+    Convert {
+        /// The file with the backtraces, stdin if omitted
+        file: Option<PathBuf>,
+
+        /// The schema version to convert to
+        #[arg(long, value_name = "VERSION", default_value_t = backtracetk::model::SCHEMA_VERSION)]
+        to: u32,
+    },
+    /// Render the backtraces in text pasted into the terminal, e.g., from a chat message. Reads
+    /// until an empty line or the end of input (Ctrl-D)
+    Paste {
+        #[command(flatten)]
+        render_args: RenderArgs,
+    },
+    /// Serve parse requests over JSON-RPC for editor integrations
+    Serve {
+        /// Listen on a Unix socket at this path instead of using stdin/stdout
+        #[cfg(unix)]
+        #[arg(long)]
+        socket: Option<PathBuf>,
+    },
+    /// Show the panics recorded in the history, see the `history` option
+    #[command(args_conflicts_with_subcommands = true)]
+    History {
+        #[command(subcommand)]
+        command: Option<HistoryCommand>,
+
+        /// Show every occurrence of the panic with this fingerprint
+        fingerprint: Option<String>,
+    },
+    /// Render the backtraces of failed tests reported by `cargo nextest run --message-format
+    /// libtest-json`
+    Nextest {
+        /// The file with the events. Defaults to reading them from stdin as they are reported.
+        file: Option<PathBuf>,
+
+        #[command(flatten)]
+        render_args: RenderArgs,
+    },
+    /// Aggregate statistics over many logs, e.g., the crash logs of a fuzzing campaign: unique
+    /// fingerprints, and the most common panic messages and innermost user frames
+    Analyze {
+        /// The format of the report
+        #[arg(long, value_enum, default_value = "text")]
+        format: analyze::Format,
+
+        /// The log files, or directories searched recursively for log files
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+    },
+    /// Manage the panics that `--fail-on-panic` ignores, see the `known_panics` option
+    #[command(subcommand)]
+    Baseline(BaselineCommand),
+    /// Group the panics of a test run by owner and fingerprint
+    Triage {
+        /// The format of the report
+        #[arg(long, value_enum, default_value = "markdown")]
+        format: triage::Format,
+
+        /// The CODEOWNERS file used to find who owns each panic. Defaults to the one of the
+        /// repository containing the current directory.
+        #[arg(long)]
+        codeowners: Option<PathBuf>,
+
+        /// The log files of the test run
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+}
+
+#[derive(clap::Subcommand)]
+enum HistoryCommand {
+    /// Show the panics whose message, location, or frames (functions or files) match a regex,
+    /// e.g., to check whether a panic happened before
+    Grep {
+        /// The regex to search for
+        pattern: Regex,
+    },
+}
+
+#[derive(clap::Subcommand)]
+enum BaselineCommand {
+    /// Set `known_panics` to the fingerprints of the panics in the given logs. The local
+    /// configuration file is updated, or `backtracetk.toml` is created in the current directory
+    Update {
+        /// The log files with the expected panics
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+}
+
+#[derive(clap::Subcommand)]
+enum ConfigCommand {
+    /// Print the configuration that applies when running a command
+    Show {
+        /// Also print where each value comes from
+        #[arg(long)]
+        origin: bool,
+
+        #[arg(trailing_var_arg(true))]
+        cmd: Vec<String>,
+    },
+    /// Rewrite deprecated keys in configuration files
+    Migrate {
+        /// The file to migrate. Defaults to the configuration files that would be read from the
+        /// current directory.
+        file: Option<PathBuf>,
+    },
 }
 
 fn main() -> anyhow::Result<()> {
-    let mut args = Args::parse();
+    let args = Args::parse();
+    match args.command {
+        Some(Commands::Run(run)) => return run_commands(run),
+        Some(Commands::Config(command)) => return run_config_command(command),
+        Some(Commands::Parse {
+            file,
+            format,
+            render_to,
+            render_args,
+        }) => return run_parse_command(&file, format, &render_to, &render_args),
+        Some(Commands::Attach {
+            pid,
+            from,
+            format,
+            render_to,
+            render_args,
+        }) => {
+            let mut config = Config::read(&[])?;
+            render_args.apply(&mut config)?;
+            let out = &mut render_to.open()?;
+            return attach::run(pid, from.as_deref(), &config, format, out);
+        }
+        Some(Commands::Follow {
+            file,
+            from_start,
+            pid,
+            format,
+            render_to,
+            render_args,
+        }) => {
+            let mut config = Config::read(&[])?;
+            render_args.apply(&mut config)?;
+            let out = &mut render_to.open()?;
+            return follow::run(&file, from_start, pid, false, &config, format, out);
+        }
+        Some(Commands::Convert { file, to }) => {
+            let out = &mut io::stdout().lock();
+            return match file {
+                Some(file) => {
+                    let reader = std::fs::File::open(&file)
+                        .with_context(|| format!("failed to read `{}`", file.display()))?;
+                    convert::run(io::BufReader::new(reader), to, out)
+                }
+                None => convert::run(io::stdin().lock(), to, out),
+            };
+        }
+        Some(Commands::Paste { render_args }) => return run_paste_command(&render_args),
+        #[cfg(unix)]
+        Some(Commands::Serve { socket: Some(path) }) => {
+            return serve::serve_socket(&path, &Config::read(&[])?)
+        }
+        Some(Commands::Serve { .. }) => return serve::serve_stdio(&Config::read(&[])?),
+        Some(Commands::History {
+            command: Some(HistoryCommand::Grep { pattern }),
+            ..
+        }) => return history::History::open()?.grep(&pattern),
+        Some(Commands::History { fingerprint, .. }) => {
+            return history::History::open()?.print(fingerprint.as_deref())
+        }
+        Some(Commands::Nextest { file, render_args }) => {
+            return run_nextest_command(file.as_deref(), &render_args)
+        }
+        Some(Commands::Analyze { format, paths }) => return run_analyze_command(format, &paths),
+        Some(Commands::Baseline(command)) => return run_baseline_command(command),
+        Some(Commands::Triage {
+            format,
+            codeowners,
+            files,
+        }) => return run_triage_command(format, codeowners.as_deref(), &files),
+        None => {}
+    }
 
     if args.print_default_config {
-        println!("{}", Config::default());
+        println!("{}", Config::default().to_commented_toml());
         std::process::exit(0);
     }
 
-    let config = Config::read()?;
+    if let Some(file) = &args.check_parser {
+        let passed = run_check_parser(file, &args.run.render_args)?;
+        std::process::exit(if passed { 0 } else { 1 });
+    }
 
     if args.print_config {
-        println!("{config}");
+        let cmd = args.run.cmd.split(|arg| arg == "--then").next().unwrap();
+        println!("{}", Config::read(cmd)?);
         std::process::exit(0);
     }
 
+    // There's nothing to read from a terminal, so show the help like for any missing argument
+    if args.run.cmd.is_empty() && !args.run.stdin && io::stdin().is_terminal() {
+        anstream::eprintln!("{}", Args::command().render_help().ansi());
+        std::process::exit(2);
+    }
+
+    run_commands(args.run)
+}
+
+/// Runs the commands in a `--then` chain, rendering the backtraces in their output.
+/// Without a command, the output is read from stdin instead, see `--stdin`.
+fn run_commands(args: RunArgs) -> anyhow::Result<()> {
+    let stdin = args.stdin || args.cmd.is_empty();
+    // `--then` separates the commands in a chain, run one after the other
+    let commands: Vec<&[String]> = args.cmd.split(|arg| arg == "--then").collect();
+    if !stdin && commands.iter().any(|cmd| cmd.is_empty()) {
+        anyhow::bail!("empty command in `--then` chain");
+    }
+
+    let summary_file = match &args.summary_file {
+        Some(path) => Some(exit_summary::create_file(path)?),
+        None => None,
+    };
+    #[cfg(unix)]
+    let summary_file = match args.summary_fd {
+        Some(fd) => Some(output::open_fd(fd)?),
+        None => summary_file,
+    };
+
+    let pull_request = if args.annotate_pr {
+        let pull_request = PullRequest::from_env()?;
+        if pull_request.is_none() {
+            eprintln!("Warning: not running for a pull request, `--annotate-pr` is ignored");
+        }
+        pull_request
+    } else {
+        None
+    };
+
+    #[cfg(unix)]
+    let mut notifier = args.notify.as_deref().map(notify::Notifier::connect);
+    let mut out = args.render_to.open()?;
+    #[cfg(unix)]
+    let signals = signals::Signals::register()?;
+
+    let mut start = Instant::now();
+    let mut backtraces = vec![];
+    // The exit status of the last command that ran, which ends the chain
+    let mut status = None;
+    // The panics that aren't known, see `--fail-on-panic`
+    let mut new_panics: Vec<Group> = vec![];
+    // Set when rerunning with the `RUST_BACKTRACE` setting suggested by a note, see `rerun`
+    let mut style_override = None;
+    loop {
+        for cmd in &commands {
+            let mut config = Config::read(cmd)?;
+            args.render_args.apply(&mut config)?;
+            if let Some(capture) = args.capture {
+                config.capture = capture;
+            }
+            if args.max_echo_rate.is_some() {
+                config.max_echo_rate = args.max_echo_rate;
+            }
+            if let Some(style) = style_override {
+                config.style = style;
+            }
+            let mut collector = Collector {
+                config: &config,
+                // Label backtraces with the command that produced them if there's more than one
+                command: (commands.len() > 1).then(|| cmd.join(" ")),
+                #[cfg(unix)]
+                notifier: notifier.as_mut(),
+                #[cfg(unix)]
+                signals: &signals,
+                // A report is only written once every command exits
+                rate_limiter: (config.rate_limit.is_enabled() && !args.format.is_report())
+                    .then(|| RateLimiter::new(config.rate_limit.window())),
+                out: &mut out,
+                format: args.format,
+                built_before: None,
+                build: None,
+                pid: None,
+                process: None,
+                plugin: config
+                    .plugin
+                    .command
+                    .is_some()
+                    .then(|| plugin::Worker::start(&config.plugin)),
+                backtraces: vec![],
+            };
+            let cmd_status = if stdin {
+                read_stdin(&config, args.raw, &mut collector)?
+            } else {
+                run_command(cmd, &config, args.raw, args.pty, &mut collector)?
+            };
+            collector.wait_for_plugin();
+
+            let Collector {
+                rate_limiter,
+                backtraces: collected,
+                ..
+            } = collector;
+            let cmd_backtraces = match rate_limiter {
+                Some(rate_limiter) => {
+                    for summary in rate_limiter.finish() {
+                        summary.render_to(&mut out)?;
+                    }
+                    collected
+                }
+                None => {
+                    let mut backtraces = dedupe(collected, &config);
+                    backtracetk::parse::sort_backtraces(&mut backtraces, config.sort);
+                    if !args.format.is_report() {
+                        render_backtraces(&mut out, &backtraces, &config, args.format)?;
+                    }
+                    backtraces
+                }
+            };
+            if config.history {
+                history::History::open()?.record(&cmd_backtraces, cmd)?;
+            }
+            if let Some(pull_request) = &pull_request {
+                if let Err(err) = pull_request.annotate(&cmd_backtraces, &config) {
+                    eprintln!("Warning: failed to annotate the pull request: {err:#}");
+                }
+            }
+            for group in exit_summary::groups(&cmd_backtraces) {
+                let fingerprint = group.fingerprint;
+                let known = config.known_panics.contains(&fingerprint);
+                if !known && !new_panics.iter().any(|g| g.fingerprint == fingerprint) {
+                    new_panics.push(group);
+                }
+            }
+            backtraces.extend(cmd_backtraces);
+            status = Some((*cmd, cmd_status));
+
+            // Like `&&` in the shell, the chain stops at the first command that fails
+            if !cmd_status.success() {
+                break;
+            }
+        }
+
+        // Output read from stdin can't be produced again
+        if !args.raw && !stdin {
+            let style = style_override.unwrap_or(Config::read(commands[0])?.style);
+            if let Some(style) = rerun::offer(&backtraces, style)? {
+                style_override = Some(style);
+                start = Instant::now();
+                backtraces.clear();
+                new_panics.clear();
+                continue;
+            }
+        }
+        break;
+    }
+
+    if args.format.is_report() {
+        gitlab::write_report(&mut out, &backtraces)?;
+    }
+    if let output::Format::Pretty = args.format {
+        backtracetk::render::render_test_summary(&mut out, &backtraces)?;
+    }
+
+    if let (Some(file), Some((cmd, status))) = (summary_file, status) {
+        ExitSummary::new(cmd, status, start.elapsed(), &backtraces).write(file)?;
+    }
+
+    if args.fail_on_panic && !new_panics.is_empty() {
+        eprintln!("Error: the command panicked, and these panics aren't in `known_panics`:");
+        for group in &new_panics {
+            eprint!("  {}", group.fingerprint);
+            if let Some(message) = &group.message {
+                eprint!(": {message}");
+            }
+            if let Some(location) = &group.location {
+                eprint!(" at {location}");
+            }
+            eprintln!();
+        }
+        std::process::exit(1);
+    }
+    let only_known_panics = args.fail_on_panic && !backtraces.is_empty();
+    match status {
+        Some((_, status)) if !status.success() && !only_known_panics => {
+            std::process::exit(exit_code(status))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// The exit code of a command that exited with `status`, like a shell reports it, i.e., 128 plus
+/// the signal number if it was killed by a signal.
+fn exit_code(status: ExitStatus) -> i32 {
+    #[cfg(unix)]
+    if let Some(signal) = std::os::unix::process::ExitStatusExt::signal(&status) {
+        return 128 + signal;
+    }
+    status.code().unwrap_or(1)
+}
+
+/// Runs `cmd` passing the backtraces in its output to `collector`. With `raw`, the output is echoed
+/// verbatim. With `pty`, the captured streams are connected to a pseudo-terminal, see `--pty`.
+fn run_command(
+    cmd: &[String],
+    config: &Config,
+    raw: bool,
+    pty: bool,
+    collector: &mut Collector,
+) -> anyhow::Result<ExitStatus> {
+    #[cfg(not(unix))]
+    if pty {
+        anyhow::bail!("`--pty` is only supported on Unix");
+    }
     let mut env_vars = vec![("RUST_BACKTRACE", config.style.env_var_str())];
 
     for (k, v) in &config.env {
         env_vars.push((k, v));
     }
 
-    println!("$ {}", args.cmd.join(" "));
+    // Raw mode must be transparent, so we don't print anything to stdout
+    if !raw {
+        println!("$ {}", cmd.join(" "));
+    }
 
-    let child = match Command::new(args.cmd.remove(0))
-        .args(args.cmd)
-        .stderr(Stdio::piped())
+    // Source files modified after a local binary was built may not match it, otherwise the best we
+    // know is that the program was built before it started
+    match build_info::read(&cmd[0]) {
+        Some(binary) => {
+            collector.built_before = Some(binary.modified);
+            collector.build = Some(Arc::new(binary.info));
+        }
+        None => {
+            collector.built_before = Some(SystemTime::now());
+            collector.build = None;
+        }
+    }
+    #[cfg(unix)]
+    let pty = if pty {
+        Some(pty::open().context("failed to open a pseudo-terminal")?)
+    } else {
+        None
+    };
+    let pipe = |captured: bool| -> io::Result<Stdio> {
+        #[cfg(unix)]
+        if let (true, Some((_, slave))) = (captured, &pty) {
+            return Ok(slave.try_clone()?.into());
+        }
+        Ok(if captured {
+            Stdio::piped()
+        } else {
+            Stdio::inherit()
+        })
+    };
+    let oom_watch = oom::OomWatch::start();
+    let mut child = match Command::new(&cmd[0])
+        .args(&cmd[1..])
+        .stdout(pipe(config.capture.stdout())?)
+        .stderr(pipe(config.capture.stderr())?)
         .envs(env_vars)
         .spawn()
     {
@@ -58,81 +764,605 @@ fn main() -> anyhow::Result<()> {
         }
     };
 
-    let mut parser = backtracetk::Parser::new();
-    let stderr = child.stderr.expect("failed to open stderr");
-    for line in BufReader::new(stderr).lines() {
-        let line = line?;
-        if let Echo::True = config.echo {
-            anstream::eprintln!("{line}");
+    collector.pid = config.process_context.then(|| child.id());
+
+    let mut pipes: Vec<(Stream, Box<dyn capture::Pipe>)> = vec![];
+    // Both streams are written to the terminal, so they're echoed to stderr unless only stdout is
+    // captured. The end of the output is only seen once every descriptor of the slave side is
+    // closed, so ours is dropped right away
+    #[cfg(unix)]
+    if let Some((master, _slave)) = pty {
+        let stream = if config.capture.stderr() {
+            Stream::Stderr
+        } else {
+            Stream::Stdout
+        };
+        pipes.push((stream, Box::new(master)));
+    }
+    if let Some(stdout) = child.stdout.take() {
+        pipes.push((Stream::Stdout, Box::new(stdout)));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        pipes.push((Stream::Stderr, Box::new(stderr)));
+    }
+    let parsers = capture_output(capture::capture_streams(pipes), config, raw, collector)?;
+
+    let (status, usage) = resource_usage::wait(&mut child)?;
+    finish_output(parsers, status.success(), collector);
+    // Raw mode must be transparent
+    if raw {
+        return Ok(status);
+    }
+    if oom_watch.killed(child.id(), status) {
+        let peak = match (config.resource_usage, &usage) {
+            (true, Some(usage)) => format!(" (max RSS {})", usage.max_rss()),
+            _ => String::new(),
+        };
+        anstream::eprintln!(
+            "(backtracetk: the command was killed by the OOM killer after running out of \
+             memory{peak}, so it couldn't print a backtrace)"
+        );
+    }
+    if let Some(description) = status.code().and_then(ntstatus::describe) {
+        anstream::eprintln!("(backtracetk: the command crashed with {description})");
+    }
+    if let (true, Some(usage)) = (config.resource_usage, usage) {
+        anstream::eprintln!("(backtracetk: {usage})");
+    }
+    Ok(status)
+}
+
+/// Reads the output with the backtraces from stdin, see `--stdin`. Reading stdin always succeeds
+/// as far as the exit summary is concerned.
+fn read_stdin(config: &Config, raw: bool, collector: &mut Collector) -> anyhow::Result<ExitStatus> {
+    // We don't know when the program writing to stdin was built
+    collector.built_before = None;
+    collector.build = None;
+    collector.pid = None;
+    // Echoed to stderr like the output of a command
+    let lines = capture::capture_streams(vec![(Stream::Stderr, Box::new(io::stdin()))]);
+    let parsers = capture_output(lines, config, raw, collector)?;
+    finish_output(parsers, true, collector);
+    Ok(ExitStatus::default())
+}
+
+/// Echoes `lines` to the stream they were read from, passing the backtraces in them to `collector`.
+/// With `raw`, the lines are echoed verbatim. Each stream is parsed separately, so lines from the
+/// other stream interleaved with a backtrace don't break it. Returns the parsers of the streams in
+/// the order their backtraces in progress started, see [`finish_output`].
+fn capture_output(
+    lines: Receiver<io::Result<Line>>,
+    config: &Config,
+    raw: bool,
+    collector: &mut Collector,
+) -> anyhow::Result<Vec<backtracetk::parse::Parser>> {
+    let start = Instant::now();
+    let mut stdout_parser = backtracetk::parse::Parser::from_config(config);
+    let mut stderr_parser = backtracetk::parse::Parser::from_config(config);
+    // The number of lines read so far, and the line the backtrace in progress in each stream
+    // started at, if any
+    let mut seq: u64 = 0;
+    let mut stdout_started = None;
+    let mut stderr_started = None;
+    // Toggled with `SIGUSR1`
+    #[cfg_attr(not(unix), allow(unused_mut))]
+    let mut echo = raw || bool::from(config.echo);
+    // Raw mode must be transparent, so every line is echoed
+    let mut limiter = config
+        .max_echo_rate
+        .filter(|_| !raw)
+        .map(|max_lines| EchoLimiter::new(max_lines, start));
+    loop {
+        // Wake up regularly to handle signals and rate limiting windows even if the command is
+        // quiet
+        let Line { stream, bytes } = match lines.recv_timeout(POLL_INTERVAL) {
+            Ok(line) => line?,
+            Err(RecvTimeoutError::Timeout) => {
+                #[cfg(unix)]
+                handle_signals(&mut echo, raw, collector)?;
+                report_skipped(limiter.as_mut().and_then(|l| l.expired(Instant::now())));
+                collector.tick();
+                continue;
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        #[cfg(unix)]
+        handle_signals(&mut echo, raw, collector)?;
+        report_skipped(limiter.as_mut().and_then(|l| l.expired(Instant::now())));
+        let line = capture::decode_line(&bytes);
+        let time = config.timestamps.then(|| format_elapsed(start.elapsed()));
+        let (parser, started) = match stream {
+            Stream::Stdout => (&mut stdout_parser, &mut stdout_started),
+            Stream::Stderr => (&mut stderr_parser, &mut stderr_started),
+        };
+        // Parse the line before echoing it to know whether it's part of a backtrace
+        let kind = match &time {
+            Some(time) => parser.check_line_at(&line, time),
+            None => parser.check_line(&line),
+        };
+        let in_backtrace = !matches!(
+            kind,
+            LineKind::Unrecognized | LineKind::Ignored | LineKind::Diagnostic | LineKind::Test
+        );
+        if let (LineKind::Panic, Some(pid)) = (kind, collector.pid) {
+            collector.process = process_context::read(pid);
+        }
+        let echo_line = echo
+            && match &mut limiter {
+                Some(limiter) => {
+                    limiter.check(!matches!(kind, LineKind::Unrecognized | LineKind::Ignored))
+                }
+                None => true,
+            };
+        if echo_line && raw {
+            match stream {
+                Stream::Stdout => io::stdout().write_all(&bytes)?,
+                Stream::Stderr => io::stderr().write_all(&bytes)?,
+            }
+        } else if echo_line {
+            let line = match &time {
+                Some(time) => format!("[{time}] {line}"),
+                None => line.clone(),
+            };
+            match stream {
+                Stream::Stdout => anstream::println!("{line}"),
+                Stream::Stderr => anstream::eprintln!("{line}"),
+            }
         }
-        parser.parse_line(line);
+        // Collect backtraces as soon as they're complete to send notifications right away
+        let backtraces = parser.take_backtraces();
+        if !backtraces.is_empty() {
+            // This line may start the next one
+            *started = None;
+        }
+        if in_backtrace && started.is_none() {
+            *started = Some(seq);
+        }
+        seq += 1;
+        backtraces
+            .into_iter()
+            .for_each(|backtrace| collector.collect(backtrace));
+        collector.tick();
+    }
+    report_skipped(limiter.as_mut().and_then(EchoLimiter::take_skipped));
+    let mut parsers = [
+        (stdout_started, stdout_parser),
+        (stderr_started, stderr_parser),
+    ];
+    parsers.sort_by_key(|(started, _)| started.unwrap_or(u64::MAX));
+    Ok(parsers.into_iter().map(|(_, parser)| parser).collect())
+}
+
+/// Collects the backtraces still in progress when the output of a command ended. An error returned
+/// from `main` may be printed as a single `Error: ` line, which is only taken for a report if the
+/// command failed, see [`Parser::keep_error_report`](backtracetk::parse::Parser::keep_error_report).
+fn finish_output(
+    parsers: Vec<backtracetk::parse::Parser>,
+    success: bool,
+    collector: &mut Collector,
+) {
+    for mut parser in parsers {
+        if !success {
+            parser.keep_error_report();
+        }
+        parser
+            .into_backtraces()
+            .into_iter()
+            .for_each(|backtrace| collector.collect(backtrace));
     }
+}
+
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
 
-    for backtrace in parser.into_backtraces() {
-        backtrace.render(&config, &mut Filters::new(&config));
+/// Prints how many lines weren't echoed because of `max_echo_rate`, if any.
+fn report_skipped(skipped: Option<usize>) {
+    if let Some(skipped) = skipped {
+        anstream::eprintln!("(backtracetk: skipped {skipped} lines, see `max_echo_rate`)");
     }
+}
 
+/// Handles the signals received since the last call, see [`signals`].
+#[cfg(unix)]
+fn handle_signals(echo: &mut bool, raw: bool, collector: &mut Collector) -> io::Result<()> {
+    if collector.signals.take_toggle_echo() {
+        *echo = !*echo;
+        // Raw mode must be transparent
+        if !raw {
+            let state = if *echo { "enabled" } else { "disabled" };
+            anstream::eprintln!("(backtracetk: echo {state}, send SIGUSR1 to toggle it)");
+        }
+    }
+    if collector.signals.take_summary() {
+        collector.print_summary()?;
+    }
     Ok(())
 }
 
-pub struct Filters<'a> {
-    filters: Vec<Filter<'a>>,
+/// Formats a duration as hours, minutes, and seconds with one decimal, e.g., `00:02:13.4`.
+fn format_elapsed(elapsed: Duration) -> String {
+    let tenths = elapsed.as_millis() / 100;
+    let secs = tenths / 10;
+    format!(
+        "{:02}:{:02}:{:02}.{}",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60,
+        tenths % 10
+    )
 }
 
-impl<'a> Filters<'a> {
-    fn new(config: &'a Config) -> Self {
-        let mut filters = vec![];
-        for filter in &config.hide {
-            filters.push(filter.into())
+/// Handles the backtraces detected while running a command.
+struct Collector<'a> {
+    config: &'a Config,
+    /// The label for the backtraces, see [`Backtrace::command`]
+    command: Option<String>,
+    #[cfg(unix)]
+    notifier: Option<&'a mut notify::Notifier>,
+    #[cfg(unix)]
+    signals: &'a signals::Signals,
+    /// If rate limiting is enabled, backtraces are rendered as soon as they are collected,
+    /// otherwise they are all rendered when the command exits.
+    rate_limiter: Option<RateLimiter>,
+    /// Where backtraces are rendered, see `--render-to`
+    out: &'a mut dyn io::Write,
+    /// See `--format`
+    format: output::Format,
+    /// See [`Backtrace::built_before`]
+    built_before: Option<SystemTime>,
+    /// See [`Backtrace::build`]
+    build: Option<Arc<BuildInfo>>,
+    /// The process whose state is captured when it panics, see [`Backtrace::process`]
+    pid: Option<u32>,
+    /// The state captured when the last panic was detected, for the backtrace that follows it
+    process: Option<ProcessContext>,
+    /// Runs the `[plugin]` command, if any, without blocking the capture of the output
+    plugin: Option<plugin::Worker>,
+    backtraces: Vec<Backtrace>,
+}
+
+impl Collector<'_> {
+    fn collect(&mut self, mut backtrace: Backtrace) {
+        backtrace.command.clone_from(&self.command);
+        backtrace.built_before = self.built_before;
+        backtrace.build.clone_from(&self.build);
+        // Without a panic line, e.g., for a backtrace printed by a panic hook, the state is read
+        // once the backtrace is complete, if the process is still running
+        backtrace.process = self
+            .process
+            .take()
+            .or_else(|| self.pid.and_then(process_context::read));
+        backtrace.redact(&self.config.redact);
+        match &mut self.plugin {
+            // Handled once the plugin is done with it, see `tick`
+            Some(plugin) => plugin.send(backtrace),
+            None => self.handle(backtrace),
+        }
+    }
+
+    /// Runs the actions for a backtrace that's ready, i.e., after the plugin changed it.
+    fn handle(&mut self, backtrace: Backtrace) {
+        hooks::on_panic(&self.config.notify, &backtrace);
+        hooks::on_backtrace(&self.config.on_backtrace, &backtrace);
+        #[cfg(unix)]
+        if let Some(notifier) = &mut self.notifier {
+            notifier.notify(&backtrace);
+        }
+        if let Some(rate_limiter) = &mut self.rate_limiter {
+            if rate_limiter.check(&backtrace, Instant::now()) {
+                let _ = render_backtrace(&mut self.out, &backtrace, self.config, self.format);
+            }
+        }
+        self.backtraces.push(backtrace);
+    }
+
+    /// Prints how many times each backtrace was captured so far, see [`signals`]. It's printed
+    /// with the rendered backtraces, unless they're meant for a program (e.g., a report), which
+    /// the summary would corrupt, in which case it goes to stderr.
+    #[cfg(unix)]
+    fn print_summary(&mut self) -> io::Result<()> {
+        let mut stderr = anstream::stderr();
+        let out: &mut dyn io::Write = match self.format {
+            output::Format::Pretty | output::Format::RawFiltered => &mut *self.out,
+            _ => &mut stderr,
+        };
+        let groups = exit_summary::groups(&self.backtraces);
+        let panics: usize = groups.iter().map(|group| group.occurrences).sum();
+        match panics {
+            1 => writeln!(out, "(backtracetk: 1 panic so far)")?,
+            _ => writeln!(out, "(backtracetk: {panics} panics so far)")?,
+        }
+        for group in groups {
+            write!(out, "  {} × {}", group.occurrences, group.fingerprint)?;
+            if let Some(message) = &group.message {
+                write!(out, ": {message}")?;
+            }
+            if let Some(location) = &group.location {
+                write!(out, " at {location}")?;
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+
+    /// Waits for the plugin to be done with the backtraces collected so far, e.g., when the output
+    /// of the command ends.
+    fn wait_for_plugin(&mut self) {
+        if let Some(plugin) = self.plugin.take() {
+            for backtrace in plugin.finish() {
+                self.handle(backtrace);
+            }
+        }
+    }
+
+    /// Handles the backtraces the plugin is done with, and prints the summary of rate limiting
+    /// windows that are over.
+    fn tick(&mut self) {
+        let ready = self.plugin.as_mut().map(plugin::Worker::try_recv);
+        for backtrace in ready.into_iter().flatten() {
+            self.handle(backtrace);
+        }
+        if let Some(rate_limiter) = &mut self.rate_limiter {
+            for summary in rate_limiter.expired(Instant::now()) {
+                let _ = summary.render_to(&mut self.out);
+            }
         }
-        Self { filters }
     }
 }
 
-impl FrameFilter for Filters<'_> {
-    fn should_hide(&mut self, frame: &Frame) -> bool {
-        self.filters
-            .iter_mut()
-            .any(|filter| filter.do_match(&frame.function))
+fn dedupe(backtraces: Vec<Backtrace>, config: &Config) -> Vec<Backtrace> {
+    if config.dedupe_adjacent {
+        backtracetk::parse::dedupe_adjacent(backtraces)
+    } else {
+        backtraces
     }
 }
 
-enum Filter<'a> {
-    Pattern(&'a Regex),
-    Range {
-        begin: &'a Regex,
-        end: Option<&'a Regex>,
-        inside: bool,
-    },
+/// Applies the `redact` rules (see [`Backtrace::redact`]) and runs the `[plugin]` command for
+/// backtraces parsed from a log.
+fn prepare(backtraces: &mut [Backtrace], config: &Config) {
+    for backtrace in backtraces {
+        if config.human_panic_reports {
+            read_report(backtrace);
+        }
+        backtrace.redact(&config.redact);
+        plugin::run(&config.plugin, backtrace);
+    }
 }
 
-impl Filter<'_> {
-    fn do_match(&mut self, s: &str) -> bool {
-        match self {
-            Filter::Pattern(regex) => regex.is_match(s),
-            Filter::Range { begin, end, inside } => {
-                if *inside {
-                    let Some(end) = end else { return true };
-                    *inside = !end.is_match(s);
-                    true
-                } else {
-                    *inside = begin.is_match(s);
-                    *inside
-                }
+/// Replaces a backtrace printed by `human-panic` with the one in the crash report it mentions, if
+/// it's still there.
+fn read_report(backtrace: &mut Backtrace) {
+    let report = backtrace.report_file.as_deref();
+    let Some(mut report) = report.and_then(backtracetk::human_panic::read_report) else {
+        return;
+    };
+    report.time = backtrace.time.take();
+    report.command = backtrace.command.take();
+    report.test = backtrace.test.take();
+    *backtrace = report;
+}
+
+fn render_backtraces(
+    out: &mut impl io::Write,
+    backtraces: &[Backtrace],
+    config: &Config,
+    format: output::Format,
+) -> io::Result<()> {
+    if let output::Format::Gitlab = format {
+        return gitlab::write_report(out, backtraces);
+    }
+    if let output::Format::Pretty = format {
+        return backtracetk::render::render_by_thread(out, backtraces, config);
+    }
+    for backtrace in backtraces {
+        render_backtrace(out, backtrace, config, format)?;
+    }
+    Ok(())
+}
+
+fn render_backtrace(
+    out: &mut impl io::Write,
+    backtrace: &Backtrace,
+    config: &Config,
+    format: output::Format,
+) -> io::Result<()> {
+    let filter = &mut Filters::from_config(config);
+    match format {
+        output::Format::Pretty => backtrace.render_to(out, config, filter),
+        output::Format::RawFiltered => {
+            out.write_all(backtrace.raw_text_filtered(filter).as_bytes())
+        }
+        output::Format::Json => {
+            serde_json::to_writer(&mut *out, backtrace)?;
+            writeln!(out)
+        }
+        output::Format::Gitlab => gitlab::write_report(out, std::slice::from_ref(backtrace)),
+        output::Format::Teamcity => teamcity::write_messages(out, backtrace),
+    }
+}
+
+fn run_parse_command(
+    file: &Path,
+    format: output::Format,
+    render_to: &output::Destination,
+    render_args: &RenderArgs,
+) -> anyhow::Result<()> {
+    let mut config = Config::read(&[])?;
+    render_args.apply(&mut config)?;
+    let mut backtraces = backtracetk::parse::parse_file_with_config(file, &config)
+        .with_context(|| format!("failed to read `{}`", file.display()))?;
+    prepare(&mut backtraces, &config);
+    let mut backtraces = dedupe(backtraces, &config);
+    backtracetk::parse::sort_backtraces(&mut backtraces, config.sort);
+    render_backtraces(&mut render_to.open()?, &backtraces, &config, format)?;
+    Ok(())
+}
+
+/// Renders the backtraces in the text pasted into stdin. When stdin is a terminal, an empty line
+/// ends the input so there's no need to know how to send an end of file, otherwise it's read until
+/// the end.
+fn run_paste_command(render_args: &RenderArgs) -> anyhow::Result<()> {
+    let mut config = Config::read(&[])?;
+    render_args.apply(&mut config)?;
+    let interactive = io::stdin().is_terminal();
+    if interactive {
+        eprintln!("Paste the output with the backtraces, then press Enter on an empty line:");
+    }
+    let mut parser = backtracetk::parse::Parser::from_config(&config);
+    let mut pasted = false;
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        // Chat messages often start with empty lines or add trailing whitespace to them
+        if interactive && line.trim().is_empty() {
+            if pasted {
+                break;
             }
+            continue;
+        }
+        pasted = true;
+        parser.parse_line(line.strip_suffix('\r').unwrap_or(&line));
+    }
+    let mut backtraces = parser.into_backtraces();
+    if backtraces.is_empty() {
+        eprintln!("No backtraces found in the pasted text");
+        return Ok(());
+    }
+    prepare(&mut backtraces, &config);
+    let mut backtraces = dedupe(backtraces, &config);
+    backtracetk::parse::sort_backtraces(&mut backtraces, config.sort);
+    render_backtraces(
+        &mut anstream::stderr(),
+        &backtraces,
+        &config,
+        output::Format::Pretty,
+    )?;
+    Ok(())
+}
+
+/// Prints the kind of every line in `file`, returning whether they were all recognized.
+fn run_check_parser(file: &Path, render_args: &RenderArgs) -> anyhow::Result<bool> {
+    let mut config = Config::read(&[])?;
+    render_args.apply(&mut config)?;
+    let contents =
+        std::fs::read(file).with_context(|| format!("failed to read `{}`", file.display()))?;
+    let contents = String::from_utf8_lossy(&contents);
+    let parser = backtracetk::parse::Parser::from_config(&config);
+    let report = backtracetk::parse::check::check(parser, contents.lines());
+
+    let failure = anstyle::AnsiColor::Red.on_default().bold();
+    let mut out = anstream::stdout().lock();
+    let mut failures = report.failures.iter().peekable();
+    for (i, (line, kind)) in contents.lines().zip(&report.kinds).enumerate() {
+        if failures.next_if_eq(&&i).is_some() {
+            let lineno = i + 1;
+            writeln!(out, "{failure}{lineno:>5} FAILED     {line}{failure:#}")?;
+        } else {
+            let lineno = i + 1;
+            writeln!(out, "{lineno:>5} {kind:<10} {line}")?;
         }
     }
+    writeln!(
+        out,
+        "\n{} backtraces, {} lines not recognized",
+        report.backtraces,
+        report.failures.len()
+    )?;
+    Ok(report.failures.is_empty())
 }
 
-impl<'a> From<&'a config::Hide> for Filter<'a> {
-    fn from(value: &'a config::Hide) -> Self {
-        match value {
-            config::Hide::Pattern { pattern } => Filter::Pattern(pattern),
-            config::Hide::Range { begin, end } => Filter::Range {
-                begin,
-                end: end.as_ref(),
-                inside: false,
-            },
+fn run_nextest_command(file: Option<&Path>, render_args: &RenderArgs) -> anyhow::Result<()> {
+    let mut config = Config::read(&[])?;
+    render_args.apply(&mut config)?;
+    let out = &mut anstream::stderr();
+    match file {
+        Some(file) => {
+            let reader = std::fs::File::open(file)
+                .with_context(|| format!("failed to read `{}`", file.display()))?;
+            nextest::run(io::BufReader::new(reader), &config, out)
         }
+        None => nextest::run(io::stdin().lock(), &config, out),
+    }
+}
+
+fn run_triage_command(
+    format: triage::Format,
+    codeowners: Option<&Path>,
+    files: &[PathBuf],
+) -> anyhow::Result<()> {
+    let config = Config::read(&[])?;
+    let mut backtraces = vec![];
+    for file in files {
+        backtraces.extend(
+            backtracetk::parse::parse_file_with_config(file, &config)
+                .with_context(|| format!("failed to read `{}`", file.display()))?,
+        );
+    }
+    prepare(&mut backtraces, &config);
+    let backtraces = dedupe(backtraces, &config);
+    let codeowners = triage::read_codeowners(codeowners)?;
+    let report = triage::Report::new(&backtraces, codeowners.as_ref());
+    match format {
+        triage::Format::Markdown => print!("{}", report.to_markdown()),
+        triage::Format::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+    }
+    Ok(())
+}
+
+fn run_baseline_command(command: BaselineCommand) -> anyhow::Result<()> {
+    let BaselineCommand::Update { files } = command;
+    let config = Config::read(&[])?;
+    let mut fingerprints = BTreeSet::new();
+    for file in &files {
+        let backtraces = backtracetk::parse::parse_file_with_config(file, &config)
+            .with_context(|| format!("failed to read `{}`", file.display()))?;
+        fingerprints.extend(backtraces.iter().map(Backtrace::fingerprint));
     }
+    let path = Config::local_file().unwrap_or_else(|| PathBuf::from("backtracetk.toml"));
+    let fingerprints: Vec<_> = fingerprints.into_iter().collect();
+    config::set_known_panics(&path, &fingerprints)?;
+    println!(
+        "{}: recorded {} known panics",
+        path.display(),
+        fingerprints.len()
+    );
+    Ok(())
+}
+
+fn run_analyze_command(format: analyze::Format, paths: &[PathBuf]) -> anyhow::Result<()> {
+    let config = Config::read(&[])?;
+    let report = analyze::Report::read(paths, &config)?;
+    match format {
+        analyze::Format::Text => print!("{}", report.to_text()),
+        analyze::Format::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+        analyze::Format::Csv => print!("{}", report.to_csv()),
+    }
+    Ok(())
+}
+
+fn run_config_command(command: ConfigCommand) -> anyhow::Result<()> {
+    match command {
+        ConfigCommand::Show { origin, cmd } => {
+            if origin {
+                let (config, provenance) = Config::read_with_provenance(&cmd)?;
+                println!("{config}");
+                println!("Origin of each value:");
+                print!("{provenance}");
+            } else {
+                println!("{}", Config::read(&cmd)?);
+            }
+        }
+        ConfigCommand::Migrate { file } => {
+            let files = file.map(|file| vec![file]).unwrap_or_else(Config::files);
+            for file in files {
+                let deprecations = config::migrate_file(&file)?;
+                if deprecations.is_empty() {
+                    println!("{}: nothing to migrate", file.display());
+                }
+                for deprecation in deprecations {
+                    println!("{}:{}: {deprecation}", file.display(), deprecation.line);
+                }
+            }
+        }
+    }
+    Ok(())
 }
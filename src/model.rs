@@ -0,0 +1,376 @@
+//! The parsed representation of backtraces.
+
+use std::{fmt, path::PathBuf, sync::Arc, time::SystemTime};
+
+use serde::{ser::SerializeStruct, Serialize};
+
+pub use crate::{builder::BacktraceBuilder, fingerprint::Fingerprint};
+use crate::{
+    parse::{parse_location, MAX_MESSAGE_LINES},
+    FrameAnnotator,
+};
+
+/// The version of the JSON representation of a [`Backtrace`], written first in its
+/// `schema_version` field. Fields may be added within a version, so consumers should ignore the
+/// fields they don't know, but removing or renaming a field or changing its type bumps the version.
+/// JSON written before the field existed is version 0.
+pub const SCHEMA_VERSION: u32 = 1;
+
+pub struct Backtrace {
+    pub frames: Vec<Frame>,
+    pub panic_info: Option<PanicInfo>,
+    /// Number of near-identical backtraces printed right after this one that were merged into it.
+    /// See [`dedupe_adjacent`](crate::parse::dedupe_adjacent).
+    pub duplicates: usize,
+    /// The command that printed the backtrace, set when running several commands to tell their
+    /// backtraces apart.
+    pub command: Option<String>,
+    /// The test that printed the backtrace, set when it's known precisely, e.g., from the events
+    /// reported by nextest or the section of `cargo test`'s output it was printed in.
+    pub test: Option<String>,
+    /// When the backtrace was printed, either the timestamp at the start of the log line where it
+    /// begins or the time since the command started if timestamps are enabled when running it.
+    pub time: Option<String>,
+    /// A time the program that printed the backtrace is known to have been built before, e.g., when
+    /// it started running. Code snippets of source files modified later are marked as stale since
+    /// they may not match what was compiled.
+    pub built_before: Option<SystemTime>,
+    /// How the program that printed the backtrace was built, if known.
+    pub build: Option<Arc<BuildInfo>>,
+    /// The state of the process that printed the backtrace when it panicked, if it was captured.
+    /// See [`Config::process_context`](crate::config::Config::process_context).
+    pub process: Option<ProcessContext>,
+    /// The `tracing` spans the program was in when the backtrace was printed, outermost first, e.g.,
+    /// `request{id=42}`. They are taken from the last line logged in `tracing-subscriber`'s default
+    /// format before the backtrace, so they may belong to another thread.
+    pub spans: Vec<String>,
+    /// The span trace reported by `color-eyre`, innermost first. Unlike [`Backtrace::spans`], these
+    /// are the spans of the thread that panicked or created the error.
+    pub span_trace: Vec<SpanTraceEntry>,
+    /// The compiler diagnostics printed since the previous backtrace, e.g., the warnings reported
+    /// by `cargo run` before running the program. At most
+    /// [`MAX_DIAGNOSTICS`](crate::parse::MAX_DIAGNOSTICS), the most recent last.
+    pub diagnostics: Vec<Diagnostic>,
+    /// The note printed with the backtrace suggesting another `RUST_BACKTRACE` setting, e.g.,
+    /// ``note: Some details are omitted, run with `RUST_BACKTRACE=full` for a verbose backtrace.``
+    pub note: Option<String>,
+    /// The lines printed after the frames of the last backtrace in the output, e.g., the epilogue
+    /// of a custom panic hook or cargo's `error: process didn't exit successfully`. At most
+    /// [`MAX_TRAILER_LINES`](crate::parse::MAX_TRAILER_LINES), the last ones.
+    pub trailer: Vec<String>,
+    /// The lines printed before the frames, e.g., the panic line and message and
+    /// `stack backtrace:`, after removing the log prefix and ANSI escape codes. At most
+    /// [`MAX_MESSAGE_LINES`]. See [`Backtrace::raw_text`].
+    pub raw_header: Vec<String>,
+    /// The lines printed after the frames, e.g., the note suggesting another `RUST_BACKTRACE`
+    /// setting, after removing the log prefix and ANSI escape codes. At most [`MAX_MESSAGE_LINES`].
+    pub raw_footer: Vec<String>,
+    /// The crash report written by `human-panic` that has the actual backtrace, see
+    /// [`human_panic::read_report`](crate::human_panic::read_report).
+    pub report_file: Option<PathBuf>,
+}
+
+impl Serialize for Backtrace {
+    /// Writes [`SCHEMA_VERSION`] first, so every backtrace exported as JSON says which version it
+    /// is. The fields only kept for rendering, i.e., `built_before`, the raw text, and the report
+    /// file, are left out.
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Backtrace", 14)?;
+        state.serialize_field("schema_version", &SCHEMA_VERSION)?;
+        state.serialize_field("frames", &self.frames)?;
+        state.serialize_field("panic_info", &self.panic_info)?;
+        state.serialize_field("duplicates", &self.duplicates)?;
+        state.serialize_field("command", &self.command)?;
+        state.serialize_field("test", &self.test)?;
+        state.serialize_field("time", &self.time)?;
+        state.serialize_field("build", &self.build)?;
+        state.serialize_field("process", &self.process)?;
+        state.serialize_field("spans", &self.spans)?;
+        state.serialize_field("span_trace", &self.span_trace)?;
+        state.serialize_field("diagnostics", &self.diagnostics)?;
+        state.serialize_field("note", &self.note)?;
+        state.serialize_field("trailer", &self.trailer)?;
+        state.end()
+    }
+}
+
+impl Backtrace {
+    /// The `RUST_BACKTRACE` setting suggested by [`Backtrace::note`], e.g., `full`.
+    pub fn suggested_rust_backtrace(&self) -> Option<&str> {
+        let (_, rest) = self.note.as_deref()?.split_once("RUST_BACKTRACE=")?;
+        let end = rest
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(rest.len());
+        Some(&rest[..end]).filter(|value| !value.is_empty())
+    }
+
+    /// The innermost frame in user code. See [`Frame::is_user_frame`].
+    pub fn top_user_frame(&self) -> Option<&Frame> {
+        self.frames.iter().find(|frame| frame.is_user_frame())
+    }
+
+    /// Runs `annotator` on every frame adding the resulting annotations to the frame.
+    pub fn annotate(&mut self, annotator: &mut (impl FrameAnnotator + ?Sized)) {
+        for frame in &mut self.frames {
+            let annotations = annotator.annotate(frame);
+            frame.annotations.extend(annotations);
+        }
+    }
+
+    /// Whether two backtraces are near-identical, i.e., they panicked at the same location (in the
+    /// same thread, if both report it) and their frames only differ in symbol hashes or in a few
+    /// of the innermost frames (e.g., the frames of the panic hook that printed them).
+    pub(crate) fn is_duplicate_of(&self, other: &Backtrace) -> bool {
+        /// Maximum number of innermost frames that may differ
+        const MAX_DIFFERENT_FRAMES: usize = 5;
+
+        if let (Some(a), Some(b)) = (&self.panic_info, &other.panic_info) {
+            if a.at != b.at {
+                return false;
+            }
+            // Threads running the same code may panic at the same location
+            if let (Some(a), Some(b)) = (&a.thread, &b.thread) {
+                if a != b {
+                    return false;
+                }
+            }
+        }
+        let common = self
+            .frames
+            .iter()
+            .rev()
+            .zip(other.frames.iter().rev())
+            .take_while(|(a, b)| a.is_same_location(b))
+            .count();
+        common > 0
+            && self.frames.len() - common <= MAX_DIFFERENT_FRAMES
+            && other.frames.len() - common <= MAX_DIFFERENT_FRAMES
+    }
+}
+
+/// Information about how a binary was built, extracted from the binary itself.
+#[derive(Serialize, Debug)]
+pub struct BuildInfo {
+    /// The build ID of the binary in hex, which identifies the exact build.
+    pub build_id: Option<String>,
+    /// The version of rustc that compiled the binary, e.g., `1.79.0 (129f3b996 2024-06-10)`.
+    pub rustc: Option<String>,
+}
+
+/// The state of a running process, read from `/proc/<pid>` on Linux. Each part is missing if it
+/// couldn't be read, e.g., without permission to inspect the process.
+#[derive(Serialize, Debug)]
+pub struct ProcessContext {
+    pub pid: u32,
+    /// The arguments the process was started with, starting with the program.
+    pub cmdline: Vec<String>,
+    pub cwd: Option<String>,
+    /// How many file descriptors the process has open.
+    pub open_fds: Option<usize>,
+    pub memory_maps: Option<MemoryMaps>,
+}
+
+/// A summary of the memory mappings of a process, from `/proc/<pid>/maps`.
+#[derive(Serialize, Debug)]
+pub struct MemoryMaps {
+    /// The number of mappings.
+    pub count: usize,
+    /// The total size of the mappings in bytes.
+    pub size: u64,
+    /// The number of distinct files mapped, e.g., the binary and the shared libraries it uses.
+    pub files: usize,
+}
+
+#[derive(Serialize)]
+pub struct PanicInfo {
+    /// The thread that panicked. Not every format reports it, e.g., `color-backtrace` doesn't.
+    pub thread: Option<String>,
+    pub at: String,
+    pub message: Vec<String>,
+    /// The location of the panic parsed from `at`, if it's a location. See [`PanicInfo::locate`].
+    pub location: Option<SourceInfo>,
+}
+
+impl PanicInfo {
+    /// Sets [`PanicInfo::location`] parsing it from [`PanicInfo::at`] if it isn't set yet. Older
+    /// versions of Rust print the message before the location, e.g.,
+    /// `'explicit panic', src/main.rs:2:5`.
+    pub fn locate(&mut self) {
+        if self.location.is_some() {
+            return;
+        }
+        let at = self.at.rsplit_once(", ").map_or(&*self.at, |(_, at)| at);
+        self.location = parse_location(at).map(|(file, lineno, colno)| SourceInfo {
+            file: file.into(),
+            lineno,
+            colno,
+        });
+    }
+
+    /// Adds a line following the panic line. For `color-backtrace`, the message and location are
+    /// given in separate lines prefixed with `Message:` and `Location:`. For `color-eyre`, the
+    /// location is in the line after `Location:`.
+    pub(crate) fn push_line(&mut self, line: &str) {
+        if self.at.is_empty() {
+            if self.message.last().is_some_and(|last| last == "Location:") {
+                self.message.pop();
+                self.at = line.trim().to_string();
+                return;
+            }
+            let at = line.strip_prefix("Location:").map(str::trim);
+            if let Some(at) = at.filter(|at| !at.is_empty()) {
+                self.at = at.to_string();
+                return;
+            }
+            if let Some(message) = line.strip_prefix("Message:") {
+                self.message.push(message.trim_start().to_string());
+                return;
+            }
+        }
+        if self.message.len() < MAX_MESSAGE_LINES {
+            self.message.push(line.to_string());
+        }
+    }
+}
+
+/// The name of the frame synthesized from the location of a panic printed without a backtrace.
+pub const PANIC_LOCATION: &str = "<panic location>";
+
+#[derive(Serialize)]
+pub struct Frame {
+    pub function: String,
+    pub frameno: u32,
+    pub source_info: Option<SourceInfo>,
+    /// Whether the frame is from code in another language, e.g., a C++ library called through FFI.
+    /// Foreign frames have an unknown symbol, a C++ symbol (demangled in `function`), or a C or C++
+    /// source file.
+    pub foreign: bool,
+    /// Extra information attached by a [`FrameAnnotator`]. See [`Backtrace::annotate`].
+    pub annotations: Vec<Annotation>,
+    /// Whether the frame is hidden regardless of the `hide` rules, e.g., by a plugin.
+    #[serde(skip)]
+    pub hidden: bool,
+    /// The lines the frame was parsed from, e.g., its header and location. See
+    /// [`Backtrace::raw_text`].
+    #[serde(skip)]
+    pub raw: Vec<String>,
+}
+
+/// A labeled string attached to a frame, e.g., the owner of the file or a link to the docs.
+#[derive(Serialize)]
+pub struct Annotation {
+    pub label: String,
+    pub text: String,
+}
+
+impl Frame {
+    /// Whether two frames correspond to the same function and location, ignoring the symbol hash.
+    fn is_same_location(&self, other: &Frame) -> bool {
+        let same_source = match (&self.source_info, &other.source_info) {
+            (Some(a), Some(b)) => a.file == b.file && a.lineno == b.lineno,
+            (None, None) => true,
+            _ => false,
+        };
+        same_source && strip_hash(&self.function) == strip_hash(&other.function)
+    }
+
+    /// Whether the frame belongs to the user's code, i.e., it has a location that isn't in the
+    /// standard library or in a dependency downloaded by cargo.
+    pub fn is_user_frame(&self) -> bool {
+        let Some(source_info) = &self.source_info else {
+            return false;
+        };
+        let file = source_info.file.replace('\\', "/");
+        let external = file.starts_with("/rustc/")
+            || file.contains("/rustlib/src/rust/")
+            || file.contains("/.cargo/registry/")
+            || file.contains("/.cargo/git/");
+        !external && !matches!(self.crate_name(), "std" | "core" | "alloc")
+    }
+
+    /// The name of the crate the frame's function belongs to. See [`crate_name`].
+    pub fn crate_name(&self) -> &str {
+        crate_name(&self.function)
+    }
+}
+
+/// The name of the crate a function belongs to, i.e., the first segment of its path. For trait
+/// methods like `<alloc::boxed::Box<F> as core::ops::FnOnce<A>>::call_once`, this is the crate of
+/// the self type.
+pub fn crate_name(function: &str) -> &str {
+    let function = function.trim_start_matches(['<', '&']);
+    let function = function.strip_prefix("mut ").unwrap_or(function);
+    let function = function.strip_prefix("dyn ").unwrap_or(function);
+    let end = function
+        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .unwrap_or(function.len());
+    &function[..end]
+}
+
+/// Strips the hash from a symbol name, e.g., `my_crate::main::h0123456789abcdef`.
+pub fn strip_hash(function: &str) -> &str {
+    match function.rsplit_once("::h") {
+        Some((name, hash)) if hash.len() == 16 && hash.bytes().all(|b| b.is_ascii_hexdigit()) => {
+            name
+        }
+        _ => function,
+    }
+}
+
+/// A warning or error reported by the compiler, e.g.,
+/// ```text
+/// warning: unused variable: `x`
+///  --> src/main.rs:3:9
+/// ```
+/// Only diagnostics pointing at a location are recorded, so summaries printed by cargo like
+/// ``warning: `app` (bin "app") generated 1 warning`` are ignored.
+#[derive(Serialize, Clone)]
+pub struct Diagnostic {
+    /// The level of the diagnostic including its code, if any, e.g., `warning` or `error[E0308]`
+    pub level: String,
+    pub message: String,
+    pub location: SourceInfo,
+}
+
+/// A span in a span trace reported by `color-eyre`, e.g.,
+/// ```text
+///    0: app::config::read_file with path="app.toml"
+///       at src/config.rs:12
+/// ```
+#[derive(Serialize)]
+pub struct SpanTraceEntry {
+    /// The name of the span including its module, e.g., `app::config::read_file`
+    pub name: String,
+    /// The fields of the span as printed, e.g., `path="app.toml"`
+    pub fields: Option<String>,
+    pub location: Option<SourceInfo>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct SourceInfo {
+    pub file: String,
+    pub lineno: usize,
+    /// The column of the location. Some formats (e.g., `human-panic` and `color-backtrace`) only
+    /// report the line.
+    pub colno: Option<usize>,
+}
+
+impl SourceInfo {
+    /// Whether both locations point at the same line of the same file. Relative paths may start
+    /// with `./`, e.g., in frames but not in panic lines.
+    pub fn is_same_line(&self, other: &SourceInfo) -> bool {
+        let file = self.file.strip_prefix("./").unwrap_or(&self.file);
+        let other_file = other.file.strip_prefix("./").unwrap_or(&other.file);
+        file == other_file && self.lineno == other.lineno
+    }
+}
+
+impl fmt::Display for SourceInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.file, self.lineno)?;
+        if let Some(colno) = self.colno {
+            write!(f, ":{colno}")?;
+        }
+        Ok(())
+    }
+}
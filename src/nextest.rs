@@ -0,0 +1,32 @@
+//! Rendering the backtraces of failed tests from the events reported by `cargo nextest`, e.g.,
+//! ```bash
+//! NEXTEST_EXPERIMENTAL_LIBTEST_JSON=1 cargo nextest run --message-format libtest-json | backtracetk nextest
+//! ```
+//! See [`backtracetk::parse::nextest`] for how the events are read.
+
+use std::io::{self, BufRead};
+
+use backtracetk::{
+    config::Config,
+    parse::{nextest::Event, Parser},
+};
+
+/// Reads events from `reader` until it's exhausted, rendering the backtraces of failed tests to
+/// `out` as soon as the test finishes. Lines that aren't events are echoed to stderr.
+pub fn run(reader: impl BufRead, config: &Config, out: &mut impl io::Write) -> anyhow::Result<()> {
+    for line in reader.lines() {
+        let line = line?;
+        let Some(event) = Event::parse(&line) else {
+            anstream::eprintln!("{line}");
+            continue;
+        };
+        let mut backtraces = event.backtraces(Parser::from_config(config));
+        if backtraces.is_empty() {
+            continue;
+        }
+        crate::prepare(&mut backtraces, config);
+        let backtraces = crate::dedupe(backtraces, config);
+        crate::render_backtraces(out, &backtraces, config, crate::output::Format::Pretty)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,60 @@
+//! Sending events to an editor listening on a Unix socket, so it can jump to the location of a panic
+//! as soon as it happens. Events are JSON objects, one per line, e.g.,
+//! ```json
+//! {"file": "/home/user/project/src/main.rs", "line": 10, "col": 5, "message": "explicit panic"}
+//! ```
+//! The location is the one of the top user frame (see [`Backtrace::top_user_frame`]). Backtraces
+//! without user frames don't produce events.
+
+use std::{io::Write, os::unix::net::UnixStream, path::Path};
+
+use backtracetk::model::Backtrace;
+use serde_json::json;
+
+pub struct Notifier {
+    stream: Option<UnixStream>,
+}
+
+impl Notifier {
+    /// Connects to the socket at `path`. If the connection fails we print a warning and don't send
+    /// any events, since the editor not listening shouldn't prevent running the command.
+    pub fn connect(path: &Path) -> Notifier {
+        let stream = UnixStream::connect(path)
+            .map_err(|err| {
+                eprintln!(
+                    "Warning: failed to connect to `{}`: {err}. Events won't be sent.",
+                    path.display()
+                )
+            })
+            .ok();
+        Notifier { stream }
+    }
+
+    pub fn notify(&mut self, backtrace: &Backtrace) {
+        let Some(stream) = &mut self.stream else {
+            return;
+        };
+        let Some(source_info) = backtrace
+            .top_user_frame()
+            .and_then(|frame| frame.source_info.as_ref())
+        else {
+            return;
+        };
+        let file = Path::new(&source_info.file);
+        let file = file.canonicalize().unwrap_or_else(|_| file.to_path_buf());
+        let message = backtrace
+            .panic_info
+            .as_ref()
+            .map(|panic_info| panic_info.message.join("\n"));
+        let event = json!({
+            "file": file,
+            "line": source_info.lineno,
+            "col": source_info.colno,
+            "message": message,
+        });
+        if writeln!(stream, "{event}").is_err() {
+            // The editor went away, stop sending events
+            self.stream = None;
+        }
+    }
+}
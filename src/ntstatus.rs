@@ -0,0 +1,31 @@
+//! Describing how a Windows program crashed. Windows has no signals, so a program killed by an
+//! unhandled exception (e.g., an access violation or a stack overflow) exits with an `NTSTATUS`
+//! code instead, like `0xC0000005`, which is hard to recognize as a negative decimal exit code. Such
+//! crashes usually don't print a backtrace, so the footer says what happened.
+
+/// Describes the exit code of a program killed by an exception, e.g.,
+/// `STATUS_ACCESS_VIOLATION (0xC0000005): access violation`. Only the codes programs commonly exit
+/// with when they crash are known.
+pub fn describe(code: i32) -> Option<String> {
+    let code = code as u32;
+    let (name, description) = match code {
+        0x80000003 => ("STATUS_BREAKPOINT", "a breakpoint was hit"),
+        0xC0000005 => ("STATUS_ACCESS_VIOLATION", "access violation"),
+        0xC0000008 => ("STATUS_INVALID_HANDLE", "invalid handle"),
+        0xC0000017 => ("STATUS_NO_MEMORY", "out of memory"),
+        0xC000001D => ("STATUS_ILLEGAL_INSTRUCTION", "illegal instruction"),
+        0xC0000094 => ("STATUS_INTEGER_DIVIDE_BY_ZERO", "integer division by zero"),
+        0xC00000FD => ("STATUS_STACK_OVERFLOW", "stack overflow"),
+        0xC0000135 => ("STATUS_DLL_NOT_FOUND", "a DLL wasn't found"),
+        0xC0000139 => ("STATUS_ENTRYPOINT_NOT_FOUND", "a DLL is missing a function"),
+        0xC000013A => ("STATUS_CONTROL_C_EXIT", "interrupted with Ctrl+C"),
+        0xC0000142 => ("STATUS_DLL_INIT_FAILED", "a DLL failed to initialize"),
+        0xC0000374 => ("STATUS_HEAP_CORRUPTION", "heap corruption"),
+        // Raised by `__fastfail`, which Rust uses for `std::process::abort`, e.g., after a panic
+        // while panicking or when a panic can't unwind
+        0xC0000409 => ("STATUS_STACK_BUFFER_OVERRUN", "the program aborted"),
+        0xE06D7363 => ("C++ exception", "an uncaught C++ exception"),
+        _ => return None,
+    };
+    Some(format!("{name} (0x{code:08X}): {description}"))
+}
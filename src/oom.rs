@@ -0,0 +1,116 @@
+//! Telling whether the command was killed by the OOM killer. The process is killed with `SIGKILL`,
+//! so there's no panic nor backtrace to render, only a command that vanished.
+
+use std::process::ExitStatus;
+
+/// The evidence of OOM kills collected before running the command, to compare with the evidence
+/// after it exits.
+pub struct OomWatch {
+    /// The number of processes in our cgroup killed by the OOM killer, the command runs in it too
+    #[cfg(target_os = "linux")]
+    oom_kills: Option<u64>,
+    /// When the command started, in the clock of the kernel log timestamps
+    #[cfg(target_os = "linux")]
+    started: Option<f64>,
+}
+
+impl OomWatch {
+    pub fn start() -> OomWatch {
+        OomWatch {
+            #[cfg(target_os = "linux")]
+            oom_kills: linux::cgroup_oom_kills(),
+            #[cfg(target_os = "linux")]
+            started: linux::kernel_log_time(),
+        }
+    }
+
+    /// Whether the command with the given `pid` exiting with `status` was killed by the OOM killer,
+    /// i.e., it was killed with `SIGKILL` and either an OOM kill was counted in our cgroup while it
+    /// ran or the kernel log reports it after it started. Older entries are about an earlier
+    /// process with the same pid.
+    #[cfg(target_os = "linux")]
+    pub fn killed(&self, pid: u32, status: ExitStatus) -> bool {
+        use std::os::unix::process::ExitStatusExt;
+
+        if status.signal() != Some(libc::SIGKILL) {
+            return false;
+        }
+        let counted = match (self.oom_kills, linux::cgroup_oom_kills()) {
+            (Some(before), Some(after)) => after > before,
+            _ => false,
+        };
+        if counted {
+            return true;
+        }
+        self.started
+            .is_some_and(|started| linux::kernel_log_reports(pid, started))
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn killed(&self, _: u32, _: ExitStatus) -> bool {
+        false
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::{fs, process::Command};
+
+    /// The `oom_kill` counter of our cgroup, in `memory.events` with cgroup v2 or in
+    /// `memory.oom_control` with the memory controller of cgroup v1.
+    pub fn cgroup_oom_kills() -> Option<u64> {
+        let cgroups = fs::read_to_string("/proc/self/cgroup").ok()?;
+        let files = cgroups.lines().find_map(|line| {
+            if let Some(path) = line.strip_prefix("0::") {
+                return Some(vec![format!("/sys/fs/cgroup{path}/memory.events")]);
+            }
+            let (_, path) = line.split_once(":memory:")?;
+            // In containers, the hierarchy is often mounted at our cgroup
+            Some(vec![
+                format!("/sys/fs/cgroup/memory{path}/memory.oom_control"),
+                "/sys/fs/cgroup/memory/memory.oom_control".to_string(),
+            ])
+        })?;
+        let events = files
+            .iter()
+            .find_map(|file| fs::read_to_string(file).ok())?;
+        events
+            .lines()
+            .find_map(|line| line.strip_prefix("oom_kill "))?
+            .trim()
+            .parse()
+            .ok()
+    }
+
+    /// The current time in seconds of the clock of the kernel log timestamps, which is close to
+    /// `CLOCK_MONOTONIC`.
+    pub fn kernel_log_time() -> Option<f64> {
+        // SAFETY: `timespec` is plain data
+        let mut now: libc::timespec = unsafe { std::mem::zeroed() };
+        // SAFETY: the pointer is valid for the duration of the call
+        if unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now) } != 0 {
+            return None;
+        }
+        Some(now.tv_sec as f64 + now.tv_nsec as f64 / 1e9)
+    }
+
+    /// Whether the kernel log says the OOM killer killed `pid` after `since`, e.g.,
+    /// `[ 1234.567890] Out of memory: Killed process 1234 (app) total-vm:...`. Entries without a
+    /// timestamp are ignored, since they may be about an earlier process with the same pid. Reading
+    /// the log may require privileges, in which case nothing is reported.
+    pub fn kernel_log_reports(pid: u32, since: f64) -> bool {
+        let Ok(output) = Command::new("dmesg").output() else {
+            return false;
+        };
+        let killed = format!("Killed process {pid} ");
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .any(|line| line.contains(&killed) && timestamp(line).is_some_and(|time| time >= since))
+    }
+
+    /// The timestamp in seconds of a kernel log entry, e.g., `1234.567890` in `[ 1234.567890] ...`.
+    fn timestamp(line: &str) -> Option<f64> {
+        let (time, _) = line.strip_prefix('[')?.split_once(']')?;
+        time.trim().parse().ok()
+    }
+}
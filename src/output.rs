@@ -0,0 +1,122 @@
+//! Where rendered backtraces are written.
+
+use std::{
+    fs::{File, OpenOptions},
+    io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anstream::AutoStream;
+use anyhow::Context;
+
+/// How backtraces are written, see `--format`.
+#[derive(Clone, Copy, Default, clap::ValueEnum)]
+pub enum Format {
+    /// Rendered by backtracetk
+    #[default]
+    Pretty,
+    /// The text of the backtrace as printed by the standard library, leaving out hidden frames
+    RawFiltered,
+    /// The parsed backtrace as a JSON object on a single line, including hidden frames
+    Json,
+    /// A GitLab Code Quality report with every panic, written once all backtraces are collected
+    Gitlab,
+    /// TeamCity service messages failing the test that panicked or reporting a build problem
+    Teamcity,
+}
+
+impl Format {
+    /// Whether backtraces are written all at once in a report instead of one by one.
+    pub fn is_report(self) -> bool {
+        matches!(self, Format::Gitlab)
+    }
+}
+
+/// A destination for rendered output given on the command line as `stderr`, `stdout`, `tty` (the
+/// controlling terminal, even if stderr is redirected), `fd:N` (an open file descriptor), or the
+/// path of a file (e.g., `/dev/pts/3` to use a second terminal).
+#[derive(Clone, Debug)]
+pub enum Destination {
+    Stderr,
+    Stdout,
+    Tty,
+    #[cfg(unix)]
+    Fd(i32),
+    File(PathBuf),
+}
+
+impl Destination {
+    /// Opens the destination. Colors are emitted only if the destination is a terminal.
+    pub fn open(&self) -> anyhow::Result<Box<dyn io::Write>> {
+        let file = match self {
+            Destination::Stderr => return Ok(Box::new(anstream::stderr())),
+            Destination::Stdout => return Ok(Box::new(anstream::stdout())),
+            Destination::Tty => open_for_append(Path::new(TTY))?,
+            #[cfg(unix)]
+            Destination::Fd(fd) => open_fd(*fd)?,
+            Destination::File(path) => open_for_append(path)?,
+        };
+        Ok(Box::new(AutoStream::auto(file)))
+    }
+}
+
+impl FromStr for Destination {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stderr" => return Ok(Destination::Stderr),
+            "stdout" => return Ok(Destination::Stdout),
+            "tty" => return Ok(Destination::Tty),
+            _ => {}
+        }
+        if let Some(fd) = s.strip_prefix("fd:") {
+            #[cfg(unix)]
+            return fd
+                .parse()
+                .map(Destination::Fd)
+                .map_err(|_| format!("invalid file descriptor `{fd}`"));
+            #[cfg(not(unix))]
+            return Err("file descriptors are only supported on Unix".to_string());
+        }
+        Ok(Destination::File(PathBuf::from(s)))
+    }
+}
+
+#[cfg(unix)]
+const TTY: &str = "/dev/tty";
+#[cfg(not(unix))]
+const TTY: &str = "CONOUT$";
+
+fn open_for_append(path: &Path) -> anyhow::Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("failed to open `{}`", path.display()))
+}
+
+/// Opens the file descriptor `fd`, which must be open for writing, e.g., with `3>out.txt` in the
+/// shell. The file is a duplicate of `fd`, so the same descriptor can be opened more than once,
+/// e.g., with `--summary-fd 3 --render-to fd:3`, and a standard stream stays usable by the rest of
+/// the process. Descriptors other than the standard streams are closed on exec, so the commands we
+/// run don't inherit them.
+#[cfg(unix)]
+pub fn open_fd(fd: i32) -> anyhow::Result<File> {
+    use std::os::fd::BorrowedFd;
+
+    // SAFETY: `fcntl` has no memory safety requirements, it fails if the descriptor isn't open
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFD) };
+    if flags < 0 {
+        anyhow::bail!("file descriptor {fd} is not open");
+    }
+    if fd > 2 {
+        // SAFETY: as above
+        unsafe { libc::fcntl(fd, libc::F_SETFD, flags | libc::FD_CLOEXEC) };
+    }
+    // SAFETY: the descriptor is open, and it's only borrowed to duplicate it. The duplicate is
+    // closed on exec.
+    let fd = unsafe { BorrowedFd::borrow_raw(fd) }.try_clone_to_owned()?;
+    Ok(File::from(fd))
+}
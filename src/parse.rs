@@ -0,0 +1,1492 @@
+//! Reading backtraces from the output of a program.
+
+pub mod check;
+mod miri;
+#[cfg(feature = "cli")]
+pub mod nextest;
+#[cfg(feature = "parallel")]
+mod parallel;
+mod span_trace;
+mod verification;
+
+use std::{borrow::Cow, collections::VecDeque, fmt, path::PathBuf};
+
+use regex::Regex;
+
+use crate::{
+    config::{self, Config},
+    foreign, Backtrace, BacktraceBuilder, Diagnostic, PanicInfo, SourceInfo, SpanTraceEntry,
+    PANIC_LOCATION,
+};
+#[cfg(feature = "parallel")]
+pub use parallel::{parse_bytes_parallel, parse_file, parse_file_with_config};
+
+/// How many of the lines printed after the last backtrace are kept, see [`Backtrace::trailer`].
+pub const MAX_TRAILER_LINES: usize = 10;
+
+/// How many lines of a panic message are kept. The lines after a panic line are part of its message
+/// until a backtrace or another panic starts, so a panic printed without a backtrace could
+/// otherwise take the rest of the output.
+pub const MAX_MESSAGE_LINES: usize = 1000;
+
+/// Merges adjacent backtraces that are near-identical, which happens when a trace is printed twice,
+/// e.g., by a custom panic hook and then by the default one. The first backtrace is kept (taking
+/// the panic info of the duplicate if it doesn't have one) and [`Backtrace::duplicates`] records
+/// how many were merged into it.
+pub fn dedupe_adjacent(backtraces: Vec<Backtrace>) -> Vec<Backtrace> {
+    let mut deduped: Vec<Backtrace> = vec![];
+    for backtrace in backtraces {
+        match deduped.last_mut() {
+            Some(prev) if prev.is_duplicate_of(&backtrace) => {
+                prev.duplicates += 1 + backtrace.duplicates;
+                if prev.panic_info.is_none() {
+                    prev.panic_info = backtrace.panic_info;
+                }
+                // Only the last backtrace has a trailer
+                if prev.trailer.is_empty() {
+                    prev.trailer = backtrace.trailer;
+                }
+            }
+            _ => deduped.push(backtrace),
+        }
+    }
+    deduped
+}
+
+/// Sorts backtraces in the given order, see [`Config::sort`]. The sort is stable, so backtraces
+/// that compare equal keep the order they were captured in.
+pub fn sort_backtraces(backtraces: &mut [Backtrace], order: config::Sort) {
+    let message = |backtrace: &Backtrace| {
+        let panic_info = backtrace.panic_info.as_ref();
+        panic_info.and_then(|panic_info| panic_info.message.first().cloned())
+    };
+    match order {
+        config::Sort::Capture => {}
+        // Backtraces without a time go last
+        config::Sort::Time => {
+            backtraces.sort_by(|a, b| (a.time.is_none(), &a.time).cmp(&(b.time.is_none(), &b.time)))
+        }
+        config::Sort::Fingerprint => backtraces.sort_by_cached_key(Backtrace::fingerprint),
+        config::Sort::Message => backtraces.sort_by_cached_key(message),
+        config::Sort::UserFrames => backtraces.sort_by_cached_key(|backtrace| {
+            let user_frames = backtrace
+                .frames
+                .iter()
+                .filter(|f| f.is_user_frame())
+                .count();
+            std::cmp::Reverse(user_frames)
+        }),
+    }
+}
+
+/// How many of the most recent diagnostics are kept, see [`Backtrace::diagnostics`].
+pub const MAX_DIAGNOSTICS: usize = 5;
+
+/// Parses backtraces line by line. Frames are assembled as lines are parsed, so only the lines that
+/// are part of a backtrace (or a panic message) are kept in memory.
+pub struct Parser {
+    panic_regex: Regex,
+    prefix_regex: Regex,
+    /// See the `log_prefix` option
+    log_prefix: Option<Regex>,
+    backtraces: Vec<Backtrace>,
+    /// The backtrace in progress
+    builder: BacktraceBuilder,
+    panic_info: Option<PanicInfo>,
+    in_panic_info: bool,
+    /// An `Error: ` line and the lines of causes after it, held until the rest of an error report
+    /// follows them, see [`ParsedLine::ErrorReport`]
+    pending_error: Option<PendingError>,
+    /// Whether the lines so far are part of the causes of an error, see [`ParsedLine::ErrorReport`]
+    in_error_report: bool,
+    /// Whether the backtrace in progress is for an error report, which is kept even if it has no
+    /// frames, see [`ParsedLine::ErrorReport`]
+    error_report: bool,
+    /// Whether the lines so far are part of a span trace, see [`span_trace`]
+    in_span_trace: bool,
+    /// The span trace of the backtrace in progress, see [`Backtrace::span_trace`]
+    span_trace: Vec<SpanTraceEntry>,
+    /// Whether the last line was a frame header, in which case a source line following it belongs
+    /// to that frame.
+    after_header: bool,
+    /// Whether the last line was part of a frame (its header or its source line), in which case an
+    /// inlined frame may follow.
+    after_frame: bool,
+    /// When the backtrace in progress was printed
+    time: Option<String>,
+    /// The test whose captured output is being parsed, see [`parse_test_section`]
+    test: Option<String>,
+    /// The test of the backtrace in progress, see [`Backtrace::test`]
+    backtrace_test: Option<String>,
+    /// The spans of the last line logged with `tracing-subscriber`, see [`Parser::record_spans`]
+    spans: Vec<String>,
+    /// The spans when the backtrace in progress was printed, see [`Backtrace::spans`]
+    backtrace_spans: Vec<String>,
+    /// Whether the lines being parsed are part of an error reported by Miri, see [`miri`].
+    in_miri: bool,
+    /// Where the parser is within a report of a verification tool, see [`verification`].
+    verification: verification::State,
+    /// The level and message of the diagnostic in the last line, whose location is in the next one
+    diagnostic: Option<(String, String)>,
+    diagnostics: VecDeque<Diagnostic>,
+    /// The note suggesting another `RUST_BACKTRACE` setting for the backtrace in progress
+    note: Option<String>,
+    /// The lines after the frames of the backtrace in progress, see [`Backtrace::trailer`]
+    trailer: VecDeque<String>,
+    /// See [`Backtrace::raw_header`] and [`Backtrace::raw_footer`]
+    raw_header: Vec<String>,
+    raw_footer: Vec<String>,
+    /// Which parts of the state carried over a panic line were replaced since the parser was
+    /// created, see `parse_bytes_parallel`
+    replaced: Replaced,
+}
+
+/// The lines of a possible error report, see [`Parser::pending_error`].
+struct PendingError {
+    lines: Vec<String>,
+    /// When the `Error: ` line was printed
+    time: Option<String>,
+}
+
+/// Which parts of the state carried over a panic line a parser replaced, i.e., don't depend on the
+/// lines before the ones it parsed.
+#[derive(Default, Clone, Copy)]
+#[cfg_attr(not(feature = "parallel"), allow(dead_code))]
+struct Replaced {
+    test: bool,
+    spans: bool,
+    diagnostics: bool,
+}
+
+/// How the parser interpreted a line, see [`Parser::check_line`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LineKind {
+    /// The first line of a panic or of an error reported by a tool, e.g., Miri
+    Panic,
+    /// A line of the message of a panic
+    PanicMessage,
+    /// The line before the frames, e.g., `stack backtrace:`
+    BacktraceStart,
+    /// The header of a frame, a frame inlined into the previous one, a step of a trace, or a line
+    /// saying some frames were omitted
+    Frame,
+    /// The location of a frame
+    Source,
+    /// A note suggesting another `RUST_BACKTRACE` setting
+    Note,
+    /// A diagnostic reported by the compiler or its location
+    Diagnostic,
+    /// The header of the captured output of a test, e.g., `---- tests::parse stdout ----`
+    Test,
+    /// A line of a span trace, see [`Backtrace::span_trace`]
+    SpanTrace,
+    /// Another line of a report by a verification tool or Miri
+    Report,
+    /// A line matching a pattern where it isn't expected, e.g., a source line that doesn't follow
+    /// a frame header
+    Ignored,
+    /// A line that doesn't match any pattern
+    Unrecognized,
+}
+
+impl fmt::Display for LineKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            LineKind::Panic => "panic",
+            LineKind::PanicMessage => "message",
+            LineKind::BacktraceStart => "start",
+            LineKind::Frame => "frame",
+            LineKind::Source => "source",
+            LineKind::Note => "note",
+            LineKind::Diagnostic => "diagnostic",
+            LineKind::Test => "test",
+            LineKind::SpanTrace => "span",
+            LineKind::Report => "report",
+            LineKind::Ignored => "ignored",
+            LineKind::Unrecognized => "-",
+        };
+        f.pad(name)
+    }
+}
+
+enum ParsedLine<'a> {
+    /// A line reporting a panic, e.g.,
+    /// ```ignore
+    /// thread 'rustc' panicked at /rustc/b3aa8e7168a3d940122db3561289ffbf3f587262/compiler/rustc_errors/src/lib.rs:1651:9:
+    /// ```
+    ThreadPanic { thread: String, at: String },
+    /// The first line of a panic reported by `color-backtrace`. It's followed by the message and
+    /// location of the panic, e.g.,
+    /// ```ignore
+    /// The application panicked (crashed).
+    /// Message:  called `Option::unwrap()` on a `None` value
+    /// Location: src/main.rs:10
+    /// ```
+    ApplicationPanic,
+    /// The first line of an error returned from `main` and reported with `anyhow` or `eyre`. It's
+    /// followed by the causes of the error and by the backtrace, if it was captured, e.g.,
+    /// ```ignore
+    /// Error: failed to load config
+    ///
+    /// Caused by:
+    ///     0: failed to read `app.toml`
+    ///     1: No such file or directory (os error 2)
+    ///
+    /// Stack backtrace:
+    /// ```
+    /// Programs also log lines starting with `Error: `, so the line is only taken for a report
+    /// once `Caused by:`, a backtrace, or a span trace follows it.
+    ErrorReport,
+    /// The line printed by `human-panic` with the path to the crash report, e.g.,
+    /// ```ignore
+    /// We have generated a report file at "/tmp/report-8b1e5f0a.toml". Submit an issue or email ...
+    /// ```
+    HumanPanicReport(PathBuf),
+    /// A note suggesting another `RUST_BACKTRACE` setting, either after a panic when backtraces
+    /// are disabled or after the frames of a short backtrace, e.g.,
+    /// ```ignore
+    /// note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace
+    /// ```
+    BacktraceNote(&'a str),
+    /// The header printed by `color-eyre` before a span trace, see [`span_trace`]
+    SpanTraceStart,
+    /// The begining of a trace starts with `stack backtrace:`, or with a `━━━ BACKTRACE ━━━` line
+    /// for `color-backtrace`
+    BacktraceStart,
+    /// The "header" of a frame containing the frame number and the function's name, e.g.,
+    /// ```ignore
+    ///   28: rustc_middle::ty::context::tls::enter_context`
+    /// ```
+    BacktraceHeader { function: &'a str, frameno: u32 },
+    /// A frame inlined into the previous one as printed in `human-panic` reports, e.g.,
+    /// ```ignore
+    ///    3:     0x55d5f6a1b2c3 - std::rt::lang_start_internal::h1bd1b3e2c5c35d4e
+    ///                          - std::panicking::try::h7e5e3a9b1c2d3e4f
+    /// ```
+    InlinedFrame(&'a str),
+    /// A line in place of frames omitted from a short backtrace, e.g., in a rustc ICE or by
+    /// `color-backtrace`
+    /// ```ignore
+    ///       [... omitted 2 frames ...]
+    ///                                 ⋮ 13 frames hidden ⋮
+    /// ```
+    OmittedFrames,
+    /// Line containing source information about a frame, e.g.,
+    /// ```ignore
+    ///              at /rustc/b3aa8e7168a3d940122db3561289ffbf3f587262/compiler/rustc_middle/src/ty/context/tls.rs:79:9
+    /// ```
+    BacktraceSource {
+        file: &'a str,
+        lineno: usize,
+        colno: Option<usize>,
+    },
+    /// The first line of an error reported by Miri, e.g.,
+    /// ```ignore
+    /// error: Undefined Behavior: memory access failed: alloc1234 has been freed
+    /// ```
+    MiriError(&'a str),
+    /// A frame in an error reported by Miri, with its location if it's in the same line, e.g.,
+    /// ```ignore
+    ///   = note: inside `read` at src/main.rs:4:20: 4:24
+    /// ```
+    MiriFrame {
+        function: &'a str,
+        location: Option<(&'a str, usize, Option<usize>)>,
+    },
+    /// A span pointing at a location in an error reported by the compiler or Miri, e.g.,
+    /// ```ignore
+    ///  --> src/main.rs:9:5
+    /// ```
+    Span {
+        file: &'a str,
+        lineno: usize,
+        colno: Option<usize>,
+    },
+    /// The line after the errors reported by the compiler or Miri, e.g.,
+    /// ```ignore
+    /// error: aborting due to 1 previous error
+    /// ```
+    ErrorsEnd,
+    /// The first line of a diagnostic reported by the compiler, e.g.,
+    /// ```ignore
+    /// error[E0308]: mismatched types
+    /// ```
+    Diagnostic { level: &'a str, message: &'a str },
+    /// The first line of the result of a Kani check, e.g.,
+    /// ```ignore
+    /// Check 1: main.assertion.1
+    /// ```
+    KaniCheck,
+    /// A field in the result of a Kani check, e.g.,
+    /// ```ignore
+    ///          - Status: FAILURE
+    /// ```
+    KaniField { field: &'a str, value: &'a str },
+    /// The first line of a counterexample trace, e.g.,
+    /// ```ignore
+    /// Trace for main.assertion.1:
+    /// ```
+    TraceStart,
+    /// A step in a counterexample trace or the location of the violated property, e.g.,
+    /// ```ignore
+    /// State 19 file src/main.rs function main line 4 thread 0
+    /// ```
+    TraceStep(verification::Step<'a>),
+    /// The line after the steps of a counterexample trace, followed by the violated property
+    ViolatedProperty,
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Parser {
+    pub fn new() -> Parser {
+        let panic_regex =
+            // Since Rust 1.88, the ID of the thread follows its name, e.g., `thread 'main' (1234)`
+            Regex::new(r"^thread\s+'(?P<thread>[^']+)'(?:\s+\(\d+\))?\s+panicked\s+at\s+(?P<at>.+)")
+                .unwrap();
+        Parser {
+            panic_regex,
+            prefix_regex: prefix_regex(),
+            log_prefix: None,
+            backtraces: vec![],
+            builder: BacktraceBuilder::new(),
+            panic_info: None,
+            in_panic_info: false,
+            pending_error: None,
+            in_error_report: false,
+            error_report: false,
+            in_span_trace: false,
+            span_trace: vec![],
+            after_header: false,
+            after_frame: false,
+            time: None,
+            test: None,
+            backtrace_test: None,
+            spans: vec![],
+            backtrace_spans: vec![],
+            in_miri: false,
+            verification: verification::State::None,
+            diagnostic: None,
+            diagnostics: VecDeque::new(),
+            note: None,
+            trailer: VecDeque::new(),
+            raw_header: vec![],
+            raw_footer: vec![],
+            replaced: Replaced::default(),
+        }
+    }
+
+    /// A parser with the options in `config`, i.e., also stripping the prefixes matching
+    /// `log_prefix`.
+    pub fn from_config(config: &Config) -> Parser {
+        Parser {
+            log_prefix: config.log_prefix.as_ref().map(|prefix| prefix.0.clone()),
+            ..Parser::new()
+        }
+    }
+
+    /// Parses a line. Prefixes added by logging libraries (see [`prefix_regex`]) are ignored. If
+    /// the prefix has a timestamp, it's recorded as the time of the backtrace the line belongs to.
+    pub fn parse_line(&mut self, line: &str) {
+        self.check_line(line);
+    }
+
+    /// Parses a line like [`Parser::parse_line`], returning how it was interpreted. See
+    /// [`check`].
+    pub fn check_line(&mut self, line: &str) -> LineKind {
+        let line = strip_ansi(line);
+        match self.strip_prefix(&line) {
+            Some((time, line)) => self.parse_line_inner(line, time),
+            None => self.parse_line_inner(&line, None),
+        }
+    }
+
+    /// Parses a line printed at `time`, e.g., the time elapsed since the command started. A
+    /// timestamp in the prefix of the line is ignored.
+    pub fn parse_line_at(&mut self, line: &str, time: &str) {
+        self.check_line_at(line, time);
+    }
+
+    /// Parses a line like [`Parser::parse_line_at`], returning how it was interpreted.
+    pub fn check_line_at(&mut self, line: &str, time: &str) -> LineKind {
+        let line = strip_ansi(line);
+        let line = self.strip_prefix(&line).map_or(&*line, |(_, line)| line);
+        self.parse_line_inner(line, Some(time))
+    }
+
+    /// Strips the prefix added by a logging library, returning its timestamp (if any) and the rest
+    /// of the line. A prefix matching `log_prefix` is stripped first, the rest may still have a
+    /// prefix we recognize, e.g., if `log_prefix` matches the name of the service in aggregated
+    /// logs.
+    fn strip_prefix<'a>(&mut self, line: &'a str) -> Option<(Option<&'a str>, &'a str)> {
+        let custom = self
+            .log_prefix
+            .as_ref()
+            .and_then(|regex| regex.captures(line))
+            .filter(|captures| captures.get(0).unwrap().start() == 0);
+        let Some(captures) = custom else {
+            return self.strip_known_prefix(line);
+        };
+        let time = captures.name("time").map(|time| time.as_str());
+        let rest = &line[captures.get(0).unwrap().end()..];
+        match self.strip_known_prefix(rest) {
+            Some((inner_time, rest)) => Some((time.or(inner_time), rest)),
+            None => Some((time, rest)),
+        }
+    }
+
+    /// Strips a prefix matching [`prefix_regex`]. The spans in a prefix added by
+    /// `tracing-subscriber` are recorded.
+    fn strip_known_prefix<'a>(&mut self, line: &'a str) -> Option<(Option<&'a str>, &'a str)> {
+        if !may_have_prefix(line) {
+            return None;
+        }
+        let captures = self.prefix_regex.captures(line)?;
+        let end = captures.get(0).unwrap().end();
+        if captures.name("level").is_some() || captures.name("bare_level").is_some() {
+            let spans = captures.name("spans").or(captures.name("bare_spans"));
+            self.record_spans(spans.map_or("", |spans| spans.as_str()), &line[end..]);
+        }
+        let time = captures.name("time").or(captures.name("bracketed_time"));
+        Some((time.map(|time| time.as_str()), &line[end..]))
+    }
+
+    /// Records the spans of a line logged with `tracing-subscriber`, e.g., `request{id=1}:job:`, as
+    /// the active ones. With span events enabled, a span is logged as `exit` or `close` (followed
+    /// by its timings) when it's no longer active.
+    fn record_spans(&mut self, spans: &str, text: &str) {
+        self.spans.clear();
+        self.replaced.spans = true;
+        let mut in_fields = false;
+        let mut start = 0;
+        for (i, c) in spans.char_indices() {
+            match c {
+                '{' => in_fields = true,
+                '}' => in_fields = false,
+                ':' if !in_fields => {
+                    self.spans.push(spans[start..i].to_string());
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+        if text.trim_end() == "exit" || text.starts_with("close ") {
+            self.spans.pop();
+        }
+    }
+
+    /// Starts a backtrace printed at `time` in the current spans.
+    fn start_backtrace(&mut self, time: Option<&str>) {
+        self.time = time.map(str::to_string);
+        self.backtrace_test.clone_from(&self.test);
+        self.backtrace_spans.clone_from(&self.spans);
+    }
+
+    fn parse_line_inner(&mut self, line: &str, time: Option<&str>) -> LineKind {
+        let after_header = std::mem::take(&mut self.after_header);
+        let after_frame = std::mem::take(&mut self.after_frame);
+        let diagnostic = self.diagnostic.take();
+        let frames = self.builder.len();
+        if let Some(test) = parse_test_section(line) {
+            // The output of the previous test ends here
+            self.finish_panic();
+            self.test = Some(test.to_string());
+            self.replaced.test = true;
+            return LineKind::Test;
+        }
+        if is_end_of_tests(line) {
+            self.test = None;
+            self.replaced.test = true;
+        }
+        // The fields of a Kani check follow it right away, otherwise it wasn't a check
+        if self.verification == verification::State::KaniCheck
+            && verification::parse_kani_field(line).is_none()
+        {
+            self.verification = verification::State::None;
+            self.panic_info = None;
+        }
+        if let Some(pending) = &mut self.pending_error {
+            if line == "Caused by:" {
+                self.start_error_report();
+                self.in_error_report = true;
+            } else if is_error_cause_line(line) {
+                if pending.lines.len() < MAX_MESSAGE_LINES {
+                    pending.lines.push(line.to_string());
+                }
+                self.push_trailer(line);
+                return LineKind::Unrecognized;
+            }
+        }
+        if std::mem::take(&mut self.in_error_report) {
+            // The numbered causes would be taken for frames otherwise
+            if is_error_cause_line(line) {
+                if let (false, Some(panic_info)) = (line.trim().is_empty(), &mut self.panic_info) {
+                    panic_info.push_line(line.trim_end());
+                }
+                self.in_error_report = true;
+                self.push_raw_header(line);
+                return LineKind::PanicMessage;
+            }
+        }
+        if std::mem::take(&mut self.in_span_trace) && self.parse_span_trace_line(line) {
+            self.in_span_trace = true;
+            self.push_raw_header(line);
+            return LineKind::SpanTrace;
+        }
+        let parsed = self.classify(line);
+        if self.pending_error.is_some() {
+            if matches!(
+                parsed,
+                Some(ParsedLine::BacktraceStart | ParsedLine::SpanTraceStart)
+            ) {
+                self.start_error_report();
+            } else {
+                // The `Error: ` line was only logged
+                self.pending_error = None;
+            }
+        }
+        let Some(parsed) = parsed else {
+            self.push_trailer(line);
+            // A line that doesn't match any of the patterns is only relevant if it's part of a
+            // panic message.
+            if self.verification == verification::State::PropertyMessage {
+                // The description of the violated property ends the trace
+                if let Some(panic_info) = &mut self.panic_info {
+                    panic_info.message.push(line.trim().to_string());
+                }
+                self.finish_backtrace();
+                return LineKind::PanicMessage;
+            } else if let Some(panic_info) = &mut self.panic_info {
+                if self.in_panic_info {
+                    panic_info.push_line(line);
+                    self.push_raw_header(line);
+                    return LineKind::PanicMessage;
+                }
+            }
+            if self.in_miri && miri::is_detail(line) {
+                return LineKind::Report;
+            }
+            return LineKind::Unrecognized;
+        };
+        self.in_panic_info = false;
+        let kind = self.line_kind(&parsed, after_header, after_frame);
+        match parsed {
+            ParsedLine::ThreadPanic { thread, at } => {
+                // A panic ends any backtrace in progress, otherwise its frames would be paired with
+                // this panic's info.
+                self.finish_panic();
+                self.in_panic_info = true;
+                self.start_backtrace(time);
+                self.panic_info = Some(PanicInfo {
+                    thread: Some(thread),
+                    at,
+                    message: vec![],
+                    location: None,
+                });
+            }
+            ParsedLine::ApplicationPanic => {
+                self.finish_panic();
+                self.in_panic_info = true;
+                self.start_backtrace(time);
+                // The location is filled in when we find the `Location:` line
+                self.panic_info = Some(PanicInfo {
+                    thread: None,
+                    at: String::new(),
+                    message: vec![],
+                    location: None,
+                });
+            }
+            ParsedLine::ErrorReport => {
+                // See `start_error_report`
+                self.pending_error = Some(PendingError {
+                    lines: vec![line.to_string()],
+                    time: time.map(str::to_string),
+                });
+            }
+            ParsedLine::HumanPanicReport(path) => {
+                self.finish_backtrace();
+                self.panic_info = None;
+                // The backtrace is in the report, which is only read if asked to, see
+                // `human_panic::read_report`
+                let mut builder = BacktraceBuilder::new();
+                builder
+                    .panic_info(Some(PanicInfo {
+                        thread: None,
+                        at: String::new(),
+                        message: vec![line.to_string()],
+                        location: None,
+                    }))
+                    .time(time.map(str::to_string));
+                let mut backtrace = builder.build_frameless();
+                backtrace.report_file = Some(path);
+                backtrace.raw_header = vec![line.to_string()];
+                self.backtraces.push(backtrace);
+            }
+            ParsedLine::BacktraceNote(note) => {
+                self.note = Some(note.to_string());
+                // No backtrace follows the note after a panic
+                if self.builder.is_empty() {
+                    if self.panic_info.is_some() {
+                        self.raw_footer.push(note.to_string());
+                    }
+                    self.finish_panic();
+                }
+            }
+            ParsedLine::SpanTraceStart => {
+                self.in_span_trace = true;
+                self.span_trace.clear();
+                if self.panic_info.is_none() {
+                    self.start_backtrace(time);
+                }
+            }
+            ParsedLine::BacktraceStart => {
+                // The span trace or error report printed before the frames is part of the backtrace
+                let span_trace = std::mem::take(&mut self.span_trace);
+                let error_report = std::mem::take(&mut self.error_report);
+                self.finish_backtrace();
+                self.span_trace = span_trace;
+                self.error_report = error_report;
+                // Otherwise the backtrace begins at the panic line
+                match &mut self.panic_info {
+                    None => self.start_backtrace(time),
+                    // `color-backtrace` leaves an empty line before the frames
+                    Some(panic_info) => {
+                        while panic_info
+                            .message
+                            .last()
+                            .is_some_and(|l| l.trim().is_empty())
+                        {
+                            panic_info.message.pop();
+                        }
+                    }
+                }
+            }
+            ParsedLine::BacktraceHeader { function, frameno } => {
+                self.after_header = true;
+                self.after_frame = true;
+                let (function, foreign) = foreign::demangle(function);
+                self.builder.printed_frame(frameno, function, None).foreign = foreign;
+            }
+            ParsedLine::InlinedFrame(function) => {
+                // An inlined frame that doesn't follow a frame is ignored.
+                if after_frame && !self.builder.is_empty() {
+                    self.after_header = true;
+                    self.after_frame = true;
+                    let (function, foreign) = foreign::demangle(function);
+                    if let Some(frame) = self.builder.inlined_frame(function, None) {
+                        frame.foreign = foreign;
+                    }
+                }
+            }
+            // The backtrace continues after the omitted frames
+            ParsedLine::OmittedFrames => self.after_frame = after_frame,
+            ParsedLine::BacktraceSource {
+                file,
+                lineno,
+                colno,
+            } => {
+                // A source line that doesn't follow a header is ignored.
+                if let (true, Some(frame)) = (after_header, self.builder.last_frame_mut()) {
+                    self.after_frame = true;
+                    frame.foreign |= foreign::is_foreign_source(file);
+                    frame.source_info = Some(SourceInfo {
+                        file: file.to_string(),
+                        lineno,
+                        colno,
+                    });
+                }
+            }
+            ParsedLine::MiriError(message) => {
+                self.finish_panic();
+                self.in_miri = true;
+                self.start_backtrace(time);
+                // The location is filled in with the first span
+                self.panic_info = Some(PanicInfo {
+                    thread: None,
+                    at: String::new(),
+                    message: vec![message.to_string()],
+                    location: None,
+                });
+            }
+            ParsedLine::MiriFrame { function, location } if self.in_miri => {
+                // Miri doesn't number frames, the first one is the innermost
+                let source_info = location.map(|(file, lineno, colno)| SourceInfo {
+                    file: file.to_string(),
+                    lineno,
+                    colno,
+                });
+                // Otherwise the location is in the span that follows
+                self.after_header = source_info.is_none();
+                self.builder.frame(function, source_info);
+            }
+            ParsedLine::Span {
+                file,
+                lineno,
+                colno,
+            } if self.in_miri => {
+                let location = SourceInfo {
+                    file: file.to_string(),
+                    lineno,
+                    colno,
+                };
+                if let (true, Some(frame)) = (after_header, self.builder.last_frame_mut()) {
+                    frame.source_info = Some(location);
+                } else if let Some(panic_info) = &mut self.panic_info {
+                    if panic_info.at.is_empty() {
+                        panic_info.at = location.to_string();
+                    }
+                }
+            }
+            ParsedLine::ErrorsEnd if self.in_miri => self.finish_backtrace(),
+            ParsedLine::Diagnostic { level, message } => {
+                self.diagnostic = Some((level.to_string(), message.to_string()));
+            }
+            ParsedLine::Span {
+                file,
+                lineno,
+                colno,
+            } => {
+                // The location of a diagnostic is in the line right after it
+                if let Some((level, message)) = diagnostic {
+                    if self.diagnostics.len() == MAX_DIAGNOSTICS {
+                        self.diagnostics.pop_front();
+                    }
+                    self.diagnostics.push_back(Diagnostic {
+                        level,
+                        message,
+                        location: SourceInfo {
+                            file: file.to_string(),
+                            lineno,
+                            colno,
+                        },
+                    });
+                }
+            }
+            ParsedLine::MiriFrame { .. } | ParsedLine::ErrorsEnd => {}
+            ParsedLine::KaniCheck => {
+                self.finish_panic();
+                self.verification = verification::State::KaniCheck;
+                self.start_backtrace(time);
+            }
+            ParsedLine::KaniField { field, value } => match field {
+                // Only failed checks are reported. The message and location are filled in with the
+                // fields that follow.
+                "Status" if value == "FAILURE" => {
+                    self.panic_info = Some(PanicInfo {
+                        thread: None,
+                        at: String::new(),
+                        message: vec![],
+                        location: None,
+                    });
+                }
+                "Status" => self.verification = verification::State::None,
+                "Description" => {
+                    if let Some(panic_info) = &mut self.panic_info {
+                        let description = value.strip_prefix('"').unwrap_or(value);
+                        let description = description.strip_suffix('"').unwrap_or(description);
+                        panic_info.message.push(description.to_string());
+                    }
+                }
+                "Location" if self.panic_info.is_some() => {
+                    if let Some((function, (file, lineno, colno))) =
+                        verification::parse_kani_location(value)
+                    {
+                        let source_info = SourceInfo {
+                            file: file.to_string(),
+                            lineno,
+                            colno,
+                        };
+                        if let Some(panic_info) = &mut self.panic_info {
+                            panic_info.at = source_info.to_string();
+                        }
+                        self.builder.frame(function, Some(source_info));
+                    }
+                    // The location is the last field
+                    self.finish_backtrace();
+                }
+                _ => {}
+            },
+            ParsedLine::TraceStart => {
+                self.finish_panic();
+                // Steps are in the order they were executed, so the last one is the innermost
+                self.builder = BacktraceBuilder::outermost_first();
+                self.verification = verification::State::Trace;
+                self.start_backtrace(time);
+                // The message and location are filled in with the violated property
+                self.panic_info = Some(PanicInfo {
+                    thread: None,
+                    at: String::new(),
+                    message: vec![],
+                    location: None,
+                });
+            }
+            ParsedLine::TraceStep(step) => {
+                let source_info = SourceInfo {
+                    file: step.file.to_string(),
+                    lineno: step.lineno,
+                    colno: None,
+                };
+                if self.verification == verification::State::Property {
+                    self.verification = verification::State::PropertyMessage;
+                    if let Some(panic_info) = &mut self.panic_info {
+                        panic_info.at = source_info.to_string();
+                    }
+                }
+                // There's a step for every assignment, so a line is usually repeated
+                let repeated = self.builder.last_frame().is_some_and(|frame| {
+                    frame.function == step.function
+                        && frame.source_info.as_ref().is_some_and(|last| {
+                            last.file == source_info.file && last.lineno == source_info.lineno
+                        })
+                });
+                if !repeated {
+                    self.builder.frame(step.function, Some(source_info));
+                }
+            }
+            ParsedLine::ViolatedProperty => self.verification = verification::State::Property,
+        }
+        if self.after_frame || self.builder.len() > frames {
+            // The lines so far were part of the backtrace after all
+            self.trailer.clear();
+        } else if self.note.as_deref() != Some(line) {
+            self.push_trailer(line);
+        }
+        self.push_raw(line, kind);
+        kind
+    }
+
+    /// Keeps a line printed before the frames of the backtrace in progress, at most
+    /// [`MAX_MESSAGE_LINES`] like the panic message, see [`Backtrace::raw_header`].
+    fn push_raw_header(&mut self, line: &str) {
+        if self.raw_header.len() < MAX_MESSAGE_LINES {
+            self.raw_header.push(line.to_string());
+        }
+    }
+
+    /// Keeps a line of the backtrace in progress as it was parsed, see [`Backtrace::raw_text`].
+    fn push_raw(&mut self, line: &str, kind: LineKind) {
+        match kind {
+            LineKind::Panic => {
+                self.raw_header = vec![line.to_string()];
+                self.raw_footer.clear();
+            }
+            LineKind::BacktraceStart => {
+                // Otherwise the backtrace begins here, e.g., for an error reported by `anyhow`
+                if self.panic_info.is_none() {
+                    self.raw_header.clear();
+                }
+                self.push_raw_header(line);
+            }
+            LineKind::Frame | LineKind::Source => {
+                if let Some(frame) = self.builder.last_frame_mut() {
+                    frame.raw.push(line.to_string());
+                }
+            }
+            LineKind::SpanTrace => self.push_raw_header(line),
+            LineKind::Note
+                if !self.builder.is_empty() && self.raw_footer.len() < MAX_MESSAGE_LINES =>
+            {
+                self.raw_footer.push(line.to_string())
+            }
+            _ => {}
+        }
+    }
+
+    /// How a classified line is interpreted given the lines before it.
+    fn line_kind(&self, parsed: &ParsedLine, after_header: bool, after_frame: bool) -> LineKind {
+        match parsed {
+            ParsedLine::ThreadPanic { .. }
+            | ParsedLine::ApplicationPanic
+            | ParsedLine::HumanPanicReport(_)
+            | ParsedLine::MiriError(_)
+            | ParsedLine::KaniCheck
+            | ParsedLine::TraceStart => LineKind::Panic,
+            ParsedLine::BacktraceNote(_) => LineKind::Note,
+            ParsedLine::SpanTraceStart => LineKind::SpanTrace,
+            ParsedLine::BacktraceStart => LineKind::BacktraceStart,
+            ParsedLine::BacktraceHeader { .. }
+            | ParsedLine::OmittedFrames
+            | ParsedLine::TraceStep(_) => LineKind::Frame,
+            ParsedLine::InlinedFrame(_) if after_frame => LineKind::Frame,
+            ParsedLine::MiriFrame { .. } if self.in_miri => LineKind::Frame,
+            ParsedLine::BacktraceSource { .. } if after_header => LineKind::Source,
+            ParsedLine::Span { .. } if self.in_miri => LineKind::Source,
+            ParsedLine::Diagnostic { .. } | ParsedLine::Span { .. } => LineKind::Diagnostic,
+            ParsedLine::KaniField { .. } | ParsedLine::ViolatedProperty => LineKind::Report,
+            ParsedLine::ErrorsEnd if self.in_miri => LineKind::Report,
+            ParsedLine::InlinedFrame(_)
+            | ParsedLine::MiriFrame { .. }
+            | ParsedLine::BacktraceSource { .. }
+            | ParsedLine::ErrorsEnd => LineKind::Ignored,
+            // Until the rest of the report follows it
+            ParsedLine::ErrorReport => LineKind::Unrecognized,
+        }
+    }
+
+    /// Keeps a line after the frames of the backtrace in progress in case it's the last one, see
+    /// [`Backtrace::trailer`].
+    fn push_trailer(&mut self, line: &str) {
+        if self.builder.is_empty() {
+            return;
+        }
+        if self.trailer.len() == MAX_TRAILER_LINES {
+            self.trailer.pop_front();
+        }
+        self.trailer.push_back(line.to_string());
+    }
+
+    /// Classifies a line returning `None` if it doesn't match any of the backtrace patterns. This
+    /// is the hot path when parsing long logs, so frame headers and source lines are parsed by hand
+    /// instead of using regexes.
+    fn classify<'a>(&self, line: &'a str) -> Option<ParsedLine<'a>> {
+        if line.eq_ignore_ascii_case("stack backtrace:") || is_color_backtrace_start(line) {
+            return Some(ParsedLine::BacktraceStart);
+        }
+        if span_trace::is_start(line) {
+            return Some(ParsedLine::SpanTraceStart);
+        }
+        if line == "The application panicked (crashed)." {
+            return Some(ParsedLine::ApplicationPanic);
+        }
+        // Part of a panic message otherwise, e.g., the `Debug` output of an error
+        if line.starts_with("Error: ") && !self.in_panic_info {
+            return Some(ParsedLine::ErrorReport);
+        }
+        if let Some(rest) = line.strip_prefix("We have generated a report file at \"") {
+            let (path, _) = rest.split_once('"')?;
+            return Some(ParsedLine::HumanPanicReport(PathBuf::from(path)));
+        }
+        if line.starts_with("note: ") && line.contains("`RUST_BACKTRACE=") {
+            return Some(ParsedLine::BacktraceNote(line));
+        }
+        if line.starts_with("error") || line.starts_with("warning") {
+            if miri::is_end(line) {
+                return Some(ParsedLine::ErrorsEnd);
+            }
+            if let Some(message) = miri::parse_error(line) {
+                return Some(ParsedLine::MiriError(message));
+            }
+            // Otherwise it's likely a line of the panic message
+            if self.in_panic_info {
+                return None;
+            }
+            let (level, message) = parse_diagnostic(line)?;
+            return Some(ParsedLine::Diagnostic { level, message });
+        }
+        if line.starts_with("thread") {
+            let captures = self.panic_regex.captures(line)?;
+            let thread = captures.name("thread").unwrap().as_str().to_string();
+            let at = captures.name("at").unwrap().as_str().to_string();
+            return Some(ParsedLine::ThreadPanic { thread, at });
+        }
+        match self.verification {
+            verification::State::KaniCheck => {
+                if let Some((field, value)) = verification::parse_kani_field(line) {
+                    return Some(ParsedLine::KaniField { field, value });
+                }
+            }
+            verification::State::Trace => {
+                if line == "Violated property:" {
+                    return Some(ParsedLine::ViolatedProperty);
+                }
+                if let Some(step) = verification::parse_step(line) {
+                    return Some(ParsedLine::TraceStep(step));
+                }
+            }
+            verification::State::Property => {
+                return verification::parse_step(line).map(ParsedLine::TraceStep);
+            }
+            verification::State::None | verification::State::PropertyMessage => {}
+        }
+        if verification::is_kani_check(line) {
+            return Some(ParsedLine::KaniCheck);
+        }
+        if verification::is_trace_start(line) {
+            return Some(ParsedLine::TraceStart);
+        }
+        if let Some((function, location)) = miri::parse_frame(line) {
+            return Some(ParsedLine::MiriFrame { function, location });
+        }
+        // Both frame headers and source lines are indented
+        let trimmed = line.trim_start();
+        if trimmed.len() == line.len() {
+            return None;
+        }
+        if let Some((frameno, function)) = parse_frame_header(trimmed) {
+            Some(ParsedLine::BacktraceHeader { function, frameno })
+        } else if trimmed.starts_with("[... omitted ")
+            || (trimmed.starts_with('⋮') && trimmed.ends_with(" hidden ⋮"))
+        {
+            Some(ParsedLine::OmittedFrames)
+        } else if let Some(function) = trimmed.strip_prefix("- ") {
+            Some(ParsedLine::InlinedFrame(function.trim()))
+        } else if let Some((file, lineno, colno)) = miri::parse_span(trimmed) {
+            Some(ParsedLine::Span {
+                file,
+                lineno,
+                colno,
+            })
+        } else {
+            let (file, lineno, colno) = parse_source_info(trimmed)?;
+            Some(ParsedLine::BacktraceSource {
+                file,
+                lineno,
+                colno,
+            })
+        }
+    }
+
+    /// Finishes the backtrace of the panic in progress, which has no more frames to come. If no
+    /// backtrace was printed for the panic, e.g., because `RUST_BACKTRACE` wasn't set, a single
+    /// frame is synthesized from the location of the panic so it's still rendered with a snippet.
+    /// Starts the backtrace of an error report with the lines held in [`Parser::pending_error`],
+    /// once the rest of the report follows them.
+    fn start_error_report(&mut self) {
+        let Some(pending) = self.pending_error.take() else {
+            return;
+        };
+        self.finish_panic();
+        self.error_report = true;
+        self.start_backtrace(pending.time.as_deref());
+        let mut lines = pending.lines.into_iter();
+        let first = lines.next().unwrap_or_default();
+        // There's no location, the backtrace says where the error was created
+        let mut panic_info = PanicInfo {
+            thread: None,
+            at: String::new(),
+            message: vec![first.clone()],
+            location: None,
+        };
+        self.raw_header = vec![first];
+        for line in lines {
+            if !line.trim().is_empty() {
+                panic_info.push_line(line.trim_end());
+            }
+            self.push_raw_header(&line);
+        }
+        self.panic_info = Some(panic_info);
+    }
+
+    fn finish_panic(&mut self) {
+        if let (true, Some(panic_info)) = (self.builder.is_empty(), &mut self.panic_info) {
+            panic_info.locate();
+            if let Some(location) = &panic_info.location {
+                self.builder.frame(PANIC_LOCATION, Some(location.clone()));
+            }
+        }
+        self.finish_backtrace();
+    }
+
+    /// Parses a line after a [`ParsedLine::SpanTraceStart`], returning whether it's part of the span
+    /// trace, i.e., an empty line, a span, or the location of the previous span.
+    fn parse_span_trace_line(&mut self, line: &str) -> bool {
+        if line.trim().is_empty() {
+            return true;
+        }
+        if let Some(span) = span_trace::parse_span(line) {
+            let location = span.location.map(|(file, lineno, colno)| SourceInfo {
+                file: file.to_string(),
+                lineno,
+                colno,
+            });
+            self.span_trace.push(SpanTraceEntry {
+                name: span.name.to_string(),
+                fields: span.fields.map(str::to_string),
+                location,
+            });
+            return true;
+        }
+        if let Some((file, lineno, colno)) = span_trace::parse_span_location(line) {
+            if let Some(span) = self.span_trace.last_mut() {
+                span.location = Some(SourceInfo {
+                    file: file.to_string(),
+                    lineno,
+                    colno,
+                });
+                return true;
+            }
+        }
+        false
+    }
+
+    fn finish_backtrace(&mut self) {
+        self.in_miri = false;
+        self.in_error_report = false;
+        self.in_span_trace = false;
+        // A check or trace without a location has nothing to show, and its panic info would be
+        // paired with the next backtrace otherwise
+        if self.verification != verification::State::None && self.builder.is_empty() {
+            self.panic_info = None;
+        }
+        self.verification = verification::State::None;
+        let error_report = std::mem::take(&mut self.error_report);
+        // A note without frames, e.g., after a panic line we didn't recognize, is dropped
+        let note = self.note.take();
+        let span_trace = std::mem::take(&mut self.span_trace);
+        let raw_footer = std::mem::take(&mut self.raw_footer);
+        self.trailer.clear();
+        // The builder is reset even if it's empty, e.g., a trace may end before its first step
+        let mut builder = std::mem::take(&mut self.builder);
+        // An error report or a span trace is worth showing even if no backtrace was captured
+        let report = error_report && self.panic_info.is_some() || !span_trace.is_empty();
+        if !builder.is_empty() || report {
+            builder
+                .panic_info(self.panic_info.take())
+                .time(self.time.take());
+            let mut backtrace = builder.build_frameless();
+            backtrace.diagnostics = std::mem::take(&mut self.diagnostics).into();
+            self.replaced.diagnostics = true;
+            backtrace.note = note;
+            backtrace.spans = std::mem::take(&mut self.backtrace_spans);
+            backtrace.test = self.backtrace_test.take();
+            backtrace.span_trace = span_trace;
+            backtrace.raw_header = std::mem::take(&mut self.raw_header);
+            backtrace.raw_footer = raw_footer;
+            self.backtraces.push(backtrace);
+        }
+    }
+
+    /// Parses every line in a buffer. Lines are borrowed from the buffer unless they are not valid
+    /// UTF-8, in which case they are decoded lossily.
+    pub fn parse_bytes(&mut self, bytes: &[u8]) {
+        if bytes.is_empty() {
+            return;
+        }
+        let bytes = bytes.strip_suffix(b"\n").unwrap_or(bytes);
+        for line in bytes.split(|b| *b == b'\n') {
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            self.parse_line(&String::from_utf8_lossy(line));
+        }
+    }
+
+    /// Takes the backtraces that are complete so far. The backtrace in progress, if any, is not
+    /// included since more frames may follow.
+    pub fn take_backtraces(&mut self) -> Vec<Backtrace> {
+        std::mem::take(&mut self.backtraces)
+    }
+
+    /// Takes an `Error: ` line at the end of the output for an error report, even if nothing after
+    /// it shows it's one. An error returned from `main` is printed like this when no backtrace was
+    /// captured, so this is meant for when the program exits with an error.
+    pub fn keep_error_report(&mut self) {
+        self.start_error_report();
+    }
+
+    /// Finishes parsing, returning the backtraces that weren't taken yet. The lines after the
+    /// frames of the last backtrace become its [`Backtrace::trailer`].
+    pub fn into_backtraces(mut self) -> Vec<Backtrace> {
+        let mut trailer = Vec::from(std::mem::take(&mut self.trailer));
+        while trailer.last().is_some_and(|line| line.trim().is_empty()) {
+            trailer.pop();
+        }
+        let leading = trailer
+            .iter()
+            .take_while(|line| line.trim().is_empty())
+            .count();
+        trailer.drain(..leading);
+        let finished = self.backtraces.len();
+        self.finish_panic();
+        if let (true, Some(last)) = (self.backtraces.len() > finished, self.backtraces.last_mut()) {
+            last.trailer = trailer;
+        }
+        self.backtraces
+    }
+}
+
+/// The regex matching the prefixes that logging libraries add to lines, i.e., anything before the
+/// text of the line that may contain a timestamp. Supported prefixes are
+///
+/// * a timestamp, either an ISO 8601 date and time or the time elapsed since the command started as
+///   printed when echoing with timestamps, optionally in brackets, e.g., `[00:02:13.4] `.
+/// * `env_logger`'s default format, e.g., `[2024-06-01T12:00:00Z ERROR my_app] `.
+/// * `tracing-subscriber`'s default format, with or without the timestamp, e.g.,
+///   `2024-06-01T12:00:00.123456Z ERROR request{id=1}: my_app::api: `.
+fn prefix_regex() -> Regex {
+    const TIMESTAMP: &str =
+        r"\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}:\d{2}(?:[.,]\d+)?(?:Z|[+-]\d{2}:?\d{2})?";
+    const ELAPSED: &str = r"\d{2,}:\d{2}:\d{2}(?:\.\d+)?";
+    const LEVEL: &str = r"(?:TRACE|DEBUG|INFO|WARN|ERROR)";
+    // The spans (if any) followed by the target
+    let target =
+        |spans: &str| format!(r"(?:(?P<{spans}>(?:\w+(?:\{{[^}}]*\}})?:)+)\s)?[\w:]*\w:\s");
+    let env_logger = format!(r"\[(?:(?P<bracketed_time>{TIMESTAMP})\s+)?{LEVEL}\s+[^\]\s]+\]\s");
+    let timestamp = format!(
+        r"\[?(?P<time>{TIMESTAMP}|{ELAPSED})\]?\s(?:\s*(?P<level>{LEVEL})\s+{})?",
+        target("spans")
+    );
+    let tracing = format!(r"\s*(?P<bare_level>{LEVEL})\s+{}", target("bare_spans"));
+    Regex::new(&format!("^(?:{env_logger}|{timestamp}|{tracing})")).unwrap()
+}
+
+/// Strips the ANSI escape codes from a line that starts with one, since logging libraries may color
+/// the prefix.
+fn strip_ansi(line: &str) -> Cow<'_, str> {
+    if line.starts_with('\x1b') {
+        Cow::Owned(anstream::adapter::strip_str(line).to_string())
+    } else {
+        Cow::Borrowed(line)
+    }
+}
+
+/// Whether a line is part of the causes of an error after a [`ParsedLine::ErrorReport`]: empty
+/// lines, the headings of the sections before the backtrace, and the indented causes.
+fn is_error_cause_line(line: &str) -> bool {
+    line.trim().is_empty()
+        // Except for the headers of the sections printed by `color-eyre`, e.g., `━━━ SPANTRACE ━━━`
+        || (line.starts_with(char::is_whitespace) && !line.trim_start().starts_with('━'))
+        || line == "Caused by:"
+        || line == "Location:"
+}
+
+/// Parses the header `cargo test` prints before the captured output of a failed test, e.g.,
+/// `---- tests::parse stdout ----`, returning the name of the test.
+fn parse_test_section(line: &str) -> Option<&str> {
+    let rest = line.strip_prefix("---- ")?;
+    rest.strip_suffix(" stdout ----")
+        .or_else(|| rest.strip_suffix(" stderr ----"))
+}
+
+/// Whether the line ends the captured output of the failed tests, i.e., the list of failures after
+/// it, the result of the tests, or the start of the tests in another binary.
+fn is_end_of_tests(line: &str) -> bool {
+    line == "failures:"
+        || line.starts_with("test result: ")
+        || (line.starts_with("running ") && (line.ends_with(" tests") || line.ends_with(" test")))
+}
+
+/// A quick check to avoid running [`prefix_regex`] on most lines: prefixes start with a timestamp
+/// or a level, possibly after a bracket or spaces.
+fn may_have_prefix(line: &str) -> bool {
+    let rest = line.strip_prefix('[').unwrap_or(line);
+    let bytes = rest.as_bytes();
+    let is_digit = |i: usize| bytes.get(i).is_some_and(u8::is_ascii_digit);
+    // `2024-` or `00:00`, but not the frame number in `12: foo`
+    if (0..4).all(is_digit) && bytes.get(4) == Some(&b'-')
+        || (0..2).all(is_digit) && bytes.get(2) == Some(&b':') && is_digit(3)
+    {
+        return true;
+    }
+    let rest = rest.trim_start_matches(' ');
+    ["TRACE", "DEBUG", "INFO", "WARN", "ERROR"]
+        .iter()
+        .any(|level| rest.starts_with(level))
+}
+
+/// Parses the header of a frame (without the indentation), e.g.,
+/// ```ignore
+/// 28: rustc_middle::ty::context::tls::enter_context
+/// ```
+/// The function name may be preceded by an address, e.g., `28: 0x7f4b2c1b1e4d - main`.
+fn parse_frame_header(s: &str) -> Option<(u32, &str)> {
+    let digits = s.find(|c: char| !c.is_ascii_digit())?;
+    let frameno = s[..digits].parse().ok()?;
+    let rest = s[digits..].strip_prefix(':')?;
+    let function = strip_whitespace(rest)?;
+    let function = strip_address(function)
+        .filter(|f| !f.is_empty())
+        .unwrap_or(function);
+    if function.is_empty() {
+        return None;
+    }
+    Some((frameno, function))
+}
+
+/// Strips a `<address> - ` prefix.
+fn strip_address(s: &str) -> Option<&str> {
+    let word = s.find(|c: char| !(c.is_alphanumeric() || c == '_'))?;
+    if word == 0 {
+        return None;
+    }
+    let rest = strip_whitespace(&s[word..])?.strip_prefix('-')?;
+    strip_whitespace(rest)
+}
+
+/// Parses the line with the location of a frame (without the indentation), e.g.,
+/// ```ignore
+/// at /rustc/b3aa8e7168a3d940122db3561289ffbf3f587262/compiler/rustc_middle/src/ty/context/tls.rs:79:9
+/// ```
+fn parse_source_info(s: &str) -> Option<(&str, usize, Option<usize>)> {
+    parse_location(strip_whitespace(s.strip_prefix("at")?)?)
+}
+
+/// Parses a location like `src/main.rs:10:5`, ignoring anything after the column.
+pub(crate) fn parse_location(s: &str) -> Option<(&str, usize, Option<usize>)> {
+    // Absolute paths on Windows start with a drive letter, e.g., `C:\Users\...`
+    let drive = match s.as_bytes() {
+        [letter, b':', b'\\' | b'/', ..] if letter.is_ascii_alphabetic() => 2,
+        _ => 0,
+    };
+    let (file, rest) = s[drive..].split_once(':')?;
+    let file = &s[..drive + file.len()];
+    if file.is_empty() {
+        return None;
+    }
+    let (lineno, rest) = split_number(rest)?;
+    let colno = match rest.strip_prefix(':') {
+        Some(rest) => Some(split_number(rest)?.0),
+        None => None,
+    };
+    Some((file, lineno, colno))
+}
+
+/// Parses the first line of a diagnostic, e.g., `warning: unused variable: `x`` or
+/// `error[E0308]: mismatched types`, returning the level (with the code) and the message.
+fn parse_diagnostic(line: &str) -> Option<(&str, &str)> {
+    let (level, message) = line.split_once(": ")?;
+    let name = level.split_once('[').map_or(level, |(name, _)| name);
+    matches!(name, "error" | "warning").then_some((level, message))
+}
+
+/// Whether the line is the header printed by `color-backtrace` before the frames, e.g.,
+/// ```ignore
+///   ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━ BACKTRACE ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
+/// ```
+fn is_color_backtrace_start(line: &str) -> bool {
+    let trimmed = line.trim();
+    trimmed.starts_with('━') && trimmed.trim_matches('━').trim() == "BACKTRACE"
+}
+
+/// Splits a leading decimal number from the rest of the string.
+fn split_number(s: &str) -> Option<(usize, &str)> {
+    let digits = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    Some((s[..digits].parse().ok()?, &s[digits..]))
+}
+
+/// Strips leading whitespace returning `None` if there was none.
+fn strip_whitespace(s: &str) -> Option<&str> {
+    let trimmed = s.trim_start();
+    (trimmed.len() < s.len()).then_some(trimmed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sort_backtraces, Parser};
+    use crate::{config::Sort, Backtrace, BacktraceBuilder, PanicInfo, SourceInfo};
+
+    fn parse(log: &str) -> Vec<Backtrace> {
+        let mut parser = Parser::new();
+        for line in log.lines() {
+            parser.parse_line(line);
+        }
+        parser.into_backtraces()
+    }
+
+    /// A backtrace panicking with `message` at `time`, with `user_frames` frames of the program.
+    fn backtrace(message: &str, time: Option<&str>, user_frames: usize) -> Backtrace {
+        let mut builder = BacktraceBuilder::new();
+        for i in 0..user_frames {
+            let source_info = SourceInfo {
+                file: "./src/main.rs".into(),
+                lineno: i + 1,
+                colno: None,
+            };
+            builder.frame(format!("app::{message}{i}"), Some(source_info));
+        }
+        builder.frame("std::rt::lang_start", None);
+        let mut backtrace = builder.build().unwrap();
+        backtrace.time = time.map(String::from);
+        backtrace.panic_info = Some(PanicInfo {
+            thread: None,
+            at: "src/main.rs:1:1".into(),
+            message: vec![message.into()],
+            location: None,
+        });
+        backtrace
+    }
+
+    fn sorted(order: Sort) -> Vec<String> {
+        let mut backtraces = [
+            backtrace("b", Some("12:00:02"), 1),
+            backtrace("c", None, 3),
+            backtrace("a", Some("12:00:01"), 2),
+            backtrace("b", Some("12:00:03"), 1),
+        ];
+        sort_backtraces(&mut backtraces, order);
+        backtraces
+            .iter()
+            .map(|backtrace| backtrace.panic_info.as_ref().unwrap().message[0].clone())
+            .collect()
+    }
+
+    #[test]
+    fn sort() {
+        assert_eq!(sorted(Sort::Capture), ["b", "c", "a", "b"]);
+        // Backtraces without a time go last
+        assert_eq!(sorted(Sort::Time), ["a", "b", "b", "c"]);
+        assert_eq!(sorted(Sort::Message), ["a", "b", "b", "c"]);
+        assert_eq!(sorted(Sort::UserFrames), ["c", "a", "b", "b"]);
+        // Backtraces with the same fingerprint are next to each other, in the order they were
+        // captured
+        let fingerprint = sorted(Sort::Fingerprint);
+        let b = fingerprint.iter().position(|m| m == "b").unwrap();
+        assert_eq!(fingerprint[b + 1], "b");
+    }
+
+    /// An `Error: ` line is a report once `Caused by:` or a backtrace follows it, and only a
+    /// logged line otherwise.
+    #[test]
+    fn error_reports() {
+        let backtraces =
+            parse("Error: failed to load\n\nCaused by:\n    No such file or directory");
+        assert_eq!(backtraces.len(), 1);
+        let message = &backtraces[0].panic_info.as_ref().unwrap().message;
+        assert_eq!(
+            message,
+            &[
+                "Error: failed to load",
+                "Caused by:",
+                "    No such file or directory"
+            ]
+        );
+
+        let backtraces = parse(
+            "Error: failed to load\n\n\
+             Stack backtrace:\n   \
+             0: app::main\n             \
+             at ./src/main.rs:4:5",
+        );
+        assert_eq!(backtraces.len(), 1);
+        assert_eq!(backtraces[0].frames.len(), 1);
+
+        assert!(parse("Error: retrying connection\n    attempt 2 of 5").is_empty());
+        // Like any line logged after a backtrace
+        let backtraces = parse(
+            "thread 'main' panicked at src/main.rs:4:5:\n\
+             boom\n\
+             stack backtrace:\n   \
+             0: app::main\n             \
+             at ./src/main.rs:4:5\n\
+             Error: retrying connection\n\
+             INFO connected",
+        );
+        assert_eq!(backtraces.len(), 1);
+        assert_eq!(
+            backtraces[0].trailer,
+            ["Error: retrying connection", "INFO connected"]
+        );
+    }
+
+    /// An error returned from `main` may be printed as a single `Error: ` line, which is a report
+    /// when the program exits with an error.
+    #[test]
+    fn keep_error_report() {
+        let mut parser = Parser::new();
+        parser.parse_line("Error: failed to load");
+        parser.keep_error_report();
+        let backtraces = parser.into_backtraces();
+        assert_eq!(backtraces.len(), 1);
+        let message = &backtraces[0].panic_info.as_ref().unwrap().message;
+        assert_eq!(message, &["Error: failed to load"]);
+
+        // Only the last line can be the report
+        let mut parser = Parser::new();
+        parser.parse_line("Error: failed to load");
+        parser.parse_line("exiting");
+        parser.keep_error_report();
+        assert!(parser.into_backtraces().is_empty());
+    }
+}
@@ -0,0 +1,62 @@
+//! Checking how the parser interprets the lines of a log, see `--check-parser`. Most lines in a log
+//! are unrelated to backtraces, so a line that isn't recognized is only reported as a failure if
+//! - it's in the middle of a backtrace, i.e., more frames of the same backtrace follow it, or
+//! - it looks like a panic line, i.e., it contains `panicked at`.
+
+use super::{LineKind, Parser};
+
+pub struct Report {
+    /// The kind of every line in the log
+    pub kinds: Vec<LineKind>,
+    /// The indices of the lines the parser failed to recognize
+    pub failures: Vec<usize>,
+    /// The number of backtraces found
+    pub backtraces: usize,
+}
+
+/// Parses `lines` with `parser` (e.g., built with [`Parser::from_config`] to strip `log_prefix`)
+/// recording how each one was interpreted.
+pub fn check<'a>(mut parser: Parser, lines: impl IntoIterator<Item = &'a str>) -> Report {
+    let mut kinds = vec![];
+    let mut failures = vec![];
+    // The lines not recognized since the last frame of the backtrace in progress
+    let mut pending = vec![];
+    let mut in_backtrace = false;
+    for (i, line) in lines.into_iter().enumerate() {
+        let kind = parser.check_line(line);
+        match kind {
+            LineKind::Panic | LineKind::Test => {
+                pending.clear();
+                in_backtrace = false;
+            }
+            LineKind::BacktraceStart => {
+                pending.clear();
+                in_backtrace = true;
+            }
+            LineKind::Frame | LineKind::Source => {
+                // The backtrace continues, so the lines in between were part of it
+                failures.append(&mut pending);
+                in_backtrace = true;
+            }
+            LineKind::Unrecognized | LineKind::Ignored => {
+                if line.contains("panicked at") {
+                    failures.push(i);
+                } else if in_backtrace {
+                    pending.push(i);
+                }
+            }
+            LineKind::PanicMessage
+            | LineKind::Note
+            | LineKind::Diagnostic
+            | LineKind::Report
+            | LineKind::SpanTrace => {}
+        }
+        kinds.push(kind);
+    }
+    failures.sort_unstable();
+    Report {
+        kinds,
+        failures,
+        backtraces: parser.into_backtraces().len(),
+    }
+}
@@ -0,0 +1,82 @@
+//! Parsing the errors reported by [Miri](https://github.com/rust-lang/miri), e.g.,
+//! ```text
+//! error: Undefined Behavior: memory access failed: alloc1234 has been freed
+//!  --> src/main.rs:4:20
+//!   |
+//! 4 |     let _x = unsafe { *ptr };
+//!   |                       ^^^^ Undefined Behavior occurred here
+//!   |
+//!   = note: BACKTRACE:
+//!   = note: inside `read` at src/main.rs:4:20: 4:24
+//! note: inside `main`
+//!  --> src/main.rs:9:5
+//!   |
+//! 9 |     read(ptr);
+//!   |     ^^^^^^^^^
+//! ```
+//! The error becomes the panic message and each `inside` note becomes a frame, innermost first.
+//! The location of a frame is either in the note or in the span that follows it.
+
+use super::{parse_location, strip_whitespace};
+
+/// The kinds of errors Miri reports for a program, as opposed to errors compiling it.
+const ERROR_KINDS: &[&str] = &[
+    "Undefined Behavior",
+    "unsupported operation",
+    "abnormal termination",
+    "memory leaked",
+    "resource exhaustion",
+    "deadlock",
+];
+
+type Location<'a> = (&'a str, usize, Option<usize>);
+
+/// Parses the first line of an error, returning the message including the kind of error.
+pub(crate) fn parse_error(line: &str) -> Option<&str> {
+    let message = line.strip_prefix("error: ")?;
+    let (kind, _) = message.split_once(':')?;
+    ERROR_KINDS.contains(&kind).then_some(message)
+}
+
+/// Whether the line ends the errors, e.g., `error: aborting due to 1 previous error`.
+pub(crate) fn is_end(line: &str) -> bool {
+    line.starts_with("error: aborting due to")
+}
+
+/// Whether the line is part of the details of an error, e.g., the source code under a span, a
+/// ``= help: `` note, or a ``help: `` line followed by the span where memory was allocated.
+pub(crate) fn is_detail(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    // The line numbers of the source code are in the gutter, e.g., `4 |     let _x = unsafe { *ptr };`
+    let gutter = trimmed.trim_start_matches(|c: char| c.is_ascii_digit());
+    gutter.trim_start().starts_with('|')
+        || trimmed.starts_with("= ")
+        || line.starts_with("help: ")
+        || line.starts_with("note: ")
+}
+
+/// Parses a note with a frame, e.g., ``= note: inside `read` at src/main.rs:4:20: 4:24`` or
+/// ``note: inside `main` ``.
+pub(crate) fn parse_frame(line: &str) -> Option<(&str, Option<Location<'_>>)> {
+    let line = line.trim_start();
+    let line = line.strip_prefix("= ").unwrap_or(line);
+    let rest = line.strip_prefix("note: inside ")?;
+    let (function, rest) = if let Some(rest) = rest.strip_prefix('`') {
+        let end = rest.rfind('`')?;
+        (&rest[..end], &rest[end + 1..])
+    } else {
+        // Closures are reported as ``inside closure`` without a name
+        ("{{closure}}", rest.strip_prefix("closure")?)
+    };
+    let location = rest
+        .strip_prefix(" at")
+        .and_then(strip_whitespace)
+        .and_then(parse_location);
+    Some((function, location))
+}
+
+/// Parses a span, e.g., ` --> src/main.rs:9:5`.
+pub(crate) fn parse_span(line: &str) -> Option<Location<'_>> {
+    let rest = line.trim_start().strip_prefix("-->")?;
+    parse_location(strip_whitespace(rest)?)
+}
@@ -0,0 +1,53 @@
+//! Reading the events reported by `cargo nextest run --message-format libtest-json`. Events are
+//! JSON objects, one per line. The event for a failed test carries the output captured while
+//! running it, e.g.,
+//! ```json
+//! {"type": "test", "event": "failed", "name": "app::bin/app$tests::parse", "stdout": "thread 'tests::parse' panicked at ..."}
+//! ```
+//! Backtraces are parsed from the output of each test separately, so they are attributed to the
+//! right test even when tests run in parallel and their output would be interleaved in a log.
+
+use serde::Deserialize;
+
+use super::Parser;
+use crate::Backtrace;
+
+#[derive(Deserialize)]
+pub struct Event {
+    #[serde(rename = "type")]
+    kind: String,
+    event: String,
+    name: Option<String>,
+    stdout: Option<String>,
+}
+
+impl Event {
+    /// Parses a line of the output, returning `None` if it isn't an event.
+    pub fn parse(line: &str) -> Option<Event> {
+        serde_json::from_str(line).ok()
+    }
+
+    /// The output captured while running the test, if the event reports a failed test.
+    pub fn failure_output(&self) -> Option<&str> {
+        if self.kind != "test" || self.event != "failed" {
+            return None;
+        }
+        self.stdout.as_deref()
+    }
+
+    /// The backtraces in the output of a failed test, parsed with `parser` and attributed to the
+    /// test.
+    pub fn backtraces(&self, mut parser: Parser) -> Vec<Backtrace> {
+        let Some(output) = self.failure_output() else {
+            return vec![];
+        };
+        for line in output.lines() {
+            parser.parse_line(line);
+        }
+        let mut backtraces = parser.into_backtraces();
+        for backtrace in &mut backtraces {
+            backtrace.test.clone_from(&self.name);
+        }
+        backtraces
+    }
+}
@@ -0,0 +1,218 @@
+//! Parsing large logs with multiple threads, enabled with the `parallel` feature.
+
+use std::{collections::VecDeque, fs::File, io, path::Path};
+
+use rayon::prelude::*;
+
+use super::{Parser, MAX_DIAGNOSTICS};
+use crate::{config::Config, Backtrace, Diagnostic};
+
+/// Parses all the backtraces in a file. The file is memory-mapped, so large logs are parsed without
+/// reading them into memory or allocating a string per line.
+pub fn parse_file(path: &Path) -> io::Result<Vec<Backtrace>> {
+    parse_file_with(path, Parser::new)
+}
+
+/// Parses all the backtraces in a file like [`parse_file`] with the options in `config`, see
+/// [`Parser::from_config`].
+pub fn parse_file_with_config(path: &Path, config: &Config) -> io::Result<Vec<Backtrace>> {
+    parse_file_with(path, || Parser::from_config(config))
+}
+
+fn parse_file_with(
+    path: &Path,
+    new_parser: impl Fn() -> Parser + Sync,
+) -> io::Result<Vec<Backtrace>> {
+    let file = File::open(path)?;
+    // Mapping an empty file fails on some platforms.
+    if file.metadata()?.len() == 0 {
+        return Ok(vec![]);
+    }
+    // SAFETY: the map is only read while parsing. If the file is truncated concurrently we may get a
+    // SIGBUS, which is acceptable for a log that is being parsed after the fact.
+    let mmap = unsafe { memmap2::Mmap::map(&file)? };
+    Ok(parse_bytes_parallel_with(&mmap, new_parser))
+}
+
+/// Parses all the backtraces in a buffer using multiple threads. The result is the same as parsing
+/// the buffer with [`Parser::parse_bytes`].
+///
+/// The buffer is split into chunks starting at panic lines. A panic line finishes the backtrace in
+/// progress and resets most of the state of the parser, so each chunk can be parsed independently
+/// and the results concatenated in order. We can't split at `stack backtrace:` lines because the
+/// preceding panic message belongs to the backtrace that follows. The rest of the state, i.e., the
+/// test whose output is being parsed, the active spans, and the diagnostics printed since the last
+/// backtrace (see [`Carry`]), is passed from each chunk to the next, parsing again the chunks whose
+/// backtraces depend on it.
+pub fn parse_bytes_parallel(bytes: &[u8]) -> Vec<Backtrace> {
+    parse_bytes_parallel_with(bytes, Parser::new)
+}
+
+fn parse_bytes_parallel_with(
+    bytes: &[u8],
+    new_parser: impl Fn() -> Parser + Sync,
+) -> Vec<Backtrace> {
+    // Small inputs aren't worth the overhead
+    const MIN_CHUNK_SIZE: usize = 1 << 20;
+    let n = usize::min(
+        rayon::current_num_threads() * 4,
+        bytes.len() / MIN_CHUNK_SIZE,
+    );
+    if n <= 1 {
+        let mut parser = new_parser();
+        parser.parse_bytes(bytes);
+        return parser.into_backtraces();
+    }
+    let chunks = split_at_panics(bytes, n, &new_parser());
+    let last = chunks.len() - 1;
+    let parse = |i: usize, carry: Carry| {
+        let mut parser = new_parser();
+        parser.seed(carry);
+        parser.parse_bytes(chunks[i]);
+        // Like the panic line starting the next chunk would. The last chunk is finished by
+        // `into_backtraces`, which also keeps the lines after its last backtrace.
+        if i < last {
+            parser.finish_panic();
+        }
+        parser
+    };
+    // First as if every chunk was at the start of the buffer
+    let parsers: Vec<_> = (0..chunks.len())
+        .into_par_iter()
+        .map(|i| parse(i, Carry::default()))
+        .collect();
+    let mut carries = vec![Carry::default()];
+    for parser in &parsers[..last] {
+        let carry = parser.carry_after(carries.last().unwrap());
+        carries.push(carry);
+    }
+    parsers
+        .into_par_iter()
+        .zip(carries)
+        .enumerate()
+        .map(|(i, (parser, carry))| {
+            let parser = if carry.is_empty() {
+                parser
+            } else {
+                parse(i, carry)
+            };
+            if i < last {
+                parser.backtraces
+            } else {
+                parser.into_backtraces()
+            }
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+/// Splits the buffer in (at most) `n` chunks of roughly the same size, such that every chunk but
+/// the first starts with a panic line.
+fn split_at_panics<'a>(bytes: &'a [u8], n: usize, parser: &Parser) -> Vec<&'a [u8]> {
+    let mut boundaries = vec![0];
+    for k in 1..n {
+        let offset = k * bytes.len() / n;
+        if offset <= *boundaries.last().unwrap() {
+            continue;
+        }
+        // Start from the first full line after the offset
+        let Some(newline) = bytes[offset..].iter().position(|b| *b == b'\n') else {
+            break;
+        };
+        let mut start = offset + newline + 1;
+        let boundary = loop {
+            if start >= bytes.len() {
+                break None;
+            }
+            let end = bytes[start..]
+                .iter()
+                .position(|b| *b == b'\n')
+                .map_or(bytes.len(), |i| start + i);
+            if parser.is_panic_line(&bytes[start..end]) {
+                break Some(start);
+            }
+            start = end + 1;
+        };
+        // If there's no panic line until the end, there won't be one for the next offsets either
+        let Some(boundary) = boundary else { break };
+        boundaries.push(boundary);
+    }
+    boundaries.push(bytes.len());
+    boundaries
+        .windows(2)
+        .map(|w| &bytes[w[0]..w[1]])
+        .filter(|chunk| !chunk.is_empty())
+        .collect()
+}
+
+/// The state of a parser that carries over a panic line, i.e., what the lines before the start of
+/// a chunk contribute to its backtraces, see [`parse_bytes_parallel`].
+#[derive(Default)]
+struct Carry {
+    /// See [`Parser::test`]
+    test: Option<String>,
+    /// See [`Parser::spans`]
+    spans: Vec<String>,
+    /// The diagnostics printed after the last backtrace
+    diagnostics: VecDeque<Diagnostic>,
+}
+
+impl Carry {
+    fn is_empty(&self) -> bool {
+        self.test.is_none() && self.spans.is_empty() && self.diagnostics.is_empty()
+    }
+}
+
+impl Parser {
+    fn is_panic_line(&self, line: &[u8]) -> bool {
+        // Without `log_prefix`, most lines are ruled out without decoding them
+        if self.log_prefix.is_none() && !line.starts_with(b"thread") {
+            return false;
+        }
+        let line = String::from_utf8_lossy(line);
+        // The prefix is stripped like in `strip_prefix`
+        let line = match self.log_prefix.as_ref().and_then(|regex| regex.find(&line)) {
+            Some(prefix) if prefix.start() == 0 => &line[prefix.end()..],
+            _ => &line,
+        };
+        line.starts_with("thread") && self.panic_regex.is_match(line)
+    }
+
+    /// Starts parsing after the lines that left a parser in the `carry` state.
+    fn seed(&mut self, carry: Carry) {
+        self.test = carry.test;
+        self.spans = carry.spans;
+        self.diagnostics = carry.diagnostics;
+    }
+
+    /// The state after parsing the lines given to this parser if it was seeded with `carry`
+    /// instead, see [`Parser::seed`].
+    fn carry_after(&self, carry: &Carry) -> Carry {
+        let test = if self.replaced.test {
+            &self.test
+        } else {
+            &carry.test
+        };
+        let spans = if self.replaced.spans {
+            &self.spans
+        } else {
+            &carry.spans
+        };
+        let mut diagnostics = self.diagnostics.clone();
+        if !self.replaced.diagnostics {
+            for diagnostic in carry.diagnostics.iter().rev() {
+                if diagnostics.len() == MAX_DIAGNOSTICS {
+                    break;
+                }
+                diagnostics.push_front(diagnostic.clone());
+            }
+        }
+        Carry {
+            test: test.clone(),
+            spans: spans.clone(),
+            diagnostics,
+        }
+    }
+}
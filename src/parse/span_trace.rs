@@ -0,0 +1,121 @@
+//! Parsing the span traces reported by [color-eyre](https://crates.io/crates/color-eyre), i.e., the
+//! `tracing` spans that were active when a panic happened or an error was created, e.g.,
+//! ```text
+//!   ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━ SPANTRACE ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
+//!
+//!    0: app::config::read_file with path="app.toml"
+//!       at src/config.rs:12
+//!    1: app::run
+//!       at src/main.rs:20
+//! ```
+//! Spans are numbered like frames, innermost first. The location may also follow the fields in the
+//! same line, e.g., `0: app::run at src/main.rs:20`.
+
+use super::{parse_location, split_number, strip_whitespace};
+
+type Location<'a> = (&'a str, usize, Option<usize>);
+
+/// A span in a span trace as printed, see [`parse_span`].
+pub(crate) struct Span<'a> {
+    pub name: &'a str,
+    pub fields: Option<&'a str>,
+    pub location: Option<Location<'a>>,
+}
+
+/// Whether the line is the header printed before the spans, e.g.,
+/// `━━━━━━━━━━ SPANTRACE ━━━━━━━━━━`.
+pub(crate) fn is_start(line: &str) -> bool {
+    let trimmed = line.trim();
+    trimmed.starts_with('━') && trimmed.trim_matches('━').trim() == "SPANTRACE"
+}
+
+/// Parses a numbered span, e.g., `   0: app::config::read_file with path="app.toml"`.
+pub(crate) fn parse_span(line: &str) -> Option<Span<'_>> {
+    let line = strip_whitespace(line)?;
+    let (_, rest) = split_number(line)?;
+    let rest = rest.strip_prefix(": ")?.trim_end();
+    // The location may follow the fields, which may contain ` at ` themselves
+    let (rest, location) = match rest.rfind(" at ") {
+        Some(i) => match parse_location(&rest[i + 4..]) {
+            Some(location) => (&rest[..i], Some(location)),
+            None => (rest, None),
+        },
+        None => (rest, None),
+    };
+    let (name, fields) = match rest.split_once(" with ") {
+        Some((name, fields)) => (name, Some(fields)),
+        None => (rest, None),
+    };
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return None;
+    }
+    Some(Span {
+        name,
+        fields,
+        location,
+    })
+}
+
+/// Parses the location of the previous span, e.g., `      at src/config.rs:12`.
+pub(crate) fn parse_span_location(line: &str) -> Option<Location<'_>> {
+    parse_location(strip_whitespace(line)?.strip_prefix("at ")?.trim_end())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_start, parse_span, parse_span_location};
+
+    #[test]
+    fn start() {
+        assert!(is_start(
+            "  ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━ SPANTRACE ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━"
+        ));
+        assert!(!is_start(
+            "  ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━ BACKTRACE ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━"
+        ));
+        assert!(!is_start("SPANTRACE"));
+    }
+
+    #[test]
+    fn span() {
+        let span = parse_span(r#"   0: app::config::read_file with path="app.toml""#).unwrap();
+        assert_eq!(span.name, "app::config::read_file");
+        assert_eq!(span.fields, Some(r#"path="app.toml""#));
+        assert!(span.location.is_none());
+
+        let span = parse_span("   1: app::run").unwrap();
+        assert_eq!(span.name, "app::run");
+        assert!(span.fields.is_none());
+
+        assert!(parse_span("   0: not a span").is_none());
+        assert!(parse_span("0: app::run").is_none());
+    }
+
+    /// The location may follow the fields, which may contain ` at ` themselves.
+    #[test]
+    fn span_with_location() {
+        let span = parse_span("   0: app::run at src/main.rs:20").unwrap();
+        assert_eq!(span.name, "app::run");
+        assert_eq!(span.location, Some(("src/main.rs", 20, None)));
+
+        let span =
+            parse_span(r#"   0: app::greet with msg="look at me" at src/main.rs:7:3"#).unwrap();
+        assert_eq!(span.name, "app::greet");
+        assert_eq!(span.fields, Some(r#"msg="look at me""#));
+        assert_eq!(span.location, Some(("src/main.rs", 7, Some(3))));
+
+        let span = parse_span(r#"   0: app::greet with msg="look at me""#).unwrap();
+        assert_eq!(span.fields, Some(r#"msg="look at me""#));
+        assert!(span.location.is_none());
+    }
+
+    #[test]
+    fn span_location() {
+        assert_eq!(
+            parse_span_location("      at src/config.rs:12"),
+            Some(("src/config.rs", 12, None))
+        );
+        assert!(parse_span_location("at src/config.rs:12").is_none());
+        assert!(parse_span_location("      src/config.rs:12").is_none());
+    }
+}
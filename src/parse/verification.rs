@@ -0,0 +1,215 @@
+//! Parsing the failures reported by verification tools: the results of
+//! [Kani](https://github.com/model-checking/kani) checks and the counterexample traces printed by
+//! CBMC (e.g., with `kani --cbmc-args --trace`).
+//!
+//! A failed Kani check becomes a backtrace with a single frame at the location of the check, e.g.,
+//! ```text
+//! Check 1: main.assertion.1
+//!          - Status: FAILURE
+//!          - Description: "assertion failed: x < 10"
+//!          - Location: src/main.rs:5:5 in function main
+//! ```
+//! In a counterexample trace, every step becomes a frame (the last step is the innermost) and the
+//! violated property becomes the panic message, e.g.,
+//! ```text
+//! Trace for main.assertion.1:
+//!
+//! State 19 file src/main.rs function main line 4 thread 0
+//! ----------------------------------------------------
+//!   x=11ul (00000000 00000000 00000000 00001011)
+//!
+//! Violated property:
+//!   file src/main.rs function main line 5 thread 0
+//!   assertion failed: x < 10
+//! ```
+
+use super::{parse_location, split_number};
+
+type Location<'a> = (&'a str, usize, Option<usize>);
+
+/// Where the parser is within a report.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum State {
+    None,
+    /// After the line starting a Kani check
+    KaniCheck,
+    /// After the line starting a trace
+    Trace,
+    /// After the `Violated property:` line, the location of the property follows
+    Property,
+    /// After the location of the violated property, its description follows
+    PropertyMessage,
+}
+
+/// A step in a trace or the location of a violated property.
+pub(crate) struct Step<'a> {
+    pub(crate) function: &'a str,
+    pub(crate) file: &'a str,
+    pub(crate) lineno: usize,
+}
+
+/// Whether the line starts the result of a Kani check, e.g., `Check 1: main.assertion.1`, where the
+/// name of the check is the function, the kind of property, and a number.
+pub(crate) fn is_kani_check(line: &str) -> bool {
+    let Some(rest) = line.strip_prefix("Check ") else {
+        return false;
+    };
+    let Some(name) = split_number(rest).and_then(|(_, rest)| rest.strip_prefix(": ")) else {
+        return false;
+    };
+    let Some((name, number)) = name.rsplit_once('.') else {
+        return false;
+    };
+    let kind = name.rsplit_once('.').map(|(_, kind)| kind);
+    !number.is_empty()
+        && number.bytes().all(|b| b.is_ascii_digit())
+        && kind.is_some_and(|kind| !kind.is_empty() && !kind.contains(char::is_whitespace))
+}
+
+/// Parses a field of a Kani check, e.g., ` - Status: FAILURE`.
+pub(crate) fn parse_kani_field(line: &str) -> Option<(&str, &str)> {
+    let rest = line.trim_start().strip_prefix("- ")?;
+    let (field, value) = rest.split_once(": ")?;
+    matches!(field, "Status" | "Description" | "Location").then_some((field, value))
+}
+
+/// Parses the value of the `Location` field of a Kani check, e.g.,
+/// `src/main.rs:5:5 in function main`.
+pub(crate) fn parse_kani_location(value: &str) -> Option<(&str, Location<'_>)> {
+    let (location, function) = value.rsplit_once(" in function ")?;
+    Some((function, parse_location(location)?))
+}
+
+/// Whether the line starts a counterexample trace, e.g., `Trace for main.assertion.1:`.
+pub(crate) fn is_trace_start(line: &str) -> bool {
+    line.starts_with("Trace for ") && line.ends_with(':')
+}
+
+/// Parses a step of a trace, e.g., `State 19 file src/main.rs function main line 4 thread 0`, or
+/// the location of a violated property, which is the same without the `State` part.
+pub(crate) fn parse_step(line: &str) -> Option<Step<'_>> {
+    let line = line.trim_start();
+    let line = match line.strip_prefix("State ") {
+        Some(rest) => split_number(rest)?.1.trim_start(),
+        None => line,
+    };
+    let rest = line.strip_prefix("file ")?;
+    let (file, rest) = rest.split_once(" function ")?;
+    let (function, rest) = rest.rsplit_once(" line ")?;
+    let (lineno, _) = split_number(rest)?;
+    Some(Step {
+        function,
+        file,
+        lineno,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse::Parser;
+
+    fn parse(log: &str) -> Vec<crate::Backtrace> {
+        let mut parser = Parser::new();
+        for line in log.lines() {
+            parser.parse_line(line);
+        }
+        parser.into_backtraces()
+    }
+
+    #[test]
+    fn kani_check() {
+        assert!(super::is_kani_check("Check 1: main.assertion.1"));
+        assert!(super::is_kani_check(
+            "Check 12: <i32 as std::ops::Add>::add.arithmetic_overflow.3"
+        ));
+        assert!(!super::is_kani_check("Check 1: done"));
+        assert!(!super::is_kani_check("Check 1: see the logs."));
+        assert!(!super::is_kani_check("Check 1: main.assertion"));
+    }
+
+    #[test]
+    fn failed_kani_check() {
+        let backtraces = parse(
+            "Check 1: main.assertion.1\n\
+             \t - Status: FAILURE\n\
+             \t - Description: \"assertion failed: x < 10\"\n\
+             \t - Location: src/main.rs:5:5 in function main\n",
+        );
+        assert_eq!(backtraces.len(), 1);
+        let panic_info = backtraces[0].panic_info.as_ref().unwrap();
+        assert_eq!(panic_info.message, ["assertion failed: x < 10"]);
+        assert_eq!(panic_info.at, "src/main.rs:5:5");
+        assert_eq!(backtraces[0].frames[0].function, "main");
+    }
+
+    /// A line that looks like a check but isn't followed by its fields doesn't leave a panic behind
+    /// for the next backtrace.
+    #[test]
+    fn not_a_kani_check() {
+        let backtraces = parse(
+            "Check 1: cache.warmup.1\n\
+             warming up\n\
+             stack backtrace:\n   \
+             0: app::main\n             \
+             at ./src/main.rs:3:5\n",
+        );
+        assert_eq!(backtraces.len(), 1);
+        assert!(backtraces[0].panic_info.is_none());
+
+        let backtraces = parse(
+            "Check 1: main.assertion.1\n\
+             \t - Status: FAILURE\n\
+             \t - Description: \"assertion failed\"\n\
+             stack backtrace:\n   \
+             0: app::main\n             \
+             at ./src/main.rs:3:5\n",
+        );
+        assert_eq!(backtraces.len(), 1);
+        assert!(backtraces[0].panic_info.is_none());
+    }
+
+    #[test]
+    fn trace() {
+        let backtraces = parse(
+            "Trace for main.assertion.1:\n\
+             \n\
+             State 12 file src/main.rs function check line 9 thread 0\n\
+             ----------------------------------------------------\n  \
+             y=3ul (00000000 00000000 00000000 00000011)\n\
+             \n\
+             State 19 file src/main.rs function main line 4 thread 0\n\
+             ----------------------------------------------------\n  \
+             x=11ul (00000000 00000000 00000000 00001011)\n\
+             \n\
+             Violated property:\n  \
+             file src/main.rs function main line 5 thread 0\n  \
+             assertion failed: x < 10\n",
+        );
+        assert_eq!(backtraces.len(), 1);
+        let panic_info = backtraces[0].panic_info.as_ref().unwrap();
+        assert_eq!(panic_info.message, ["assertion failed: x < 10"]);
+        let frames: Vec<_> = backtraces[0]
+            .frames
+            .iter()
+            .map(|frame| {
+                let source_info = frame.source_info.as_ref().unwrap();
+                (frame.function.as_str(), source_info.lineno)
+            })
+            .collect();
+        assert_eq!(frames, [("main", 5), ("main", 4), ("check", 9)]);
+    }
+
+    #[test]
+    fn step() {
+        let step = super::parse_step("State 19 file src/main.rs function main line 4 thread 0");
+        let step = step.unwrap();
+        assert_eq!(
+            (step.function, step.file, step.lineno),
+            ("main", "src/main.rs", 4)
+        );
+        let step = super::parse_step("  file src/lib.rs function <T as Foo>::foo line 12 thread 0");
+        let step = step.unwrap();
+        assert_eq!(step.function, "<T as Foo>::foo");
+        assert!(super::parse_step("State 19 file src/main.rs").is_none());
+    }
+}
@@ -1,14 +1,34 @@
+//! Configuration is built by merging several *partial* layers (defaults, the global file, the
+//! local file, ...). [`Partial`] types are the layers and [`Complete`] types are the final result.
+
 use std::{
     collections::HashMap,
     hash::{BuildHasher, Hash},
+    path::PathBuf,
 };
 
+use serde::{Deserialize, Serialize};
+
 pub trait Partial {
     type Complete;
 
+    /// Merges the layer `other` on top of `self`: values set in `other` take precedence, and
+    /// collections are concatenated with the values in `other` last.
     fn merge_with(self, other: Self) -> Self;
 
     fn into_complete(self) -> Self::Complete;
+
+    /// Whether the value was explicitly set, as opposed to being left out and falling back to a
+    /// default when calling [`Partial::into_complete`].
+    fn is_set(&self) -> bool;
+
+    /// Calls `f` with the path to every value that is set. `path` is the path to `self` and is
+    /// extended with field names when visiting nested structs.
+    fn visit_set(&self, path: &mut Vec<&'static str>, f: &mut dyn FnMut(&[&'static str])) {
+        if self.is_set() {
+            f(path)
+        }
+    }
 }
 
 impl<T> Partial for Option<T>
@@ -24,35 +44,41 @@ where
     fn into_complete(self) -> Self::Complete {
         self.unwrap_or_default()
     }
-}
 
-impl<T> Partial for Vec<T> {
-    type Complete = Vec<T>;
-
-    fn merge_with(mut self, other: Self) -> Self {
-        self.extend(other);
-        self
-    }
-
-    fn into_complete(self) -> Self::Complete {
-        self
+    fn is_set(&self) -> bool {
+        self.is_some()
     }
 }
 
-impl<K, V, S> Partial for HashMap<K, V, S>
+/// The partial version of a collection, e.g., a [`Vec`] or a [`HashMap`]. Collections are
+/// concatenated when merging, and a collection is set if its key is present even if it's empty,
+/// e.g., `hide = []`.
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Collection<C>(pub Option<C>);
+
+impl<C> Partial for Collection<C>
 where
-    K: Eq + Hash,
-    S: BuildHasher,
+    C: Default + IntoIterator + Extend<C::Item>,
 {
-    type Complete = HashMap<K, V, S>;
+    type Complete = C;
 
-    fn merge_with(mut self, other: Self) -> Self {
-        self.extend(other);
-        self
+    fn merge_with(self, other: Self) -> Self {
+        match (self.0, other.0) {
+            (Some(mut values), Some(other)) => {
+                values.extend(other);
+                Collection(Some(values))
+            }
+            (values, other) => Collection(other.or(values)),
+        }
     }
 
     fn into_complete(self) -> Self::Complete {
-        self
+        self.0.unwrap_or_default()
+    }
+
+    fn is_set(&self) -> bool {
+        self.0.is_some()
     }
 }
 
@@ -60,38 +86,60 @@ pub trait Complete {
     type Partial: Partial;
 
     fn into_partial(self) -> Self::Partial;
+
+    /// Calls `f` with the path and doc comment of every documented field. `path` is the path to a
+    /// value of this type and is extended with field names when visiting nested structs. Only types
+    /// deriving `Partialize` have documented fields.
+    fn visit_docs(path: &mut Vec<&'static str>, f: &mut dyn FnMut(&[&'static str], &'static str)) {
+        let _ = (path, f);
+    }
 }
 
 impl<T> Complete for Vec<T> {
-    type Partial = Vec<T>;
+    type Partial = Collection<Vec<T>>;
 
     fn into_partial(self) -> Self::Partial {
-        self
+        Collection(Some(self))
     }
 }
 
 impl<K, V, S> Complete for HashMap<K, V, S>
 where
     K: Eq + Hash,
-    S: BuildHasher,
+    S: BuildHasher + Default,
 {
-    type Partial = HashMap<K, V, S>;
+    type Partial = Collection<HashMap<K, V, S>>;
 
     fn into_partial(self) -> Self::Partial {
-        self
+        Collection(Some(self))
     }
 }
 
-impl Complete for bool {
-    type Partial = Option<bool>;
-
-    fn into_partial(self) -> Self::Partial {
-        Some(self)
-    }
+/// Scalar values are replaced as a whole when merging, so their partial version is just an
+/// [`Option`] that is `None` when the value hasn't been set.
+macro_rules! impl_complete_for_scalars {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl Complete for $ty {
+                type Partial = Option<$ty>;
+
+                fn into_partial(self) -> Self::Partial {
+                    Some(self)
+                }
+            }
+        )*
+    };
 }
 
-impl Complete for String {
-    type Partial = Option<String>;
+impl_complete_for_scalars!(
+    bool, char, String, PathBuf, u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize,
+    f32, f64,
+);
+
+/// An optional value is also treated as a scalar, i.e., a layer that sets it to `None` overrides
+/// the value in previous layers.
+impl<T> Complete for Option<T> {
+    type Partial = Option<Option<T>>;
 
     fn into_partial(self) -> Self::Partial {
         Some(self)
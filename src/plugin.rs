@@ -0,0 +1,334 @@
+//! Running the `[plugin]` command, which can hide, rename, or annotate the frames of a backtrace
+//! with logic that doesn't fit in the configuration. The command receives the backtrace as JSON on
+//! stdin, in the same format as `--format json`, and writes the changes to stdout, e.g.,
+//! ```json
+//! {
+//!   "notes": ["owned by @payments"],
+//!   "frames": [
+//!     { "hide": true },
+//!     null,
+//!     { "function": "app::checkout", "annotations": [{ "label": "runbook", "text": "go/checkout" }] }
+//!   ]
+//! }
+//! ```
+//! The changes to a frame are at the same index as the frame in the input, and `null` or missing
+//! entries leave frames unchanged. An empty output leaves the backtrace unchanged.
+//!
+//! Plugins are external commands, so they can be written in any language. The command is killed
+//! if it takes longer than the `timeout` of the `[plugin]` section. While capturing the output of a
+//! command, plugins run in the background (see [`Worker`]), so a slow plugin doesn't hold up the
+//! output.
+
+use std::{
+    io::{Read, Write},
+    process::{Child, Output, Stdio},
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::bail;
+use backtracetk::{
+    config::Plugin,
+    model::{Annotation, Backtrace},
+};
+use serde::Deserialize;
+
+use crate::hooks::shell;
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Changes {
+    /// Lines added to [`Backtrace::trailer`]
+    notes: Vec<String>,
+    frames: Vec<Option<FrameChanges>>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct FrameChanges {
+    hide: bool,
+    function: Option<String>,
+    annotations: Vec<AnnotationChange>,
+}
+
+#[derive(Deserialize)]
+struct AnnotationChange {
+    label: String,
+    text: String,
+}
+
+/// Runs the `[plugin]` command for `backtrace` and applies the changes it writes.
+pub fn run(config: &Plugin, backtrace: &mut Backtrace) {
+    let Some(command) = &config.command else {
+        return;
+    };
+    let timeout = Duration::from_secs(config.timeout);
+    let output = match run_command(command, timeout, backtrace) {
+        Ok(output) => output,
+        Err(err) => {
+            eprintln!("Warning: failed to run plugin command `{command}`: {err}");
+            return;
+        }
+    };
+    if !output.status.success() {
+        eprintln!(
+            "Warning: plugin command `{command}` failed: {}",
+            output.status
+        );
+        return;
+    }
+    if output.stdout.trim_ascii().is_empty() {
+        return;
+    }
+    let changes: Changes = match serde_json::from_slice(&output.stdout) {
+        Ok(changes) => changes,
+        Err(err) => {
+            eprintln!("Warning: failed to parse the output of plugin command `{command}`: {err}");
+            return;
+        }
+    };
+    backtrace.trailer.extend(changes.notes);
+    for (frame, changes) in backtrace.frames.iter_mut().zip(changes.frames) {
+        let Some(changes) = changes else {
+            continue;
+        };
+        frame.hidden |= changes.hide;
+        if let Some(function) = changes.function {
+            frame.function = function;
+        }
+        frame.annotations.extend(
+            changes
+                .annotations
+                .into_iter()
+                .map(|annotation| Annotation {
+                    label: annotation.label,
+                    text: annotation.text,
+                }),
+        );
+    }
+}
+
+/// Runs the `[plugin]` command in a background thread, one backtrace at a time. Backtraces come
+/// back in the order they were sent.
+pub struct Worker {
+    jobs: Sender<Backtrace>,
+    done: Receiver<Backtrace>,
+    /// How many backtraces were sent and haven't come back
+    pending: usize,
+}
+
+impl Worker {
+    pub fn start(config: &Plugin) -> Worker {
+        let config = Plugin {
+            command: config.command.clone(),
+            timeout: config.timeout,
+        };
+        let (jobs, rx) = mpsc::channel::<Backtrace>();
+        let (tx, done) = mpsc::channel();
+        thread::spawn(move || {
+            for mut backtrace in rx {
+                run(&config, &mut backtrace);
+                if tx.send(backtrace).is_err() {
+                    break;
+                }
+            }
+        });
+        Worker {
+            jobs,
+            done,
+            pending: 0,
+        }
+    }
+
+    pub fn send(&mut self, backtrace: Backtrace) {
+        // The thread only stops once we stop sending
+        let _ = self.jobs.send(backtrace);
+        self.pending += 1;
+    }
+
+    /// The backtraces the command is done with so far.
+    pub fn try_recv(&mut self) -> Vec<Backtrace> {
+        let backtraces: Vec<_> = self.done.try_iter().collect();
+        self.pending -= backtraces.len();
+        backtraces
+    }
+
+    /// Waits until the command is done with every backtrace sent, e.g., when the output ends.
+    pub fn finish(self) -> Vec<Backtrace> {
+        drop(self.jobs);
+        self.done.iter().take(self.pending).collect()
+    }
+}
+
+fn run_command(command: &str, timeout: Duration, backtrace: &Backtrace) -> anyhow::Result<Output> {
+    let json = serde_json::to_vec(backtrace)?;
+    let mut cmd = shell(command);
+    cmd.stdin(Stdio::piped()).stdout(Stdio::piped());
+    // In its own process group, so the processes it starts are also killed on timeout
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut cmd, 0);
+    let mut child = cmd.spawn()?;
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = child.stdout.take().unwrap();
+    // Written and read from other threads, so a command writing before it's done reading doesn't
+    // block, and we can stop waiting for it
+    let feed = thread::spawn(move || {
+        // The command may exit without reading its input, so a broken pipe isn't an error
+        let _ = stdin.write_all(&json);
+    });
+    let read = thread::spawn(move || {
+        let mut output = vec![];
+        stdout.read_to_end(&mut output).map(|_| output)
+    });
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            kill(&mut child);
+            bail!("timed out after {}s", timeout.as_secs());
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+    let _ = feed.join();
+    let stdout = read.join().unwrap()?;
+    Ok(Output {
+        status,
+        stdout,
+        stderr: vec![],
+    })
+}
+
+/// Kills the command and the processes it started.
+fn kill(child: &mut Child) {
+    #[cfg(unix)]
+    // SAFETY: `kill` has no memory safety requirements. The command is the leader of its process
+    // group, whose id is its pid.
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+    }
+    #[cfg(not(unix))]
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use backtracetk::{config::Plugin, model::Backtrace, parse::Parser};
+
+    use super::{run, Worker};
+
+    fn backtrace() -> Backtrace {
+        let mut parser = Parser::new();
+        for line in [
+            "thread 'main' panicked at src/main.rs:4:5:",
+            "boom",
+            "stack backtrace:",
+            "   0: std::panicking::begin_panic",
+            "   1: app::main",
+            "             at ./src/main.rs:4:5",
+            "   2: std::rt::lang_start",
+        ] {
+            parser.parse_line(line);
+        }
+        parser.into_backtraces().pop().unwrap()
+    }
+
+    /// A plugin that reads the backtrace and writes `output`.
+    fn plugin(output: &str) -> Plugin {
+        Plugin {
+            command: Some(format!("cat > /dev/null; printf '%s' '{output}'")),
+            timeout: 5,
+        }
+    }
+
+    fn functions(backtrace: &Backtrace) -> Vec<&str> {
+        backtrace.frames.iter().map(|f| &f.function[..]).collect()
+    }
+
+    #[test]
+    fn changes() {
+        let mut backtrace = backtrace();
+        let plugin = plugin(
+            r#"{
+                "notes": ["owned by @payments"],
+                "frames": [
+                    { "hide": true },
+                    null,
+                    { "function": "app::start", "annotations": [{ "label": "runbook", "text": "go/start" }] }
+                ]
+            }"#,
+        );
+        run(&plugin, &mut backtrace);
+        let hidden: Vec<_> = backtrace.frames.iter().map(|f| f.hidden).collect();
+        assert_eq!(hidden, [true, false, false]);
+        assert_eq!(
+            functions(&backtrace),
+            ["std::panicking::begin_panic", "app::main", "app::start"]
+        );
+        let annotation = &backtrace.frames[2].annotations[0];
+        assert_eq!(
+            (&annotation.label[..], &annotation.text[..]),
+            ("runbook", "go/start")
+        );
+        assert_eq!(backtrace.trailer, ["owned by @payments"]);
+    }
+
+    /// The backtrace is left unchanged if the plugin doesn't do its job.
+    #[test]
+    fn failures() {
+        let unchanged = |plugin: Plugin| {
+            let mut backtrace = backtrace();
+            run(&plugin, &mut backtrace);
+            assert_eq!(
+                functions(&backtrace),
+                [
+                    "std::panicking::begin_panic",
+                    "app::main",
+                    "std::rt::lang_start"
+                ]
+            );
+            assert!(backtrace.frames.iter().all(|f| !f.hidden));
+            assert!(backtrace.trailer.is_empty());
+        };
+        unchanged(plugin(""));
+        unchanged(plugin("not json"));
+        unchanged(plugin(r#"{ "frames": [{ "hide": "yes" }] }"#));
+        unchanged(Plugin {
+            command: Some(r#"printf '{ "notes": ["x"] }'; exit 1"#.to_string()),
+            timeout: 5,
+        });
+
+        let start = Instant::now();
+        unchanged(Plugin {
+            command: Some(r#"sleep 10; printf '{ "notes": ["x"] }'"#.to_string()),
+            timeout: 1,
+        });
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    /// Backtraces come back from the worker in the order they were sent.
+    #[test]
+    fn worker() {
+        let mut worker = Worker::start(&Plugin {
+            command: Some(r#"cat > /dev/null; printf '{ "notes": ["seen"] }'"#.to_string()),
+            timeout: 5,
+        });
+        for message in ["first", "second"] {
+            let mut backtrace = backtrace();
+            backtrace.panic_info.as_mut().unwrap().message = vec![message.to_string()];
+            worker.send(backtrace);
+        }
+        let backtraces = worker.finish();
+        let messages: Vec<_> = backtraces
+            .iter()
+            .map(|b| &b.panic_info.as_ref().unwrap().message[0][..])
+            .collect();
+        assert_eq!(messages, ["first", "second"]);
+        assert!(backtraces.iter().all(|b| b.trailer == ["seen"]));
+    }
+}
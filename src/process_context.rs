@@ -0,0 +1,77 @@
+//! Capturing the state of a process from `/proc` when it panics, see the `process_context` option
+//! and `follow --pid`. The process usually exits right after printing the backtrace, so the state
+//! is read as soon as the panic is detected.
+
+use backtracetk::model::ProcessContext;
+
+/// Whether the process with id `pid` is still running. Where `/proc` isn't available, we can't
+/// tell and assume it is.
+#[cfg(target_os = "linux")]
+pub fn is_running(pid: u32) -> bool {
+    std::path::Path::new("/proc").join(pid.to_string()).exists()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn is_running(_: u32) -> bool {
+    true
+}
+
+/// Reads the state of the process with id `pid`, or `None` if it isn't running anymore or `/proc`
+/// isn't available.
+#[cfg(target_os = "linux")]
+pub fn read(pid: u32) -> Option<ProcessContext> {
+    use std::{collections::HashSet, fs, path::Path};
+
+    use backtracetk::model::MemoryMaps;
+
+    let dir = Path::new("/proc").join(pid.to_string());
+    let cmdline = fs::read(dir.join("cmdline")).ok()?;
+    let cmdline = cmdline
+        .split(|&b| b == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| String::from_utf8_lossy(arg).into_owned())
+        .collect();
+    let cwd = fs::read_link(dir.join("cwd"))
+        .ok()
+        .map(|cwd| cwd.display().to_string());
+    let open_fds = fs::read_dir(dir.join("fd")).ok().map(Iterator::count);
+    let memory_maps = fs::read_to_string(dir.join("maps")).ok().map(|maps| {
+        let mut count = 0;
+        let mut size = 0;
+        let mut files = HashSet::new();
+        // e.g., `55d4a3c00000-55d4a3c21000 r--p 00000000 fd:01 1234  /usr/bin/app`
+        for line in maps.lines() {
+            let mut fields = line.split_whitespace();
+            let Some((start, end)) = fields.next().and_then(|range| range.split_once('-')) else {
+                continue;
+            };
+            let (Ok(start), Ok(end)) =
+                (u64::from_str_radix(start, 16), u64::from_str_radix(end, 16))
+            else {
+                continue;
+            };
+            count += 1;
+            size += end.saturating_sub(start);
+            if let Some(path) = fields.nth(4).filter(|path| path.starts_with('/')) {
+                files.insert(path);
+            }
+        }
+        MemoryMaps {
+            count,
+            size,
+            files: files.len(),
+        }
+    });
+    Some(ProcessContext {
+        pid,
+        cmdline,
+        cwd,
+        open_fds,
+        memory_maps,
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn read(_: u32) -> Option<ProcessContext> {
+    None
+}
@@ -0,0 +1,65 @@
+//! Running the command in a pseudo-terminal, see `--pty`. Programs often check whether their output
+//! is a terminal to decide whether to use colors and line buffering, so their output looks the same
+//! as when running them directly.
+
+use std::{
+    fs::File,
+    io::{self, Read},
+    os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd},
+    ptr,
+};
+
+/// The side of a pseudo-terminal read by backtracetk.
+pub struct Master(File);
+
+/// Opens a pseudo-terminal returning its master and slave sides. The terminal has the size of the
+/// terminal backtracetk runs in, if any.
+pub fn open() -> io::Result<(Master, File)> {
+    let size = window_size();
+    let size = size.as_ref().map_or(ptr::null(), |size| size as *const _);
+    let (mut master, mut slave) = (-1, -1);
+    // SAFETY: the pointers are valid for the duration of the call, and the name isn't written
+    let res = unsafe { libc::openpty(&mut master, &mut slave, ptr::null_mut(), ptr::null(), size) };
+    if res < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: `openpty` returned two open descriptors owned by nobody else
+    let (master, slave) = unsafe { (OwnedFd::from_raw_fd(master), OwnedFd::from_raw_fd(slave)) };
+    // The command only gets the slave side, as the streams set up when spawning it
+    for fd in [&master, &slave] {
+        // SAFETY: the descriptor is open
+        if unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok((Master(File::from(master)), File::from(slave)))
+}
+
+/// The size of the terminal stderr or stdout is connected to.
+fn window_size() -> Option<libc::winsize> {
+    [libc::STDERR_FILENO, libc::STDOUT_FILENO]
+        .into_iter()
+        .find_map(|fd| {
+            // SAFETY: `winsize` is plain data and `TIOCGWINSZ` writes at most one
+            let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+            let res = unsafe { libc::ioctl(fd, libc::TIOCGWINSZ, &mut size) };
+            (res == 0 && size.ws_col > 0).then_some(size)
+        })
+}
+
+impl Read for Master {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.0.read(buf) {
+            // On Linux, reading fails once every descriptor of the slave side is closed, i.e.,
+            // when the command exits
+            Err(err) if err.raw_os_error() == Some(libc::EIO) => Ok(0),
+            res => res,
+        }
+    }
+}
+
+impl AsFd for Master {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0.as_fd()
+    }
+}
@@ -0,0 +1,219 @@
+//! Limiting how often the same backtrace is rendered, so a crash loop in a long running process
+//! doesn't flood the terminal.
+
+use std::{
+    collections::HashMap,
+    io,
+    time::{Duration, Instant},
+};
+
+use anstyle::{AnsiColor, Reset, Style};
+use backtracetk::model::{Backtrace, Fingerprint};
+
+const CYAN: Style = AnsiColor::Cyan.on_default();
+
+/// Lets through the first occurrence of each [`Fingerprint`] in a time window and counts the rest.
+/// When the window of a fingerprint ends, a [`Summary`] with the number of suppressed occurrences is
+/// reported.
+///
+/// The current time is passed explicitly to make the limiter independent of how time is measured.
+pub struct RateLimiter {
+    window: Duration,
+    windows: HashMap<Fingerprint, Window>,
+    /// Windows that ended with suppressed occurrences that haven't been reported
+    ended: Vec<Summary>,
+}
+
+struct Window {
+    start: Instant,
+    suppressed: usize,
+    message: Option<String>,
+}
+
+/// The occurrences of a backtrace suppressed during a window.
+pub struct Summary {
+    pub fingerprint: Fingerprint,
+    pub suppressed: usize,
+    /// The first line of the panic message of the backtrace
+    pub message: Option<String>,
+    pub window: Duration,
+}
+
+impl RateLimiter {
+    pub fn new(window: Duration) -> RateLimiter {
+        RateLimiter {
+            window,
+            windows: HashMap::new(),
+            ended: vec![],
+        }
+    }
+
+    /// Whether `backtrace`, seen at `now`, should be rendered. It should be rendered if it's the
+    /// first occurrence of its fingerprint since the window started, otherwise it's counted as
+    /// suppressed.
+    pub fn check(&mut self, backtrace: &Backtrace, now: Instant) -> bool {
+        let fingerprint = backtrace.fingerprint();
+        if let Some(window) = self.windows.get_mut(&fingerprint) {
+            if now.duration_since(window.start) < self.window {
+                window.suppressed += backtrace.duplicates + 1;
+                return false;
+            }
+        }
+        let message = backtrace
+            .panic_info
+            .as_ref()
+            .and_then(|panic_info| panic_info.message.first())
+            .cloned();
+        let window = Window {
+            start: now,
+            suppressed: 0,
+            message,
+        };
+        if let Some(old) = self.windows.insert(fingerprint, window) {
+            self.end_window(fingerprint, old);
+        }
+        true
+    }
+
+    /// Ends the windows that are over at `now` returning a summary for those with suppressed
+    /// occurrences.
+    pub fn expired(&mut self, now: Instant) -> Vec<Summary> {
+        let expired: Vec<_> = self
+            .windows
+            .iter()
+            .filter(|(_, window)| now.duration_since(window.start) >= self.window)
+            .map(|(fingerprint, _)| *fingerprint)
+            .collect();
+        for fingerprint in expired {
+            let window = self.windows.remove(&fingerprint).unwrap();
+            self.end_window(fingerprint, window);
+        }
+        std::mem::take(&mut self.ended)
+    }
+
+    /// Ends all windows returning a summary for those with suppressed occurrences.
+    pub fn finish(mut self) -> Vec<Summary> {
+        for (fingerprint, window) in std::mem::take(&mut self.windows) {
+            self.end_window(fingerprint, window);
+        }
+        self.ended
+    }
+
+    fn end_window(&mut self, fingerprint: Fingerprint, window: Window) {
+        if window.suppressed > 0 {
+            self.ended.push(Summary {
+                fingerprint,
+                suppressed: window.suppressed,
+                message: window.message,
+                window: self.window,
+            });
+        }
+    }
+}
+
+impl Summary {
+    pub fn render_to(&self, out: &mut impl io::Write) -> io::Result<()> {
+        let minutes = self.window.as_secs() / 60;
+        let window = match minutes {
+            1 => "minute".to_string(),
+            _ => format!("{minutes} minutes"),
+        };
+        let times = match self.suppressed {
+            1 => "time",
+            _ => "times",
+        };
+        write!(
+            out,
+            "{CYAN}(backtrace {} printed {} more {times} in the last {window}",
+            self.fingerprint, self.suppressed,
+        )?;
+        if let Some(message) = &self.message {
+            write!(out, ": {message}")?;
+        }
+        writeln!(out, "){Reset}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use backtracetk::{model::Backtrace, parse::Parser};
+
+    use super::RateLimiter;
+
+    const WINDOW: Duration = Duration::from_secs(5 * 60);
+
+    fn panic(message: &str) -> Backtrace {
+        let mut parser = Parser::new();
+        for line in [
+            "thread 'main' panicked at src/main.rs:4:5:",
+            message,
+            "stack backtrace:",
+            "   0: app::main",
+            "             at ./src/main.rs:4:5",
+        ] {
+            parser.parse_line(line);
+        }
+        parser.into_backtraces().pop().unwrap()
+    }
+
+    #[test]
+    fn suppressed_in_window() {
+        let start = Instant::now();
+        let mut limiter = RateLimiter::new(WINDOW);
+        let backtrace = panic("boom");
+        assert!(limiter.check(&backtrace, start));
+        assert!(!limiter.check(&backtrace, start + Duration::from_secs(10)));
+        assert!(!limiter.check(&backtrace, start + Duration::from_secs(20)));
+        // A different panic location isn't suppressed
+        let mut other = panic("boom");
+        other.frames[0].function = "app::run".to_string();
+        assert!(limiter.check(&other, start + Duration::from_secs(30)));
+        assert!(limiter.expired(start + Duration::from_secs(60)).is_empty());
+    }
+
+    #[test]
+    fn summary_when_window_ends() {
+        let start = Instant::now();
+        let mut limiter = RateLimiter::new(WINDOW);
+        let backtrace = panic("boom");
+        assert!(limiter.check(&backtrace, start));
+        assert!(!limiter.check(&backtrace, start + Duration::from_secs(10)));
+        assert!(!limiter.check(&backtrace, start + Duration::from_secs(20)));
+
+        let summaries = limiter.expired(start + WINDOW);
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].fingerprint, backtrace.fingerprint());
+        assert_eq!(summaries[0].suppressed, 2);
+        let mut out = vec![];
+        summaries[0].render_to(&mut out).unwrap();
+        let out = anstream::adapter::strip_str(std::str::from_utf8(&out).unwrap()).to_string();
+        assert_eq!(
+            out,
+            format!(
+                "(backtrace {} printed 2 more times in the last 5 minutes: boom)\n",
+                backtrace.fingerprint()
+            )
+        );
+
+        // A new window starts with the next occurrence
+        assert!(limiter.check(&backtrace, start + WINDOW + Duration::from_secs(1)));
+        assert!(limiter.finish().is_empty());
+    }
+
+    #[test]
+    fn finish_reports_open_windows() {
+        let start = Instant::now();
+        let mut limiter = RateLimiter::new(WINDOW);
+        let backtrace = panic("boom");
+        assert!(limiter.check(&backtrace, start));
+        assert!(!limiter.check(&backtrace, start + Duration::from_secs(10)));
+        assert!(limiter.expired(start + Duration::from_secs(20)).is_empty());
+
+        let summaries = limiter.finish();
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].suppressed, 1);
+        assert_eq!(summaries[0].message.as_deref(), Some("boom"));
+    }
+}
@@ -0,0 +1,218 @@
+//! Redaction of secrets in the text of backtraces, see [`Config::redact`].
+//!
+//! [`Config::redact`]: crate::config::Config::redact
+
+use std::borrow::Cow;
+
+use crate::{config::Redact, Backtrace};
+
+impl Backtrace {
+    /// Applies the `redact` rules to every text field: the panic message and location, the lines
+    /// the backtrace was parsed from (so the raw output is also redacted), the command and test,
+    /// the spans and their fields, the diagnostics, the note and the lines after the frames, and
+    /// the captured state of the process. Function names and source locations of frames are left
+    /// as they are since they're needed to render the frames.
+    pub fn redact(&mut self, rules: &[Redact]) {
+        if rules.is_empty() {
+            return;
+        }
+        if let Some(panic_info) = &mut self.panic_info {
+            redact_lines(rules, &mut panic_info.message);
+            if redact_string(rules, &mut panic_info.at) {
+                panic_info.location = None;
+                panic_info.locate();
+            }
+            if let Some(thread) = &mut panic_info.thread {
+                redact_string(rules, thread);
+            }
+        }
+        for text in [&mut self.command, &mut self.test, &mut self.note]
+            .into_iter()
+            .flatten()
+        {
+            redact_string(rules, text);
+        }
+        redact_lines(rules, &mut self.raw_header);
+        redact_lines(rules, &mut self.raw_footer);
+        for frame in &mut self.frames {
+            redact_lines(rules, &mut frame.raw);
+        }
+        redact_lines(rules, &mut self.trailer);
+        redact_lines(rules, &mut self.spans);
+        for span in &mut self.span_trace {
+            if let Some(fields) = &mut span.fields {
+                redact_string(rules, fields);
+            }
+        }
+        for diagnostic in &mut self.diagnostics {
+            redact_string(rules, &mut diagnostic.message);
+        }
+        if let Some(process) = &mut self.process {
+            redact_lines(rules, &mut process.cmdline);
+            if let Some(cwd) = &mut process.cwd {
+                redact_string(rules, cwd);
+            }
+        }
+    }
+}
+
+/// Replaces the matches of every rule in `text`, in the order the rules are defined.
+pub fn redact<'a>(rules: &[Redact], text: &'a str) -> Cow<'a, str> {
+    let mut text = Cow::Borrowed(text);
+    for rule in rules {
+        if let Cow::Owned(replaced) = rule.pattern.replace_all(&text, &rule.replacement) {
+            text = Cow::Owned(replaced);
+        }
+    }
+    text
+}
+
+/// Redacts `text` in place, returning whether anything was replaced.
+fn redact_string(rules: &[Redact], text: &mut String) -> bool {
+    match redact(rules, text) {
+        Cow::Owned(redacted) => {
+            *text = redacted;
+            true
+        }
+        Cow::Borrowed(_) => false,
+    }
+}
+
+fn redact_lines(rules: &[Redact], lines: &mut [String]) {
+    for line in lines {
+        redact_string(rules, line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use regex::Regex;
+
+    use crate::{
+        config::Redact, model::ProcessContext, Backtrace, BacktraceBuilder, Diagnostic, PanicInfo,
+        SourceInfo, SpanTraceEntry,
+    };
+
+    const SECRET: &str = "token=hunter2";
+
+    fn rules() -> Vec<Redact> {
+        vec![Redact {
+            pattern: Regex::new("token=\\w+").unwrap(),
+            replacement: "[REDACTED]".to_string(),
+        }]
+    }
+
+    fn backtrace() -> Backtrace {
+        let mut builder = BacktraceBuilder::new();
+        builder.frame("app::main", None);
+        builder.build().unwrap()
+    }
+
+    fn redacted(mut backtrace: Backtrace) -> Backtrace {
+        backtrace.redact(&rules());
+        backtrace
+    }
+
+    fn source_info() -> SourceInfo {
+        SourceInfo {
+            file: "src/main.rs".to_string(),
+            lineno: 1,
+            colno: None,
+        }
+    }
+
+    #[test]
+    fn panic_info() {
+        let mut backtrace = backtrace();
+        backtrace.panic_info = Some(PanicInfo {
+            thread: Some(format!("worker {SECRET}")),
+            at: format!("src/main.rs:1:1, {SECRET}"),
+            message: vec![format!("failed with {SECRET}")],
+            location: None,
+        });
+        let panic_info = redacted(backtrace).panic_info.unwrap();
+        assert_eq!(panic_info.thread.unwrap(), "worker [REDACTED]");
+        assert_eq!(panic_info.at, "src/main.rs:1:1, [REDACTED]");
+        assert_eq!(panic_info.message, ["failed with [REDACTED]"]);
+    }
+
+    #[test]
+    fn command_and_test() {
+        let mut backtrace = backtrace();
+        backtrace.command = Some(format!("./app --{SECRET}"));
+        backtrace.test = Some(format!("tests::{SECRET}"));
+        let backtrace = redacted(backtrace);
+        assert_eq!(backtrace.command.unwrap(), "./app --[REDACTED]");
+        assert_eq!(backtrace.test.unwrap(), "tests::[REDACTED]");
+    }
+
+    #[test]
+    fn note() {
+        let mut backtrace = backtrace();
+        backtrace.note = Some(format!("note: {SECRET}"));
+        assert_eq!(redacted(backtrace).note.unwrap(), "note: [REDACTED]");
+    }
+
+    #[test]
+    fn trailer() {
+        let mut backtrace = backtrace();
+        backtrace.trailer = vec![format!("exiting with {SECRET}")];
+        assert_eq!(redacted(backtrace).trailer, ["exiting with [REDACTED]"]);
+    }
+
+    #[test]
+    fn raw_lines() {
+        let mut backtrace = backtrace();
+        backtrace.raw_header = vec![format!("panicked with {SECRET}")];
+        backtrace.raw_footer = vec![format!("after {SECRET}")];
+        backtrace.frames[0].raw = vec![format!("   0: app::{SECRET}")];
+        let backtrace = redacted(backtrace);
+        assert_eq!(backtrace.raw_header, ["panicked with [REDACTED]"]);
+        assert_eq!(backtrace.raw_footer, ["after [REDACTED]"]);
+        assert_eq!(backtrace.frames[0].raw, ["   0: app::[REDACTED]"]);
+    }
+
+    #[test]
+    fn spans() {
+        let mut backtrace = backtrace();
+        backtrace.spans = vec![format!("request{{{SECRET}}}")];
+        backtrace.span_trace = vec![SpanTraceEntry {
+            name: "app::request".to_string(),
+            fields: Some(SECRET.to_string()),
+            location: None,
+        }];
+        let backtrace = redacted(backtrace);
+        assert_eq!(backtrace.spans, ["request{[REDACTED]}"]);
+        assert_eq!(
+            backtrace.span_trace[0].fields.as_deref(),
+            Some("[REDACTED]")
+        );
+    }
+
+    #[test]
+    fn diagnostics() {
+        let mut backtrace = backtrace();
+        backtrace.diagnostics = vec![Diagnostic {
+            level: "warning".to_string(),
+            message: format!("unused `{SECRET}`"),
+            location: source_info(),
+        }];
+        let backtrace = redacted(backtrace);
+        assert_eq!(backtrace.diagnostics[0].message, "unused `[REDACTED]`");
+    }
+
+    #[test]
+    fn process() {
+        let mut backtrace = backtrace();
+        backtrace.process = Some(ProcessContext {
+            pid: 1,
+            cmdline: vec!["app".to_string(), format!("--{SECRET}")],
+            cwd: Some(format!("/home/{SECRET}")),
+            open_fds: None,
+            memory_maps: None,
+        });
+        let process = redacted(backtrace).process.unwrap();
+        assert_eq!(process.cmdline, ["app", "--[REDACTED]"]);
+        assert_eq!(process.cwd.unwrap(), "/home/[REDACTED]");
+    }
+}
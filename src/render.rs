@@ -1,33 +1,193 @@
+//! Rendering backtraces to a terminal, see [`Backtrace::render_to`]. With the `html` feature,
+//! backtraces can also be rendered as HTML with `Backtrace::render_html`, and
+//! [`Backtrace::raw_text`] gives the text they were parsed from.
+
+#[cfg(feature = "html")]
+mod html;
+mod raw;
+
 use std::{
+    borrow::Cow,
+    cell::RefCell,
+    collections::HashMap,
     fmt,
-    fs::File,
+    fs::{self, File},
     io::{self, BufRead},
-    path::Path,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::SystemTime,
 };
 
 use anstyle::{AnsiColor, Color, Reset, Style};
 
-use crate::{config::Config, Backtrace, Frame, FrameFilter, PanicInfo, SourceInfo};
+use crate::{
+    config::{
+        Config, ForeignFrames, FrameVars, HeaderVars, NoUserFrames, PanicVars, Sort, Template,
+    },
+    filter::Filters,
+    redact::redact,
+    Backtrace, Diagnostic, Frame, FrameFilter, PanicInfo, SourceInfo, SpanTraceEntry,
+};
 
 const GREEN: Style = Style::new().fg_color(Some(Color::Ansi(AnsiColor::Green)));
 const CYAN: Style = Style::new().fg_color(Some(Color::Ansi(AnsiColor::Cyan)));
+const YELLOW: Style = Style::new().fg_color(Some(Color::Ansi(AnsiColor::Yellow)));
 const RED: Style = Style::new().fg_color(Some(Color::Ansi(AnsiColor::Red)));
 const BOLD: Style = Style::new().bold();
+const DIM: Style = Style::new().dimmed();
 const RESET: Reset = Reset;
 
+/// How much frames are indented under the name of their crate, see [`Config::group_by_crate`].
+const GROUP_INDENT: usize = 2;
+
+/// Terminals narrower than this use a vertical layout, where locations and snippets aren't aligned
+/// with the function names, paths are shortened, and snippet lines are truncated to fit.
+const NARROW_WIDTH: usize = 70;
+
 impl Backtrace {
+    /// Renders the backtrace to stderr.
     pub fn render(&self, config: &Config, filter: &mut impl FrameFilter) {
-        let frameno_width = self.compute_frameno_width();
+        let _ = self.render_to(&mut anstream::stderr().lock(), config, filter);
+    }
+
+    /// Renders the backtrace to the given writer. Colors and hyperlinks are always emitted, wrap the
+    /// writer in an [`anstream::AutoStream`] to strip them when not supported.
+    pub fn render_to(
+        &self,
+        out: &mut impl io::Write,
+        config: &Config,
+        filter: &mut impl FrameFilter,
+    ) -> io::Result<()> {
+        self.render_at(out, config, filter, None)
+    }
+
+    /// Renders the backtrace labeled with its `position` among the backtraces rendered, see
+    /// [`render_by_thread`].
+    fn render_at(
+        &self,
+        out: &mut impl io::Write,
+        config: &Config,
+        filter: &mut impl FrameFilter,
+        position: Option<(usize, usize)>,
+    ) -> io::Result<()> {
+        let mut frameno_width = self.compute_frameno_width();
+        // Grouped frames are indented under the name of their crate
+        if config.group_by_crate && !config.compact {
+            frameno_width += GROUP_INDENT;
+        }
         let lineno_width = self.compute_lineno_width();
-        let total_width = self.compute_width(frameno_width);
+        let term_width = terminal_width();
+        let total_width = self.compute_width(frameno_width, term_width);
         let cx = RenderCtxt {
             config,
             frameno_width,
             lineno_width,
             total_width,
+            narrow: term_width < NARROW_WIDTH,
+            built_before: self.built_before,
+            position,
+        };
+        cx.render_backtrace(out, self, filter)
+    }
+}
+
+/// Renders the tests that panicked and where, in the order they first panicked, e.g., after the
+/// backtraces in the output of `cargo test`. Backtraces without a [`Backtrace::test`] are left out.
+pub fn render_test_summary(out: &mut impl io::Write, backtraces: &[Backtrace]) -> io::Result<()> {
+    let mut tests: Vec<(&str, Vec<String>)> = vec![];
+    for backtrace in backtraces {
+        let Some(test) = &backtrace.test else {
+            continue;
+        };
+        let location = match &backtrace.panic_info {
+            Some(panic_info) if !panic_info.at.is_empty() => {
+                Some(panic_info.at.trim_end_matches(':').to_string())
+            }
+            _ => backtrace
+                .top_user_frame()
+                .and_then(|frame| frame.source_info.as_ref())
+                .map(SourceInfo::to_string),
+        };
+        let i = match tests.iter().position(|(name, _)| name == test) {
+            Some(i) => i,
+            None => {
+                tests.push((test, vec![]));
+                tests.len() - 1
+            }
+        };
+        let locations = &mut tests[i].1;
+        if let Some(location) = location.filter(|location| !locations.contains(location)) {
+            locations.push(location);
+        }
+    }
+    if tests.is_empty() {
+        return Ok(());
+    }
+    writeln!(out, "{BOLD}panicked tests:{RESET}")?;
+    let width = tests.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    for (name, locations) in &tests {
+        match &locations[..] {
+            [] => writeln!(out, "  {RED}{name}{RESET}")?,
+            _ => writeln!(
+                out,
+                "  {RED}{name:width$}{RESET}  at {}",
+                locations.join(", ")
+            )?,
+        }
+    }
+    Ok(())
+}
+
+/// Renders several backtraces, e.g., all the backtraces printed by a command. When they were
+/// printed by more than one thread, they're grouped by thread, in the order each thread first
+/// panicked, under a header naming the thread, and each is labeled with its position, e.g.,
+/// `BACKTRACE 2/3 (thread 'worker-1')`. Backtraces printed in a test aren't grouped, since the test
+/// harness runs each test in a thread of its own and they're already labeled with the test, nor
+/// when they're sorted with [`Config::sort`], which would be undone by grouping them.
+pub fn render_by_thread(
+    out: &mut impl io::Write,
+    backtraces: &[Backtrace],
+    config: &Config,
+) -> io::Result<()> {
+    let mut threads: Vec<(Option<&str>, Vec<&Backtrace>)> = vec![];
+    for backtrace in backtraces {
+        let thread = backtrace
+            .panic_info
+            .as_ref()
+            .and_then(|panic_info| panic_info.thread.as_deref());
+        match threads.iter_mut().find(|(other, _)| *other == thread) {
+            Some((_, group)) => group.push(backtrace),
+            None => threads.push((thread, vec![backtrace])),
+        }
+    }
+    if threads.len() < 2
+        || config.sort != Sort::Capture
+        || backtraces.iter().any(|backtrace| backtrace.test.is_some())
+    {
+        for backtrace in backtraces {
+            backtrace.render_to(out, config, &mut Filters::from_config(config))?;
+        }
+        return Ok(());
+    }
+    let total = backtraces.len();
+    let mut index = 0;
+    for (thread, group) in &threads {
+        let count = match group.len() {
+            1 => "1 backtrace".to_string(),
+            n => format!("{n} backtraces"),
+        };
+        let thread = match thread {
+            Some(name) => format!("thread '{name}'"),
+            None => "unknown thread".to_string(),
         };
-        cx.render_backtrace(self, filter)
+        writeln!(out, "\n{BOLD}{thread}{RESET} {DIM}({count}){RESET}")?;
+        for backtrace in group {
+            index += 1;
+            let filter = &mut Filters::from_config(config);
+            backtrace.render_at(out, config, filter, Some((index, total)))?;
+        }
     }
+    Ok(())
 }
 
 struct RenderCtxt<'a> {
@@ -35,126 +195,676 @@ struct RenderCtxt<'a> {
     frameno_width: usize,
     lineno_width: usize,
     total_width: usize,
+    /// Whether to use the vertical layout, see [`NARROW_WIDTH`]
+    narrow: bool,
+    built_before: Option<SystemTime>,
+    /// The index (from 1) of the backtrace and how many are rendered, see [`render_by_thread`]
+    position: Option<(usize, usize)>,
 }
 
 impl<'a> RenderCtxt<'a> {
-    fn render_backtrace(&self, backtrace: &Backtrace, filter: &mut impl FrameFilter) {
-        if backtrace.frames.is_empty() {
-            return;
+    fn render_backtrace(
+        &self,
+        out: &mut impl io::Write,
+        backtrace: &Backtrace,
+        filter: &mut impl FrameFilter,
+    ) -> io::Result<()> {
+        // Error reports and span traces are rendered even without frames
+        if backtrace.frames.is_empty()
+            && backtrace.panic_info.is_none()
+            && backtrace.span_trace.is_empty()
+        {
+            return Ok(());
+        }
+        if is_suppressed(backtrace, self.config) {
+            return writeln!(out, "{CYAN}{}{RESET}", suppressed_message(backtrace));
+        }
+        if self.config.compact {
+            return self.render_compact(out, backtrace, filter);
+        }
+        if let Some(template) = &self.config.header_format {
+            writeln!(out, "\n{}", format_header(template, backtrace))?;
+        } else {
+            self.render_header(out, backtrace)?;
         }
-        anstream::eprintln!("\n{:━^width$}", " BACKTRACE ", width = self.total_width);
+        if self.config.diagnostics {
+            self.render_diagnostics(out, &backtrace.diagnostics)?;
+        }
+        self.render_span_trace(out, &backtrace.span_trace)?;
 
-        let mut hidden = 0;
-        for frame in backtrace.frames.iter().rev() {
-            if filter.should_hide(frame) {
-                hidden += 1;
-            } else {
-                self.print_hidden_frames_message(hidden);
-                self.render_frame(frame);
-                hidden = 0;
+        let entries = entries(backtrace, self.config, filter);
+        // Entries are rendered outermost first, so the frames with details are the last ones
+        let mut undetailed = count_frames(&entries).saturating_sub(self.config.detailed_frames);
+        let mut group = None;
+        for (i, entry) in entries.iter().enumerate() {
+            match *entry {
+                Entry::Frame(frame) => {
+                    if self.config.group_by_crate && group != Some(frame.crate_name()) {
+                        group = Some(frame.crate_name());
+                        self.render_group_header(out, &entries[i..])?;
+                    }
+                    self.render_frame(out, frame, undetailed == 0)?;
+                    undetailed = undetailed.saturating_sub(1);
+                }
+                Entry::Hidden(hidden) => self.print_hidden_frames_message(out, hidden)?,
+                Entry::Foreign(foreign) => self.print_foreign_frames_message(out, foreign)?,
             }
         }
-        self.print_hidden_frames_message(hidden);
 
         if let Some(panic_info) = &backtrace.panic_info {
-            self.render_panic_info(panic_info);
+            self.render_panic_info(out, panic_info)?;
+            // The location was just rendered if it's the location of the frame right above
+            let last_frame = match entries.last() {
+                Some(Entry::Frame(frame)) => frame.source_info.as_ref(),
+                _ => None,
+            };
+            if let Some(location) = &panic_info.location {
+                if !last_frame.is_some_and(|last| last.is_same_line(location)) {
+                    self.render_source_info(out, location, true)?;
+                    self.render_code_snippet(out, location)?;
+                }
+            }
         }
 
-        eprintln!();
+        self.render_spans(out, &backtrace.spans)?;
+        if let Some(note) = &backtrace.note {
+            writeln!(out, "{DIM}{note}{RESET}")?;
+        }
+        for line in &backtrace.trailer {
+            writeln!(out, "{DIM}{line}{RESET}")?;
+        }
+
+        self.print_duplicates_message(out, backtrace.duplicates)?;
+
+        writeln!(out)
+    }
+
+    fn render_header(&self, out: &mut impl io::Write, backtrace: &Backtrace) -> io::Result<()> {
+        let mut header = " BACKTRACE ".to_string();
+        if let Some((index, total)) = self.position {
+            header.push_str(&format!("{index}/{total} "));
+            if let Some(thread) = backtrace
+                .panic_info
+                .as_ref()
+                .and_then(|panic_info| panic_info.thread.as_ref())
+            {
+                header.push_str(&format!("(thread '{thread}') "));
+            }
+        }
+        if let Some(command) = &backtrace.command {
+            header.push_str(&format!("({command}) "));
+        }
+        if let Some(test) = &backtrace.test {
+            header.push_str(&format!("(test {test}) "));
+        }
+        if let Some(time) = &backtrace.time {
+            header.push_str(&format!("@ {time} "));
+        }
+        if let Some(rustc) = backtrace
+            .build
+            .as_ref()
+            .and_then(|build| build.rustc.as_ref())
+        {
+            // Only the version, the commit hash and date make the header too long
+            let version = rustc.split_whitespace().next().unwrap_or(rustc);
+            header.push_str(&format!("[rustc {version}] "));
+        }
+        // Leave some room for the rule around long headers
+        let width = self.total_width.max(header.chars().count() + 6);
+        writeln!(out, "\n{header:━^width$}")
     }
 
-    fn print_hidden_frames_message(&self, hidden: u32) {
-        let msg = match hidden {
-            0 => return,
-            1 => format!(" ({hidden} frame hidden) "),
-            _ => format!(" ({hidden} frames hidden) "),
+    /// Renders the line with the name of the crate of the first frame in `entries`, followed by how
+    /// many of the frames up to the next frame from another crate are from it.
+    fn render_group_header(&self, out: &mut impl io::Write, entries: &[Entry]) -> io::Result<()> {
+        let frames = entries.iter().filter_map(|entry| match entry {
+            Entry::Frame(frame) => Some(frame.crate_name()),
+            _ => None,
+        });
+        let mut frames = frames.peekable();
+        let Some(&name) = frames.peek() else {
+            return Ok(());
         };
-        anstream::eprintln!("{CYAN}{msg:┄^width$}{RESET}", width = self.total_width);
+        let count = frames.take_while(|&other| other == name).count();
+        let name = if name.is_empty() { "<unknown>" } else { name };
+        match count {
+            1 => writeln!(out, "{BOLD}{name}{RESET} {DIM}(1 frame){RESET}"),
+            _ => writeln!(out, "{BOLD}{name}{RESET} {DIM}({count} frames){RESET}"),
+        }
     }
 
-    fn render_frame(&self, frame: &Frame) {
-        anstream::eprintln!(
-            "{:>width$}: {GREEN}{}{RESET}",
-            frame.frameno,
-            frame.function,
-            width = self.frameno_width
-        );
+    /// Renders the compiler diagnostics printed before the backtrace, one per line.
+    fn render_diagnostics(
+        &self,
+        out: &mut impl io::Write,
+        diagnostics: &[Diagnostic],
+    ) -> io::Result<()> {
+        if diagnostics.is_empty() {
+            return Ok(());
+        }
+        writeln!(out, "{DIM}recent diagnostics:{RESET}")?;
+        for diagnostic in diagnostics {
+            writeln!(
+                out,
+                "{DIM}  {}: {} ({}){RESET}",
+                diagnostic.level, diagnostic.message, diagnostic.location
+            )?;
+        }
+        writeln!(out, "{DIM}{}{RESET}", "┄".repeat(self.total_width))
+    }
 
-        if let Some(source_info) = &frame.source_info {
-            self.render_source_info(source_info);
-            let _ = self.render_code_snippet(source_info);
+    /// Renders the `tracing` spans the backtrace was printed in, outermost first.
+    fn render_spans(&self, out: &mut impl io::Write, spans: &[String]) -> io::Result<()> {
+        match spans {
+            [] => Ok(()),
+            [span] => writeln!(out, "{DIM}in span {span}{RESET}"),
+            _ => writeln!(out, "{DIM}in spans {}{RESET}", spans.join(" > ")),
         }
     }
 
-    fn render_source_info(&self, source_info: &SourceInfo) {
-        let text = format!(
-            "{}:{}:{}",
-            source_info.file, source_info.lineno, source_info.colno
-        );
-        if self.config.hyperlinks.enabled {
-            if let Some(encoded) = encode_file_path_for_url(&source_info.file) {
-                let url =
-                    self.config
-                        .hyperlinks
-                        .render(&encoded, source_info.lineno, source_info.colno);
-                anstream::eprintln!("{}  at {}", self.frameno_padding(), Link::new(text, url));
-                return;
+    /// Renders the span trace reported by `color-eyre` as a section above the frames, outermost first
+    /// like the frames.
+    fn render_span_trace(
+        &self,
+        out: &mut impl io::Write,
+        span_trace: &[SpanTraceEntry],
+    ) -> io::Result<()> {
+        if span_trace.is_empty() {
+            return Ok(());
+        }
+        writeln!(
+            out,
+            "{DIM}{:┄^width$}{RESET}",
+            " SPANTRACE ",
+            width = self.total_width
+        )?;
+        for (i, span) in span_trace.iter().enumerate().rev() {
+            write!(
+                out,
+                "{i:>width$}: {GREEN}{}{RESET}",
+                span.name,
+                width = self.frameno_width
+            )?;
+            if let Some(fields) = &span.fields {
+                write!(out, " {DIM}with {fields}{RESET}")?;
+            }
+            writeln!(out)?;
+            if let Some(location) = &span.location {
+                self.render_source_info(out, location, true)?;
             }
         }
-        anstream::eprintln!("{}  at {text}", self.frameno_padding())
+        writeln!(out, "{DIM}{}{RESET}", "┄".repeat(self.total_width))
     }
 
-    fn render_code_snippet(&self, source_info: &SourceInfo) -> io::Result<()> {
-        let path = Path::new(&source_info.file);
-        if path.exists() {
-            let file = File::open(path)?;
-            let reader = io::BufReader::new(file);
-            for (i, line) in viewport(reader, source_info)? {
-                if i == source_info.lineno {
-                    anstream::eprint!("{BOLD}");
+    /// Renders one line per frame with no snippets, rules, or colors.
+    fn render_compact(
+        &self,
+        out: &mut impl io::Write,
+        backtrace: &Backtrace,
+        filter: &mut impl FrameFilter,
+    ) -> io::Result<()> {
+        for span in backtrace.span_trace.iter().rev() {
+            write!(out, "in span {}", span.name)?;
+            if let Some(fields) = &span.fields {
+                write!(out, " with {fields}")?;
+            }
+            if let Some(location) = &span.location {
+                write!(out, " ({location})")?;
+            }
+            writeln!(out)?;
+        }
+        for entry in entries(backtrace, self.config, filter) {
+            match entry {
+                Entry::Frame(frame) => {
+                    if let Some(template) = &self.config.frame_format {
+                        writeln!(out, "{}", format_frame(template, frame))?;
+                        continue;
+                    }
+                    write!(out, "#{} {}", frame.frameno, frame.function)?;
+                    if let Some(source_info) = &frame.source_info {
+                        write!(out, " ({source_info})")?;
+                    }
+                    writeln!(out)?;
                 }
-                anstream::eprintln!(
-                    "{}    {i:>width$} | {line}",
+                Entry::Hidden(_) => {}
+                Entry::Foreign(foreign) => writeln!(out, "{}", foreign_frames_message(foreign))?,
+            }
+        }
+        if let Some(template) = &self.config.panic_format {
+            if let Some(panic_info) = &backtrace.panic_info {
+                writeln!(out, "{}", format_panic_info(template, panic_info))?;
+            }
+        } else if let Some(panic_info) = &backtrace.panic_info {
+            match &panic_info.thread {
+                Some(thread) => writeln!(out, "thread '{thread}' panicked at {}", panic_info.at)?,
+                // An error returned from `main` only has a message
+                None if panic_info.at.is_empty() => {}
+                None => writeln!(out, "panicked at {}", panic_info.at)?,
+            }
+            for line in &panic_info.message {
+                writeln!(out, "{line}")?;
+            }
+        }
+        if backtrace.duplicates > 0 {
+            writeln!(out, "{}", duplicates_message(backtrace.duplicates))?;
+        }
+        writeln!(out)
+    }
+
+    fn print_hidden_frames_message(&self, out: &mut impl io::Write, hidden: u32) -> io::Result<()> {
+        let msg = format!(" {} ", hidden_frames_message(hidden));
+        writeln!(out, "{CYAN}{msg:┄^width$}{RESET}", width = self.total_width)
+    }
+
+    fn print_foreign_frames_message(
+        &self,
+        out: &mut impl io::Write,
+        foreign: u32,
+    ) -> io::Result<()> {
+        let msg = format!(" {} ", foreign_frames_message(foreign));
+        writeln!(out, "{DIM}{msg:┄^width$}{RESET}", width = self.total_width)
+    }
+
+    fn print_duplicates_message(
+        &self,
+        out: &mut impl io::Write,
+        duplicates: usize,
+    ) -> io::Result<()> {
+        if duplicates == 0 {
+            return Ok(());
+        }
+        let msg = format!(" {} ", duplicates_message(duplicates));
+        writeln!(out, "{CYAN}{msg:┄^width$}{RESET}", width = self.total_width)
+    }
+
+    /// Renders a frame. Only frames with `detailed` set get a code snippet and a hyperlink, see
+    /// [`Config::detailed_frames`].
+    fn render_frame(
+        &self,
+        out: &mut impl io::Write,
+        frame: &Frame,
+        detailed: bool,
+    ) -> io::Result<()> {
+        if let Some(template) = &self.config.frame_format {
+            if self.config.group_by_crate {
+                write!(out, "{}", Padding(GROUP_INDENT))?;
+            }
+            write!(out, "{}", format_frame(template, frame))?;
+        } else {
+            write!(
+                out,
+                "{:>width$}: {GREEN}{}{RESET}",
+                frame.frameno,
+                frame.function,
+                width = self.frameno_width
+            )?;
+        }
+        for annotation in &frame.annotations {
+            write!(
+                out,
+                " {DIM}[{}: {}]{RESET}",
+                annotation.label, annotation.text
+            )?;
+        }
+        writeln!(out)?;
+
+        if let Some(source_info) = &frame.source_info {
+            // The template replaces the location too
+            if self.config.frame_format.is_none() {
+                self.render_source_info(out, source_info, detailed)?;
+            }
+            if detailed {
+                self.render_code_snippet(out, source_info)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn render_source_info(
+        &self,
+        out: &mut impl io::Write,
+        source_info: &SourceInfo,
+        link: bool,
+    ) -> io::Result<()> {
+        let text = if self.narrow {
+            // The padding and `at` take 4 columns
+            let file = shorten_path(&source_info.file, self.total_width.saturating_sub(4));
+            SourceInfo {
+                file,
+                ..source_info.clone()
+            }
+            .to_string()
+        } else {
+            source_info.to_string()
+        };
+        if link && self.config.hyperlinks.enabled {
+            if let Some(encoded) = encode_file_path_for_url(&source_info.file) {
+                let url = self.config.hyperlinks.render(
+                    &encoded,
+                    source_info.lineno,
+                    source_info.colno.unwrap_or(1),
+                );
+                return writeln!(
+                    out,
+                    "{}  at {}",
                     self.frameno_padding(),
-                    width = self.lineno_width
+                    Link::new(text, url)
                 );
-                if i == source_info.lineno {
-                    anstream::eprint!("{RESET}");
-                }
+            }
+        }
+        writeln!(out, "{}  at {text}", self.frameno_padding())
+    }
+
+    fn render_code_snippet(
+        &self,
+        out: &mut impl io::Write,
+        source_info: &SourceInfo,
+    ) -> io::Result<()> {
+        // Failing to read the source file is not an error, we just don't print the snippet.
+        let Ok(Some(source)) = read_source(Path::new(&source_info.file)) else {
+            return Ok(());
+        };
+        if self.built_before.is_some_and(|built| source.mtime > built) {
+            writeln!(
+                out,
+                "{}    {YELLOW}(modified after the program was built, the code may not match){RESET}",
+                self.frameno_padding()
+            )?;
+        }
+        let lines = source
+            .lines
+            .iter()
+            .enumerate()
+            .skip(source_info.lineno.saturating_sub(2))
+            .take(5)
+            .map(|(i, line)| (i + 1, line));
+        for (i, line) in lines {
+            let redacted = redact(&self.config.redact, line);
+            let line = if self.narrow {
+                // The padding, line number, and separator take the rest of the width
+                truncate(
+                    &redacted,
+                    self.total_width.saturating_sub(self.lineno_width + 8),
+                )
+            } else {
+                Cow::Borrowed(&*redacted)
+            };
+            if i == source_info.lineno {
+                write!(out, "{BOLD}")?;
+            }
+            writeln!(
+                out,
+                "{}    {i:>width$} | {line}",
+                self.frameno_padding(),
+                width = self.lineno_width
+            )?;
+            if i == source_info.lineno {
+                write!(out, "{RESET}")?;
             }
         }
         Ok(())
     }
 
+    /// The padding aligning locations and snippets with the function names, which is dropped in the
+    /// vertical layout to leave more room for them.
     fn frameno_padding(&self) -> Padding {
-        Padding(self.frameno_width)
+        if self.narrow {
+            Padding(0)
+        } else {
+            Padding(self.frameno_width)
+        }
     }
 
-    fn render_panic_info(&self, panic_info: &PanicInfo) {
-        anstream::eprint!("{RED}");
-        anstream::eprintln!(
-            "thread '{}' panickd at {}",
-            panic_info.thread,
-            panic_info.at
-        );
+    fn render_panic_info(
+        &self,
+        out: &mut impl io::Write,
+        panic_info: &PanicInfo,
+    ) -> io::Result<()> {
+        if let Some(template) = &self.config.panic_format {
+            return writeln!(
+                out,
+                "{RED}{}{RESET}",
+                format_panic_info(template, panic_info)
+            );
+        }
+        write!(out, "{RED}")?;
+        match &panic_info.thread {
+            Some(thread) => writeln!(out, "thread '{thread}' panickd at {}", panic_info.at)?,
+            None if panic_info.at.is_empty() => {}
+            None => writeln!(out, "panicked at {}", panic_info.at)?,
+        }
         for line in &panic_info.message {
-            anstream::eprintln!("{line}");
+            writeln!(out, "{line}")?;
+        }
+        write!(out, "{RESET}")
+    }
+}
+
+/// A frame to render, a run of consecutive hidden frames, or a run of consecutive foreign frames
+/// collapsed into a single row (see [`Config::foreign_frames`]).
+enum Entry<'a> {
+    Frame(&'a Frame),
+    Hidden(u32),
+    Foreign(u32),
+}
+
+/// The frames of the backtrace in the order they are rendered (outermost first), grouping
+/// consecutive hidden frames and consecutive collapsed foreign frames. If there are no frames in
+/// user code, the `hide` rules are ignored or every frame is hidden depending on
+/// [`Config::no_user_frames`].
+fn entries<'a>(
+    backtrace: &'a Backtrace,
+    config: &Config,
+    filter: &mut impl FrameFilter,
+) -> Vec<Entry<'a>> {
+    let policy = match backtrace.top_user_frame() {
+        Some(_) => None,
+        None => Some(config.no_user_frames),
+    };
+    let mut entries = vec![];
+    for frame in backtrace.frames.iter().rev() {
+        // Filters are stateful so they must see every frame
+        let hidden = filter.should_hide(frame);
+        let hidden = match policy {
+            None => hidden,
+            Some(NoUserFrames::Show) => false,
+            Some(NoUserFrames::Collapse | NoUserFrames::Suppress) => true,
+        };
+        let entry = if hidden || (frame.foreign && config.foreign_frames == ForeignFrames::Hide) {
+            Entry::Hidden(1)
+        } else if frame.foreign && config.foreign_frames == ForeignFrames::Collapse {
+            Entry::Foreign(1)
+        } else {
+            Entry::Frame(frame)
+        };
+        match (entries.last_mut(), entry) {
+            (Some(Entry::Hidden(n)), Entry::Hidden(m)) => *n += m,
+            (Some(Entry::Foreign(n)), Entry::Foreign(m)) => *n += m,
+            (_, entry) => entries.push(entry),
+        }
+    }
+    entries
+}
+
+/// The number of frames in `entries` that are rendered.
+fn count_frames(entries: &[Entry]) -> usize {
+    entries
+        .iter()
+        .filter(|entry| matches!(entry, Entry::Frame(_)))
+        .count()
+}
+
+/// Whether the backtrace isn't rendered because it has no frames in user code, see
+/// [`Config::no_user_frames`].
+fn is_suppressed(backtrace: &Backtrace, config: &Config) -> bool {
+    config.no_user_frames == NoUserFrames::Suppress && backtrace.top_user_frame().is_none()
+}
+
+fn suppressed_message(backtrace: &Backtrace) -> String {
+    let frames = backtrace.frames.len();
+    let frames = match frames {
+        1 => "1 frame".to_string(),
+        _ => format!("{frames} frames"),
+    };
+    match &backtrace.panic_info {
+        Some(panic_info) => format!(
+            "(backtrace with no frames in user code suppressed, {frames}, panicked at {})",
+            panic_info.at.trim_end_matches(':')
+        ),
+        None => format!("(backtrace with no frames in user code suppressed, {frames})"),
+    }
+}
+
+fn format_header(template: &Template<HeaderVars>, backtrace: &Backtrace) -> String {
+    let command = backtrace.command.as_deref().unwrap_or_default();
+    let test = backtrace.test.as_deref().unwrap_or_default();
+    let time = backtrace.time.as_deref().unwrap_or_default();
+    let rustc = backtrace
+        .build
+        .as_ref()
+        .and_then(|build| build.rustc.as_deref())
+        .and_then(|rustc| rustc.split_whitespace().next())
+        .unwrap_or_default();
+    template.render(&[
+        ("command", &command),
+        ("test", &test),
+        ("time", &time),
+        ("rustc", &rustc),
+        ("fingerprint", &backtrace.fingerprint()),
+    ])
+}
+
+fn format_frame(template: &Template<FrameVars>, frame: &Frame) -> String {
+    let source_info = frame.source_info.as_ref();
+    let file = source_info.map(|s| &*s.file).unwrap_or_default();
+    let line = source_info
+        .map(|s| s.lineno.to_string())
+        .unwrap_or_default();
+    let column = source_info
+        .and_then(|s| s.colno)
+        .map(|colno| colno.to_string())
+        .unwrap_or_default();
+    template.render(&[
+        ("frameno", &frame.frameno),
+        ("function", &frame.function),
+        ("file", &file),
+        ("line", &line),
+        ("column", &column),
+    ])
+}
+
+fn format_panic_info(template: &Template<PanicVars>, panic_info: &PanicInfo) -> String {
+    let thread = panic_info.thread.as_deref().unwrap_or_default();
+    let location = panic_info.at.trim_end_matches(':');
+    template.render(&[
+        ("thread", &thread),
+        ("location", &location),
+        ("message", &panic_info.message.join("\n")),
+    ])
+}
+
+fn hidden_frames_message(hidden: u32) -> String {
+    match hidden {
+        1 => format!("({hidden} frame hidden)"),
+        _ => format!("({hidden} frames hidden)"),
+    }
+}
+
+fn foreign_frames_message(foreign: u32) -> String {
+    match foreign {
+        1 => format!("({foreign} foreign frame)"),
+        _ => format!("({foreign} foreign frames)"),
+    }
+}
+
+fn duplicates_message(duplicates: usize) -> String {
+    format!("(same backtrace printed {} times)", duplicates + 1)
+}
+
+thread_local! {
+    /// The lines of the source files read for code snippets together with their modification time.
+    /// The same locations tend to appear in many backtraces (e.g., in a crash loop), so files are
+    /// only read again if they were modified.
+    static SOURCE_CACHE: RefCell<SourceCache> = RefCell::default();
+}
+
+#[derive(Clone)]
+struct Source {
+    mtime: SystemTime,
+    lines: Rc<[String]>,
+}
+
+/// How many source files are kept in [`SOURCE_CACHE`]. Commands like `serve` and `follow` run for
+/// a long time, so the files used the longest ago are evicted.
+const MAX_CACHED_SOURCES: usize = 64;
+
+#[derive(Default)]
+struct SourceCache {
+    /// The sources and when they were last used
+    sources: HashMap<PathBuf, (Source, u64)>,
+    /// Incremented every time a source is used
+    clock: u64,
+}
+
+impl SourceCache {
+    /// The source at `path` if it's cached and wasn't modified since it was read.
+    fn get(&mut self, path: &Path, mtime: SystemTime) -> Option<Source> {
+        let (source, last_used) = self.sources.get_mut(path)?;
+        if source.mtime != mtime {
+            return None;
+        }
+        self.clock += 1;
+        *last_used = self.clock;
+        Some(source.clone())
+    }
+
+    fn insert(&mut self, path: PathBuf, source: Source) {
+        if self.sources.len() >= MAX_CACHED_SOURCES && !self.sources.contains_key(&path) {
+            let oldest = self
+                .sources
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(path, _)| path.clone());
+            if let Some(oldest) = oldest {
+                self.sources.remove(&oldest);
+            }
         }
-        anstream::eprint!("{RESET}");
+        self.clock += 1;
+        self.sources.insert(path, (source, self.clock));
     }
 }
 
-fn viewport(
-    reader: io::BufReader<File>,
-    source_info: &SourceInfo,
-) -> io::Result<Vec<(usize, String)>> {
-    reader
-        .lines()
-        .enumerate()
-        .skip(source_info.lineno.saturating_sub(2))
-        .take(5)
-        .map(|(i, line)| Ok((i + 1, line?)))
-        .collect()
+/// Source files larger than this are not read for code snippets.
+const MAX_SOURCE_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Reads the source file at `path`, or returns `None` if it doesn't exist. Paths come from the
+/// output of the program, so they may point anywhere, e.g., at a device like `/dev/zero`, only
+/// regular files of a reasonable size are read.
+fn read_source(path: &Path) -> io::Result<Option<Source>> {
+    let Ok(metadata) = fs::metadata(path) else {
+        return Ok(None);
+    };
+    if !metadata.is_file() || metadata.len() > MAX_SOURCE_SIZE {
+        return Ok(None);
+    }
+    let mtime = metadata.modified()?;
+    SOURCE_CACHE.with_borrow_mut(|cache| {
+        if let Some(source) = cache.get(path, mtime) {
+            return Ok(Some(source));
+        }
+        // Decoded line by line, so an invalid byte only affects the snippets of its line
+        let lines: Rc<[String]> = io::BufReader::new(File::open(path)?)
+            .split(b'\n')
+            .map(|line| {
+                let line = line?;
+                let line = line.strip_suffix(b"\r").unwrap_or(&line);
+                Ok(String::from_utf8_lossy(line).into_owned())
+            })
+            .collect::<io::Result<_>>()?;
+        let source = Source { mtime, lines };
+        cache.insert(path.to_path_buf(), source.clone());
+        Ok(Some(source))
+    })
 }
 
 impl Backtrace {
@@ -164,18 +874,19 @@ impl Backtrace {
         self.frames
             .iter()
             .flat_map(|f| &f.source_info)
-            .map(|source_info| source_info.lineno + 3)
+            .map(|source_info| source_info.lineno.saturating_add(3))
             .max()
             .unwrap_or(1)
             .ilog10() as usize
     }
 
     fn compute_frameno_width(&self) -> usize {
-        self.frames.len().ilog10() as usize + 1
+        // Frames may not start at 0, e.g., `color-backtrace` hides the first frames
+        let max_frameno = self.frames.iter().map(|f| f.frameno).max().unwrap_or(0);
+        max_frameno.max(1).ilog10() as usize + 1
     }
 
-    fn compute_width(&self, frameno_width: usize) -> usize {
-        let term_size = termion::terminal_size().unwrap_or((80, 0)).0 as usize;
+    fn compute_width(&self, frameno_width: usize, term_size: usize) -> usize {
         self.frames
             .iter()
             .map(|f| f.width(frameno_width))
@@ -188,22 +899,80 @@ impl Backtrace {
 impl Frame {
     fn width(&self, frameno_width: usize) -> usize {
         usize::max(
-            frameno_width + 2 + self.function.len(),
+            frameno_width + 2 + self.function.len() + self.annotations_width(),
             self.source_info
                 .as_ref()
                 .map(|s| s.width(frameno_width))
                 .unwrap_or(0),
         )
     }
+
+    fn annotations_width(&self) -> usize {
+        self.annotations
+            .iter()
+            .map(|a| a.label.len() + a.text.len() + 5)
+            .sum()
+    }
 }
 
 impl SourceInfo {
     /// Width without considering the source code snippet
     fn width(&self, frameno_width: usize) -> usize {
-        frameno_width + self.file.len() + (self.lineno.ilog10() + self.colno.ilog10()) as usize + 9
+        // Locations come from the log, so they may be bogus, e.g., line 0
+        let colno_width = self
+            .colno
+            .map_or(0, |colno| colno.checked_ilog10().unwrap_or(0) as usize + 2);
+        let lineno_width = self.lineno.checked_ilog10().unwrap_or(0) as usize;
+        frameno_width + self.file.len() + lineno_width + colno_width + 7
     }
 }
 
+/// Shortens a path to fit in `width` columns for the vertical layout, see [`NARROW_WIDTH`]. Paths
+/// into the sources of the standard library and of dependencies downloaded by cargo start from the
+/// name of the library or crate, and any path is cut to its last components if it's still too long.
+fn shorten_path(file: &str, width: usize) -> String {
+    let file = file.replace('\\', "/");
+    let library = if let Some(rest) = file.strip_prefix("/rustc/") {
+        // `/rustc/<commit>/library/core/src/panicking.rs`
+        rest.split_once("/library/")
+            .map(|(_, rest)| rest.to_string())
+    } else if let Some((_, rest)) = file.split_once("/.cargo/registry/src/") {
+        // `~/.cargo/registry/src/<index>/tokio-1.38.0/src/runtime/park.rs`
+        rest.split_once('/').map(|(_, rest)| rest.to_string())
+    } else if let Some((_, rest)) = file.split_once("/.cargo/git/checkouts/") {
+        // `~/.cargo/git/checkouts/<repo>/<rev>/src/lib.rs`
+        let mut parts = rest.splitn(3, '/');
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(repo), Some(_), Some(rest)) => Some(format!("{repo}/{rest}")),
+            _ => None,
+        }
+    } else {
+        None
+    };
+    let mut short = match library {
+        Some(library) => format!("…/{library}"),
+        None => file,
+    };
+    while short.chars().count() > width {
+        let rest = short.strip_prefix("…/").unwrap_or(&short);
+        match rest.split_once('/') {
+            Some((_, rest)) => short = format!("…/{rest}"),
+            None => break,
+        }
+    }
+    short
+}
+
+/// Cuts `line` to at most `width` characters, ending it with `…` if it was cut.
+fn truncate(line: &str, width: usize) -> Cow<'_, str> {
+    if line.chars().count() <= width {
+        return Cow::Borrowed(line);
+    }
+    let mut cut: String = line.chars().take(width.saturating_sub(1)).collect();
+    cut.push('…');
+    Cow::Owned(cut)
+}
+
 struct Padding(usize);
 
 impl std::fmt::Display for Padding {
@@ -237,8 +1006,61 @@ impl fmt::Display for Link {
 }
 
 fn encode_file_path_for_url(path: &str) -> Option<String> {
-    println!("{path:?}");
     let path = Path::new(path).canonicalize().ok()?;
-    println!("{path:?}");
     Some(format!("{}", path.display()))
 }
+
+/// The width of the terminal, or 80 if it can't be determined.
+fn terminal_width() -> usize {
+    #[cfg(all(unix, feature = "tui"))]
+    if let Ok((width, _)) = termion::terminal_size() {
+        return width as usize;
+    }
+    80
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use super::{read_source, SOURCE_CACHE};
+
+    fn temp_file(name: &str, contents: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("backtracetk-{}-{name}", std::process::id()));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    /// An invalid byte only replaces its own line, other lines are still shown.
+    #[test]
+    fn invalid_utf8() {
+        let path = temp_file("invalid.rs", b"fn main() {\r\n    let x = \"\xff\";\n}\n");
+        let source = read_source(&path).unwrap().unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            &*source.lines,
+            ["fn main() {", "    let x = \"\u{fffd}\";", "}"]
+        );
+    }
+
+    #[test]
+    fn eviction() {
+        let first = temp_file("first.rs", b"first");
+        read_source(&first).unwrap();
+        for i in 0..super::MAX_CACHED_SOURCES {
+            let path = temp_file(&format!("{i}.rs"), b"");
+            read_source(&path).unwrap();
+            fs::remove_file(&path).unwrap();
+            // The first file is used more recently than the others
+            if i == 0 {
+                read_source(&first).unwrap();
+            }
+        }
+        let cached = SOURCE_CACHE.with_borrow(|cache| {
+            assert_eq!(cache.sources.len(), super::MAX_CACHED_SOURCES);
+            cache.sources.contains_key(&first)
+        });
+        fs::remove_file(&first).unwrap();
+        assert!(cached);
+    }
+}
@@ -0,0 +1,223 @@
+//! Rendering backtraces as HTML, e.g., for web-based log viewers, enabled with the `html` feature.
+//! Unlike the terminal renderer, this doesn't access the file system, so there are no code snippets
+//! and file paths are linked as they appear in the backtrace.
+//!
+//! Elements are annotated with `bt-*` classes for styling.
+
+use std::io;
+
+use super::{
+    duplicates_message, entries, foreign_frames_message, hidden_frames_message, is_suppressed,
+    suppressed_message, Entry,
+};
+use crate::{config::Config, Backtrace, Frame, FrameFilter, PanicInfo, SourceInfo, SpanTraceEntry};
+
+impl Backtrace {
+    /// Renders the backtrace as an HTML fragment.
+    pub fn render_html(
+        &self,
+        out: &mut impl io::Write,
+        config: &Config,
+        filter: &mut impl FrameFilter,
+    ) -> io::Result<()> {
+        if self.frames.is_empty() {
+            return Ok(());
+        }
+        if is_suppressed(self, config) {
+            return writeln!(
+                out,
+                r#"<div class="bt-suppressed">{}</div>"#,
+                Escape(&suppressed_message(self))
+            );
+        }
+        writeln!(out, r#"<div class="bt-backtrace">"#)?;
+        if let Some(command) = &self.command {
+            writeln!(out, r#"<div class="bt-command">{}</div>"#, Escape(command))?;
+        }
+        if let Some(test) = &self.test {
+            writeln!(out, r#"<div class="bt-test">{}</div>"#, Escape(test))?;
+        }
+        if let Some(time) = &self.time {
+            writeln!(out, r#"<div class="bt-time">{}</div>"#, Escape(time))?;
+        }
+        if let Some(rustc) = self.build.as_ref().and_then(|build| build.rustc.as_ref()) {
+            writeln!(out, r#"<div class="bt-rustc">{}</div>"#, Escape(rustc))?;
+        }
+        if config.diagnostics {
+            for diagnostic in &self.diagnostics {
+                writeln!(
+                    out,
+                    r#"<div class="bt-diagnostic">{}: {} ({})</div>"#,
+                    Escape(&diagnostic.level),
+                    Escape(&diagnostic.message),
+                    Escape(&diagnostic.location.to_string())
+                )?;
+            }
+        }
+        if !self.span_trace.is_empty() {
+            writeln!(out, r#"<div class="bt-span-trace">"#)?;
+            for (i, span) in self.span_trace.iter().enumerate().rev() {
+                render_span(out, config, i, span)?;
+            }
+            writeln!(out, "</div>")?;
+        }
+        for entry in entries(self, config, filter) {
+            match entry {
+                Entry::Frame(frame) => render_frame(out, config, frame)?,
+                Entry::Hidden(hidden) => writeln!(
+                    out,
+                    r#"<div class="bt-hidden">{}</div>"#,
+                    hidden_frames_message(hidden)
+                )?,
+                Entry::Foreign(foreign) => writeln!(
+                    out,
+                    r#"<div class="bt-foreign">{}</div>"#,
+                    foreign_frames_message(foreign)
+                )?,
+            }
+        }
+        if let Some(panic_info) = &self.panic_info {
+            render_panic_info(out, config, panic_info)?;
+        }
+        if !self.spans.is_empty() {
+            writeln!(
+                out,
+                r#"<div class="bt-spans">{}</div>"#,
+                Escape(&self.spans.join(" > "))
+            )?;
+        }
+        if let Some(note) = &self.note {
+            writeln!(out, r#"<div class="bt-note">{}</div>"#, Escape(note))?;
+        }
+        if !self.trailer.is_empty() {
+            write!(out, r#"<div class="bt-trailer">"#)?;
+            for (i, line) in self.trailer.iter().enumerate() {
+                if i > 0 {
+                    write!(out, "<br>")?;
+                }
+                write!(out, "{}", Escape(line))?;
+            }
+            writeln!(out, "</div>")?;
+        }
+        if self.duplicates > 0 {
+            writeln!(
+                out,
+                r#"<div class="bt-duplicates">{}</div>"#,
+                duplicates_message(self.duplicates)
+            )?;
+        }
+        writeln!(out, "</div>")
+    }
+}
+
+fn render_frame(out: &mut impl io::Write, config: &Config, frame: &Frame) -> io::Result<()> {
+    write!(
+        out,
+        r#"<div class="bt-frame"><span class="bt-frameno">{}:</span> <span class="bt-function">{}</span>"#,
+        frame.frameno,
+        Escape(&frame.function)
+    )?;
+    for annotation in &frame.annotations {
+        write!(
+            out,
+            r#" <span class="bt-annotation">[{}: {}]</span>"#,
+            Escape(&annotation.label),
+            Escape(&annotation.text)
+        )?;
+    }
+    if let Some(source_info) = &frame.source_info {
+        render_source_info(out, config, source_info)?;
+    }
+    writeln!(out, "</div>")
+}
+
+fn render_span(
+    out: &mut impl io::Write,
+    config: &Config,
+    i: usize,
+    span: &SpanTraceEntry,
+) -> io::Result<()> {
+    write!(
+        out,
+        r#"<div class="bt-span"><span class="bt-frameno">{i}:</span> <span class="bt-function">{}</span>"#,
+        Escape(&span.name)
+    )?;
+    if let Some(fields) = &span.fields {
+        write!(
+            out,
+            r#" <span class="bt-fields">with {}</span>"#,
+            Escape(fields)
+        )?;
+    }
+    if let Some(location) = &span.location {
+        render_source_info(out, config, location)?;
+    }
+    writeln!(out, "</div>")
+}
+
+fn render_source_info(
+    out: &mut impl io::Write,
+    config: &Config,
+    source_info: &SourceInfo,
+) -> io::Result<()> {
+    let text = source_info.to_string();
+    write!(out, r#"<div class="bt-location">at "#)?;
+    if config.hyperlinks.enabled {
+        let url = config.hyperlinks.render(
+            &source_info.file,
+            source_info.lineno,
+            source_info.colno.unwrap_or(1),
+        );
+        write!(out, r#"<a href="{}">{}</a>"#, Escape(&url), Escape(&text))?;
+    } else {
+        write!(out, "{}", Escape(&text))?;
+    }
+    write!(out, "</div>")
+}
+
+fn render_panic_info(
+    out: &mut impl io::Write,
+    config: &Config,
+    panic_info: &PanicInfo,
+) -> io::Result<()> {
+    write!(out, r#"<div class="bt-panic">"#)?;
+    // An error returned from `main` only has a message
+    let mut separator = "<br>";
+    match &panic_info.thread {
+        Some(thread) => write!(
+            out,
+            "thread '{}' panicked at {}",
+            Escape(thread),
+            Escape(&panic_info.at)
+        )?,
+        None if panic_info.at.is_empty() => separator = "",
+        None => write!(out, "panicked at {}", Escape(&panic_info.at))?,
+    }
+    for line in &panic_info.message {
+        write!(out, "{separator}{}", Escape(line))?;
+        separator = "<br>";
+    }
+    if let Some(location) = &panic_info.location {
+        render_source_info(out, config, location)?;
+    }
+    writeln!(out, "</div>")
+}
+
+/// Escapes text to be included in HTML.
+struct Escape<'a>(&'a str);
+
+impl std::fmt::Display for Escape<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '<' => f.write_str("&lt;")?,
+                '>' => f.write_str("&gt;")?,
+                '&' => f.write_str("&amp;")?,
+                '"' => f.write_str("&quot;")?,
+                '\'' => f.write_str("&#39;")?,
+                _ => write!(f, "{c}")?,
+            }
+        }
+        Ok(())
+    }
+}
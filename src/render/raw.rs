@@ -0,0 +1,69 @@
+//! Re-emitting backtraces as they were printed, e.g., for tools that expect the format of the
+//! standard library but not every frame.
+
+use crate::{Backtrace, FrameFilter};
+
+impl Backtrace {
+    /// The lines the backtrace was parsed from, i.e., the lines before the frames (e.g., the panic
+    /// line), the lines of every frame, and the lines after them (e.g., the note suggesting another
+    /// `RUST_BACKTRACE` setting). Unrelated lines printed in between are not included, and the
+    /// lines are as parsed, i.e., without the log prefix and ANSI escape codes, so the text isn't
+    /// always exactly what was printed. A panic message longer than
+    /// [`MAX_MESSAGE_LINES`](crate::parse::MAX_MESSAGE_LINES) is truncated. The text is empty for
+    /// backtraces that weren't parsed, e.g., built with a
+    /// [`BacktraceBuilder`](crate::BacktraceBuilder).
+    pub fn raw_text(&self) -> String {
+        self.raw_text_with(&vec![false; self.frames.len()])
+    }
+
+    /// Like [`Backtrace::raw_text`] but leaving out the frames hidden by `filter`. Like in the
+    /// backtraces printed by the standard library for rustc, hidden frames are replaced by a line
+    /// saying how many were omitted, e.g., `[... omitted 2 frames ...]`.
+    pub fn raw_text_filtered(&self, filter: &mut impl FrameFilter) -> String {
+        // Filters are stateful, so they see the frames in the order they are rendered
+        let mut hidden: Vec<bool> = self
+            .frames
+            .iter()
+            .rev()
+            .map(|frame| filter.should_hide(frame))
+            .collect();
+        hidden.reverse();
+        self.raw_text_with(&hidden)
+    }
+
+    fn raw_text_with(&self, hidden: &[bool]) -> String {
+        let mut text = String::new();
+        for line in &self.raw_header {
+            push_line(&mut text, line);
+        }
+        let mut omitted = 0;
+        for (frame, hidden) in self.frames.iter().zip(hidden) {
+            if *hidden {
+                omitted += 1;
+                continue;
+            }
+            push_omitted(&mut text, std::mem::take(&mut omitted));
+            for line in &frame.raw {
+                push_line(&mut text, line);
+            }
+        }
+        push_omitted(&mut text, omitted);
+        for line in &self.raw_footer {
+            push_line(&mut text, line);
+        }
+        text
+    }
+}
+
+fn push_line(text: &mut String, line: &str) {
+    text.push_str(line);
+    text.push('\n');
+}
+
+fn push_omitted(text: &mut String, omitted: usize) {
+    match omitted {
+        0 => {}
+        1 => push_line(text, "      [... omitted 1 frame ...]"),
+        _ => push_line(text, &format!("      [... omitted {omitted} frames ...]")),
+    }
+}
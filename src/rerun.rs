@@ -0,0 +1,48 @@
+//! Offering to rerun the command with the `RUST_BACKTRACE` setting suggested by the notes printed
+//! with its backtraces, e.g., `full` after a short backtrace. The offer is only made when running
+//! interactively, i.e., when both stdin and stderr are terminals, and only on Unix with the `tui`
+//! feature.
+
+use std::io;
+
+use backtracetk::{config::BacktraceStyle, model::Backtrace};
+
+/// Asks whether to rerun with the setting suggested by `backtraces` if it's different from `style`,
+/// returning the style to rerun with.
+#[cfg(all(unix, feature = "tui"))]
+pub fn offer(
+    backtraces: &[Backtrace],
+    style: BacktraceStyle,
+) -> io::Result<Option<BacktraceStyle>> {
+    use std::io::{IsTerminal, Write};
+
+    use termion::{event::Key, input::TermRead, raw::IntoRawMode};
+
+    if !io::stdin().is_terminal() || !io::stderr().is_terminal() {
+        return Ok(None);
+    }
+    let suggested = backtraces
+        .iter()
+        .filter_map(Backtrace::suggested_rust_backtrace)
+        .filter_map(BacktraceStyle::from_env_var)
+        .find(|&suggested| suggested != style);
+    let Some(suggested) = suggested else {
+        return Ok(None);
+    };
+    let mut stderr = io::stderr().into_raw_mode()?;
+    write!(
+        stderr,
+        "(backtracetk: press `r` to rerun with RUST_BACKTRACE={}, any other key to exit) ",
+        suggested.env_var_str()
+    )?;
+    stderr.flush()?;
+    let key = io::stdin().keys().next().transpose()?;
+    // Newlines aren't translated in raw mode
+    write!(stderr, "\r\n")?;
+    Ok((key == Some(Key::Char('r'))).then_some(suggested))
+}
+
+#[cfg(not(all(unix, feature = "tui")))]
+pub fn offer(_: &[Backtrace], _: BacktraceStyle) -> io::Result<Option<BacktraceStyle>> {
+    Ok(None)
+}
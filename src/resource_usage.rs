@@ -0,0 +1,72 @@
+//! The resources used by the command, see the `resource_usage` option. On Unix, they're reported by
+//! the kernel when waiting for the command with `wait4`.
+
+use std::{
+    fmt, io,
+    process::{Child, ExitStatus},
+    time::Duration,
+};
+
+pub struct ResourceUsage {
+    /// The peak resident set size in bytes
+    max_rss: u64,
+    user: Duration,
+    sys: Duration,
+}
+
+impl ResourceUsage {
+    /// The peak resident set size, e.g., `12.5 MiB`
+    pub fn max_rss(&self) -> String {
+        format!("{:.1} MiB", self.max_rss as f64 / (1024.0 * 1024.0))
+    }
+}
+
+impl fmt::Display for ResourceUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "max RSS {}, user {:.2}s, sys {:.2}s",
+            self.max_rss(),
+            self.user.as_secs_f64(),
+            self.sys.as_secs_f64()
+        )
+    }
+}
+
+/// Waits for `child` to exit, returning its exit status and the resources it used. `child` must not
+/// be waited for again.
+#[cfg(unix)]
+pub fn wait(child: &mut Child) -> io::Result<(ExitStatus, Option<ResourceUsage>)> {
+    use std::os::unix::process::ExitStatusExt;
+
+    let mut status = 0;
+    // SAFETY: `rusage` is plain data
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    loop {
+        // SAFETY: the pointers are valid for the duration of the call
+        let res = unsafe { libc::wait4(child.id() as libc::pid_t, &mut status, 0, &mut usage) };
+        if res >= 0 {
+            break;
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+    let time = |time: libc::timeval| {
+        Duration::from_secs(time.tv_sec as u64) + Duration::from_micros(time.tv_usec as u64)
+    };
+    // macOS reports the size in bytes, other systems in kilobytes
+    let unit = if cfg!(target_os = "macos") { 1 } else { 1024 };
+    let usage = ResourceUsage {
+        max_rss: usage.ru_maxrss as u64 * unit,
+        user: time(usage.ru_utime),
+        sys: time(usage.ru_stime),
+    };
+    Ok((ExitStatus::from_raw(status), Some(usage)))
+}
+
+#[cfg(not(unix))]
+pub fn wait(child: &mut Child) -> io::Result<(ExitStatus, Option<ResourceUsage>)> {
+    Ok((child.wait()?, None))
+}
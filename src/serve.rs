@@ -0,0 +1,242 @@
+//! A long-lived server speaking JSON-RPC 2.0, so editor plugins don't need to spawn a process for
+//! every panic. Messages are JSON objects, one per line. The server supports the following methods:
+//!
+//! * `parse`, with params `{"log": string, "color"?: bool}`, parses a whole log.
+//! * `push`, with params `{"chunk": string, "color"?: bool}`, feeds a chunk of a log that is being
+//!   streamed. Chunks don't need to end at a line boundary. Returns the backtraces that are complete
+//!   after the chunk.
+//! * `finish`, with params `{"color"?: bool}`, ends the stream started with `push` returning the
+//!   remaining backtraces.
+//!
+//! All methods return `{"backtraces": [{"backtrace": Backtrace, "rendered": string}]}`. The
+//! rendered text includes ANSI escape codes if `color` is true.
+
+use std::io::{self, BufRead, Write};
+#[cfg(unix)]
+use std::{
+    os::unix::net::{UnixListener, UnixStream},
+    path::Path,
+    thread,
+};
+
+use anyhow::Context;
+use backtracetk::config::Config;
+use backtracetk::filter::Filters;
+use backtracetk::{model::Backtrace, parse::Parser};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// Serves requests read from stdin writing responses to stdout.
+pub fn serve_stdio(config: &Config) -> anyhow::Result<()> {
+    Session::new(config).run(io::stdin().lock(), io::stdout().lock())
+}
+
+/// Listens on a Unix socket at `path` serving each connection in its own thread. Each connection
+/// has its own stream for `push`/`finish`. A connection failing, e.g., because the client went
+/// away, is reported without stopping the server.
+#[cfg(unix)]
+pub fn serve_socket(path: &Path, config: &Config) -> anyhow::Result<()> {
+    let listener = UnixListener::bind(path)
+        .with_context(|| format!("failed to listen on `{}`", path.display()))?;
+    thread::scope(|s| {
+        for stream in listener.incoming() {
+            let stream = stream?;
+            s.spawn(move || {
+                if let Err(err) = serve_connection(stream, config) {
+                    eprintln!("Warning: failed to serve a connection: {err:#}");
+                }
+            });
+        }
+        Ok(())
+    })
+}
+
+#[cfg(unix)]
+fn serve_connection(stream: UnixStream, config: &Config) -> anyhow::Result<()> {
+    let reader = io::BufReader::new(stream.try_clone()?);
+    Session::new(config).run(reader, stream)
+}
+
+#[derive(Deserialize)]
+struct Request {
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Deserialize)]
+struct ParseParams {
+    log: String,
+    #[serde(default)]
+    color: bool,
+}
+
+#[derive(Deserialize)]
+struct PushParams {
+    chunk: String,
+    #[serde(default)]
+    color: bool,
+}
+
+#[derive(Deserialize, Default)]
+struct FinishParams {
+    #[serde(default)]
+    color: bool,
+}
+
+#[derive(Serialize)]
+struct Rendered {
+    backtrace: Backtrace,
+    rendered: String,
+}
+
+struct Error {
+    code: i64,
+    message: String,
+}
+
+impl Error {
+    fn new(code: i64, message: impl ToString) -> Error {
+        Error {
+            code,
+            message: message.to_string(),
+        }
+    }
+}
+
+struct Session<'a> {
+    config: &'a Config,
+    /// Parser for the log being streamed with `push`
+    parser: Parser,
+    /// The last line of the stream if the last chunk didn't end with a newline
+    pending: String,
+}
+
+impl<'a> Session<'a> {
+    fn new(config: &'a Config) -> Session<'a> {
+        Session {
+            config,
+            parser: Parser::from_config(config),
+            pending: String::new(),
+        }
+    }
+
+    fn run(mut self, reader: impl BufRead, mut writer: impl Write) -> anyhow::Result<()> {
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (id, result) = match serde_json::from_str::<Value>(&line) {
+                Ok(message) => {
+                    let id = message.get("id").cloned();
+                    match serde_json::from_value::<Request>(message) {
+                        Ok(request) => (request.id.clone(), self.handle(request)),
+                        Err(err) => (id, Err(Error::new(INVALID_REQUEST, err))),
+                    }
+                }
+                Err(err) => (Some(Value::Null), Err(Error::new(PARSE_ERROR, err))),
+            };
+            // Notifications (requests without an id) don't get a response
+            let Some(id) = id else { continue };
+            let response = match result {
+                Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+                Err(err) => json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": {"code": err.code, "message": err.message},
+                }),
+            };
+            writeln!(writer, "{response}")?;
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    fn handle(&mut self, request: Request) -> Result<Value, Error> {
+        let backtraces = match &request.method[..] {
+            "parse" => {
+                let params: ParseParams = parse_params(request.params)?;
+                let mut parser = Parser::from_config(self.config);
+                for line in params.log.lines() {
+                    parser.parse_line(line);
+                }
+                self.render(parser.into_backtraces(), params.color)
+            }
+            "push" => {
+                let params: PushParams = parse_params(request.params)?;
+                self.pending.push_str(&params.chunk);
+                if let Some(end) = self.pending.rfind('\n') {
+                    for line in self.pending[..end].split('\n') {
+                        self.parser
+                            .parse_line(line.strip_suffix('\r').unwrap_or(line));
+                    }
+                    self.pending.drain(..=end);
+                }
+                let backtraces = self.parser.take_backtraces();
+                self.render(backtraces, params.color)
+            }
+            "finish" => {
+                let params: FinishParams = if request.params.is_null() {
+                    FinishParams::default()
+                } else {
+                    parse_params(request.params)?
+                };
+                let mut parser =
+                    std::mem::replace(&mut self.parser, Parser::from_config(self.config));
+                if !self.pending.is_empty() {
+                    parser.parse_line(&std::mem::take(&mut self.pending));
+                }
+                self.render(parser.into_backtraces(), params.color)
+            }
+            method => {
+                return Err(Error::new(
+                    METHOD_NOT_FOUND,
+                    format!("unknown method `{method}`"),
+                ))
+            }
+        };
+        Ok(json!({ "backtraces": backtraces }))
+    }
+
+    fn render(&self, mut backtraces: Vec<Backtrace>, color: bool) -> Vec<Rendered> {
+        for backtrace in &mut backtraces {
+            backtrace.redact(&self.config.redact);
+        }
+        if self.config.dedupe_adjacent {
+            backtraces = backtracetk::parse::dedupe_adjacent(backtraces);
+        }
+        backtraces
+            .into_iter()
+            .map(|backtrace| {
+                let mut out = vec![];
+                // Writing to a `Vec` can't fail
+                let _ = backtrace.render_to(
+                    &mut out,
+                    self.config,
+                    &mut Filters::from_config(self.config),
+                );
+                let rendered = String::from_utf8_lossy(&out);
+                let rendered = if color {
+                    rendered.into_owned()
+                } else {
+                    anstream::adapter::strip_str(&rendered).to_string()
+                };
+                Rendered {
+                    backtrace,
+                    rendered,
+                }
+            })
+            .collect()
+    }
+}
+
+fn parse_params<T: for<'de> Deserialize<'de>>(params: Value) -> Result<T, Error> {
+    serde_json::from_value(params).map_err(|err| Error::new(INVALID_PARAMS, err))
+}
@@ -0,0 +1,40 @@
+//! Controlling a run with signals sent to backtracetk (not to the command), e.g.,
+//! `kill -USR1 <PID>`. `SIGUSR1` toggles echoing the output of the command and `SIGUSR2` prints a
+//! summary of the backtraces captured so far.
+
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use signal_hook::consts::{SIGUSR1, SIGUSR2};
+
+pub struct Signals {
+    toggle_echo: Arc<AtomicBool>,
+    summary: Arc<AtomicBool>,
+}
+
+impl Signals {
+    pub fn register() -> io::Result<Signals> {
+        let signals = Signals {
+            toggle_echo: Arc::default(),
+            summary: Arc::default(),
+        };
+        signal_hook::flag::register(SIGUSR1, Arc::clone(&signals.toggle_echo))?;
+        signal_hook::flag::register(SIGUSR2, Arc::clone(&signals.summary))?;
+        Ok(signals)
+    }
+
+    /// Whether `SIGUSR1` was received since the last call.
+    pub fn take_toggle_echo(&self) -> bool {
+        self.toggle_echo.swap(false, Ordering::Relaxed)
+    }
+
+    /// Whether `SIGUSR2` was received since the last call.
+    pub fn take_summary(&self) -> bool {
+        self.summary.swap(false, Ordering::Relaxed)
+    }
+}
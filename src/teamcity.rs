@@ -0,0 +1,87 @@
+//! Reporting panics to TeamCity with service messages, see `--format teamcity`. TeamCity reads the
+//! messages from the output of a build step, e.g.,
+//! ```text
+//! ##teamcity[testStarted name='tests::parse']
+//! ##teamcity[testFailed name='tests::parse' message='explicit panic' details='...']
+//! ##teamcity[testFinished name='tests::parse']
+//! ##teamcity[buildProblem description='panicked at src/main.rs:2:5: explicit panic' identity='3b802e1b96ed75a5']
+//! ```
+//! A panic in a known test fails that test, with the frames as the details. Any other panic is a
+//! build problem identified by the fingerprint of its backtrace, so TeamCity can tell which panics
+//! are new in a build.
+
+use std::{fmt::Write as _, io};
+
+use backtracetk::model::Backtrace;
+
+/// TeamCity truncates longer descriptions of build problems.
+const MAX_DESCRIPTION_CHARS: usize = 4000;
+
+/// Writes the service messages for `backtrace`.
+pub fn write_messages(out: &mut impl io::Write, backtrace: &Backtrace) -> io::Result<()> {
+    let message = backtrace
+        .panic_info
+        .as_ref()
+        .and_then(|panic_info| panic_info.message.first())
+        .map_or("panicked", |message| &message[..]);
+    if let Some(test) = &backtrace.test {
+        let name = Escape(test);
+        writeln!(out, "##teamcity[testStarted name='{name}']")?;
+        writeln!(
+            out,
+            "##teamcity[testFailed name='{name}' message='{}' details='{}']",
+            Escape(message),
+            Escape(&details(backtrace))
+        )?;
+        return writeln!(out, "##teamcity[testFinished name='{name}']");
+    }
+    let description = match &backtrace.panic_info {
+        Some(panic_info) if !panic_info.at.is_empty() => {
+            let at = panic_info.at.trim_end_matches(':');
+            format!("panicked at {at}: {message}")
+        }
+        _ => message.to_string(),
+    };
+    let description: String = description.chars().take(MAX_DESCRIPTION_CHARS).collect();
+    writeln!(
+        out,
+        "##teamcity[buildProblem description='{}' identity='{}']",
+        Escape(&description),
+        backtrace.fingerprint()
+    )
+}
+
+/// The frames of the backtrace, innermost first as printed by the standard library.
+fn details(backtrace: &Backtrace) -> String {
+    let mut details = String::new();
+    for frame in &backtrace.frames {
+        let _ = writeln!(details, "{:>4}: {}", frame.frameno, frame.function);
+        if let Some(source_info) = &frame.source_info {
+            let _ = writeln!(details, "             at {source_info}");
+        }
+    }
+    details
+}
+
+/// Escapes a value of a service message.
+struct Escape<'a>(&'a str);
+
+impl std::fmt::Display for Escape<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '\'' => f.write_str("|'")?,
+                '|' => f.write_str("||")?,
+                '\n' => f.write_str("|n")?,
+                '\r' => f.write_str("|r")?,
+                '[' => f.write_str("|[")?,
+                ']' => f.write_str("|]")?,
+                '\u{0085}' => f.write_str("|x")?,
+                '\u{2028}' => f.write_str("|l")?,
+                '\u{2029}' => f.write_str("|p")?,
+                _ => f.write_char(c)?,
+            }
+        }
+        Ok(())
+    }
+}
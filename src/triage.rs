@@ -0,0 +1,154 @@
+//! Grouping the panics of a test run by the owner of the code that panicked and by fingerprint, to
+//! make triaging a failing suite manageable.
+//!
+//! The test a panic belongs to is the name of the panicking thread, libtest and nextest run every
+//! test in a thread named after the test, unless the test is known precisely (see
+//! [`Backtrace::test`]).
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+use backtracetk::model::{Backtrace, Fingerprint};
+use serde::Serialize;
+
+use crate::codeowners::CodeOwners;
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum Format {
+    Markdown,
+    Json,
+}
+
+#[derive(Serialize)]
+pub struct Report {
+    owners: Vec<OwnerGroup>,
+}
+
+#[derive(Serialize)]
+struct OwnerGroup {
+    /// `None` for panics in code without owners
+    owner: Option<String>,
+    panics: Vec<PanicGroup>,
+}
+
+#[derive(Serialize)]
+struct PanicGroup {
+    fingerprint: Fingerprint,
+    occurrences: usize,
+    /// The first line of the message of the first occurrence
+    message: Option<String>,
+    /// Where the first occurrence panicked
+    location: Option<String>,
+    tests: Vec<String>,
+}
+
+impl Report {
+    pub fn new(backtraces: &[Backtrace], codeowners: Option<&CodeOwners>) -> Report {
+        let mut owners: BTreeMap<Option<String>, BTreeMap<Fingerprint, PanicGroup>> =
+            BTreeMap::new();
+        for backtrace in backtraces {
+            let fingerprint = backtrace.fingerprint();
+            for owner in owners_of(backtrace, codeowners) {
+                let group = owners
+                    .entry(owner)
+                    .or_default()
+                    .entry(fingerprint)
+                    .or_insert_with(|| PanicGroup::new(fingerprint, backtrace));
+                group.occurrences += backtrace.duplicates + 1;
+                let test = backtrace.test.as_ref().or_else(|| {
+                    let panic_info = backtrace.panic_info.as_ref()?;
+                    panic_info.thread.as_ref()
+                });
+                if let Some(test) = test.filter(|test| *test != "main") {
+                    if !group.tests.contains(test) {
+                        group.tests.push(test.clone());
+                    }
+                }
+            }
+        }
+
+        // Owned code first, most frequent panics first within each owner
+        let mut owners = owners
+            .into_iter()
+            .map(|(owner, panics)| {
+                let mut panics: Vec<_> = panics.into_values().collect();
+                panics.sort_by_key(|panic| std::cmp::Reverse(panic.occurrences));
+                OwnerGroup { owner, panics }
+            })
+            .collect::<Vec<_>>();
+        owners.sort_by_key(|group| group.owner.is_none());
+        Report { owners }
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::from("# Panic triage\n");
+        for group in &self.owners {
+            let owner = group.owner.as_deref().unwrap_or("Unowned");
+            let _ = writeln!(out, "\n## {owner}");
+            for panic in &group.panics {
+                let message = panic.message.as_deref().unwrap_or("(no message)");
+                let _ = writeln!(out, "\n### `{}` {message}\n", panic.fingerprint);
+                let _ = writeln!(out, "- Occurrences: {}", panic.occurrences);
+                if let Some(location) = &panic.location {
+                    let _ = writeln!(out, "- Location: `{location}`");
+                }
+                if !panic.tests.is_empty() {
+                    let tests: Vec<_> = panic.tests.iter().map(|t| format!("`{t}`")).collect();
+                    let _ = writeln!(out, "- Tests: {}", tests.join(", "));
+                }
+            }
+        }
+        out
+    }
+}
+
+impl PanicGroup {
+    fn new(fingerprint: Fingerprint, backtrace: &Backtrace) -> PanicGroup {
+        let panic_info = backtrace.panic_info.as_ref();
+        PanicGroup {
+            fingerprint,
+            occurrences: 0,
+            message: panic_info.and_then(|p| p.message.first()).cloned(),
+            location: panic_info.map(|p| p.at.trim_end_matches(':').to_string()),
+            tests: vec![],
+        }
+    }
+}
+
+/// The owners of the innermost user frame, or of the panic location if there are no user frames.
+fn owners_of(backtrace: &Backtrace, codeowners: Option<&CodeOwners>) -> Vec<Option<String>> {
+    let Some(codeowners) = codeowners else {
+        return vec![None];
+    };
+    let file = match backtrace.top_user_frame() {
+        Some(frame) => frame.source_info.as_ref().map(|s| PathBuf::from(&s.file)),
+        None => backtrace
+            .panic_info
+            .as_ref()
+            .and_then(|p| p.at.split(':').next())
+            .map(PathBuf::from),
+    };
+    let owners = file
+        .as_deref()
+        .map_or(&[][..], |file| codeowners.owners(file));
+    if owners.is_empty() {
+        vec![None]
+    } else {
+        owners.iter().cloned().map(Some).collect()
+    }
+}
+
+/// Reads the `CODEOWNERS` file at `path` or, if not given, the one of the current repository.
+pub fn read_codeowners(path: Option<&Path>) -> anyhow::Result<Option<CodeOwners>> {
+    let path = match path {
+        Some(path) => path.to_path_buf(),
+        None => match CodeOwners::find(&std::env::current_dir()?) {
+            Some(path) => path,
+            None => return Ok(None),
+        },
+    };
+    CodeOwners::read(&path).map(Some)
+}
@@ -0,0 +1,55 @@
+//! JavaScript bindings for the parse, filter, and render pipeline, enabled with the `wasm` feature.
+//! They let web-based log viewers render backtraces exactly like the command-line tool.
+
+use std::io;
+
+use wasm_bindgen::prelude::*;
+
+use crate::{config::Config, filter::Filters, parse::Parser, Backtrace};
+
+/// Renders the backtraces found in `log` as HTML. `config` has the same format as a configuration
+/// file.
+#[wasm_bindgen(js_name = renderHtml)]
+pub fn render_html(log: &str, config: &str) -> Result<String, JsError> {
+    render(log, config, |backtrace, out, config, filters| {
+        backtrace.render_html(out, config, filters)
+    })
+}
+
+/// Renders the backtraces found in `log` as text with ANSI escape codes, like in a terminal.
+/// `config` has the same format as a configuration file.
+#[wasm_bindgen(js_name = renderAnsi)]
+pub fn render_ansi(log: &str, config: &str) -> Result<String, JsError> {
+    render(log, config, |backtrace, out, config, filters| {
+        backtrace.render_to(out, config, filters)
+    })
+}
+
+fn render(
+    log: &str,
+    config: &str,
+    render: impl Fn(&Backtrace, &mut Vec<u8>, &Config, &mut Filters) -> io::Result<()>,
+) -> Result<String, JsError> {
+    let config = Config::from_toml(config).map_err(|err| JsError::new(&format!("{err:#}")))?;
+    let mut parser = Parser::from_config(&config);
+    for line in log.lines() {
+        parser.parse_line(line);
+    }
+    let mut backtraces = parser.into_backtraces();
+    for backtrace in &mut backtraces {
+        backtrace.redact(&config.redact);
+    }
+    if config.dedupe_adjacent {
+        backtraces = crate::parse::dedupe_adjacent(backtraces);
+    }
+    let mut out = vec![];
+    for backtrace in &backtraces {
+        render(
+            backtrace,
+            &mut out,
+            &config,
+            &mut Filters::from_config(&config),
+        )?;
+    }
+    Ok(String::from_utf8_lossy(&out).into_owned())
+}
@@ -0,0 +1,250 @@
+//! Parses every sample in `tests/fixtures`, checking that the parser recognizes all the lines of
+//! their backtraces and that they are parsed as in `tests/expected`, after merging adjacent
+//! duplicates as the CLI does by default. A new sample can be checked with
+//! `backtracetk --check-parser <FILE>` before adding it, and its expected backtraces written by
+//! running the tests with `BLESS=1`.
+//!
+//! Samples ending in `.jsonl` are events reported by `cargo nextest`, whose backtraces are parsed
+//! from the output of each failed test like `backtracetk nextest` does.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use backtracetk::{
+    model::Backtrace,
+    parse::{nextest::Event, Parser},
+};
+
+/// The samples in `tests/fixtures`, leaving out the files they refer to, e.g., `human-panic` reports
+/// in `tests/fixtures/reports`.
+fn samples(dir: &Path) -> impl Iterator<Item = PathBuf> {
+    fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|ext| ext == "txt" || ext == "jsonl")
+        })
+}
+
+fn is_nextest(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "jsonl")
+}
+
+/// The outputs in which the sample at `path` is parsed: the sample itself or the output of every
+/// failed test.
+fn outputs(path: &Path) -> Vec<String> {
+    let contents = fs::read_to_string(path).unwrap();
+    if !is_nextest(path) {
+        return vec![contents];
+    }
+    contents
+        .lines()
+        .filter_map(Event::parse)
+        .filter_map(|event| event.failure_output().map(str::to_string))
+        .collect()
+}
+
+fn parse_sample(path: &Path) -> Vec<Backtrace> {
+    if !is_nextest(path) {
+        return backtracetk::parse::parse_file(path).unwrap();
+    }
+    fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .filter_map(Event::parse)
+        .flat_map(|event| event.backtraces(Parser::new()))
+        .collect()
+}
+
+#[test]
+fn fixtures() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    for path in samples(&dir) {
+        let name = path.file_name().unwrap().to_string_lossy();
+        let mut backtraces = 0;
+        for output in outputs(&path) {
+            let report = backtracetk::parse::check::check(Parser::new(), output.lines());
+            let failures: Vec<_> = report.failures.iter().map(|i| i + 1).collect();
+            assert!(
+                failures.is_empty(),
+                "{name}: lines {failures:?} not recognized"
+            );
+            backtraces += report.backtraces;
+        }
+        assert!(backtraces > 0, "{name}: no backtraces found");
+    }
+}
+
+/// The backtraces parsed from every sample, as JSON, match the ones in `tests/expected`.
+#[test]
+fn expected() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    for path in samples(&root.join("tests/fixtures")) {
+        let backtraces = backtracetk::parse::dedupe_adjacent(parse_sample(&path));
+        let actual = serde_json::to_string_pretty(&backtraces).unwrap() + "\n";
+        let name = path.file_stem().unwrap().to_string_lossy();
+        let expected_path = root.join("tests/expected").join(format!("{name}.json"));
+        if std::env::var_os("BLESS").is_some() {
+            fs::write(&expected_path, &actual).unwrap();
+            continue;
+        }
+        let expected = fs::read_to_string(&expected_path)
+            .unwrap_or_else(|err| panic!("{}: {err}", expected_path.display()));
+        assert!(
+            actual == expected,
+            "{name}: the backtraces don't match {}, run with `BLESS=1` to update it if the \
+             changes are expected\n{actual}",
+            expected_path.display()
+        );
+    }
+}
+
+/// Errors returned from `main` are reported with their message and the span trace captured by
+/// `color-eyre`, in the same backtrace as the frames if there are any.
+#[test]
+fn reports_without_backtrace() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+
+    let backtraces = backtracetk::parse::parse_file(&dir.join("color_eyre_spantrace.txt")).unwrap();
+    assert_eq!(backtraces.len(), 1);
+    let span_trace = &backtraces[0].span_trace;
+    assert_eq!(span_trace.len(), 1);
+    assert_eq!(span_trace[0].name, "app::read_file");
+    let panic_info = backtraces[0].panic_info.as_ref().unwrap();
+    assert_eq!(panic_info.at, "src/main.rs:32");
+
+    let backtraces = backtracetk::parse::parse_file(&dir.join("anyhow.txt")).unwrap();
+    assert_eq!(backtraces.len(), 1);
+    assert!(!backtraces[0].frames.is_empty());
+    let panic_info = backtraces[0].panic_info.as_ref().unwrap();
+    assert_eq!(
+        panic_info.message[0],
+        "Error: failed to read the configuration"
+    );
+
+    let backtraces = backtracetk::parse::parse_file(&dir.join("anyhow_no_backtrace.txt")).unwrap();
+    assert_eq!(backtraces.len(), 1);
+    assert!(backtraces[0].frames.is_empty());
+    let panic_info = backtraces[0].panic_info.as_ref().unwrap();
+    assert_eq!(panic_info.message[0], "Error: failed to load");
+}
+
+/// A trace printed by a panic hook and then by the default hook is merged into one, but the panics
+/// of two threads at the same location aren't.
+#[test]
+fn duplicates() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let parse = |name| {
+        let backtraces = backtracetk::parse::parse_file(&dir.join(name)).unwrap();
+        backtracetk::parse::dedupe_adjacent(backtraces)
+    };
+
+    let backtraces = parse("panic_hook_duplicate.txt");
+    assert_eq!(backtraces.len(), 1);
+    assert_eq!(backtraces[0].duplicates, 1);
+    let panic_info = backtraces[0].panic_info.as_ref().unwrap();
+    assert_eq!(panic_info.message, ["boom"]);
+
+    let backtraces = parse("multi_thread.txt");
+    assert_eq!(backtraces.len(), 2);
+    assert!(backtraces.iter().all(|backtrace| backtrace.duplicates == 0));
+}
+
+/// A `color-backtrace` panic is parsed from its message and location lines, skipping the lines in
+/// place of hidden frames.
+#[test]
+fn color_backtrace() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let backtraces = backtracetk::parse::parse_file(&dir.join("color_backtrace.txt")).unwrap();
+    assert_eq!(backtraces.len(), 1);
+    let frames: Vec<_> = backtraces[0].frames.iter().map(|f| f.frameno).collect();
+    assert_eq!(frames, [14, 15, 16]);
+    let panic_info = backtraces[0].panic_info.as_ref().unwrap();
+    assert_eq!(panic_info.at, "src/main.rs:6");
+    assert_eq!(
+        panic_info.message,
+        ["index out of bounds: the len is 3 but the index is 10"]
+    );
+}
+
+/// The line printed by `human-panic` refers to the report, whose backtrace is read on demand.
+#[test]
+fn human_panic() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let backtraces =
+        backtracetk::parse::parse_file(&root.join("tests/fixtures/human_panic.txt")).unwrap();
+    assert_eq!(backtraces.len(), 1);
+    assert!(backtraces[0].frames.is_empty());
+    let report_file = backtraces[0].report_file.as_ref().unwrap();
+
+    let backtrace = backtracetk::human_panic::read_report(&root.join(report_file)).unwrap();
+    let functions: Vec<_> = backtrace
+        .frames
+        .iter()
+        .map(|f| backtracetk::model::strip_hash(&f.function))
+        .collect();
+    assert_eq!(
+        functions,
+        [
+            "std::panicking::begin_panic_handler",
+            "core::panicking::panic",
+            "app::load_config",
+            "app::main",
+            "std::rt::lang_start::{{closure}}",
+        ]
+    );
+    let panic_info = backtrace.panic_info.as_ref().unwrap();
+    assert_eq!(panic_info.at, "src/main.rs:10");
+    assert_eq!(
+        panic_info.message,
+        ["called `Option::unwrap()` on a `None` value"]
+    );
+}
+
+/// The backtraces in the output of a failed test are attributed to the test named in the event,
+/// and the events of tests that passed are skipped.
+#[test]
+fn nextest() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/nextest.jsonl");
+    let backtraces = parse_sample(&path);
+    assert_eq!(backtraces.len(), 1);
+    assert_eq!(backtraces[0].test.as_deref(), Some("app::lib$tests::parse"));
+    let panic_info = backtraces[0].panic_info.as_ref().unwrap();
+    assert_eq!(panic_info.thread.as_deref(), Some("tests::parse"));
+}
+
+/// A Miri error becomes a backtrace with a frame per `inside` note, skipping the source code and
+/// help between them.
+#[test]
+fn miri() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/miri.txt");
+    let backtraces = parse_sample(&path);
+    assert_eq!(backtraces.len(), 1);
+    let frames: Vec<_> = backtraces[0]
+        .frames
+        .iter()
+        .map(|f| (&f.function[..], f.source_info.as_ref().unwrap().lineno))
+        .collect();
+    assert_eq!(frames, [("read", 4), ("main", 11)]);
+    let panic_info = backtraces[0].panic_info.as_ref().unwrap();
+    assert_eq!(panic_info.at, "src/main.rs:4:23");
+    assert!(panic_info.message[0].starts_with("Undefined Behavior: memory access failed"));
+}
+
+/// Lines logged with an `Error: ` prefix aren't taken for error reports, since nothing that makes
+/// them one follows them.
+#[test]
+fn logged_errors() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/logged_errors.txt");
+    let backtraces = parse_sample(&path);
+    assert_eq!(backtraces.len(), 1);
+    let panic_info = backtraces[0].panic_info.as_ref().unwrap();
+    assert_eq!(panic_info.message, ["too many failed requests"]);
+    assert_eq!(
+        backtraces[0].trailer,
+        ["Error: shutting down after a panic"]
+    );
+}
@@ -0,0 +1,92 @@
+//! Checks that parsing a large log in parallel gives the same backtraces as parsing it line by
+//! line, including the state that carries over the panic lines the log is split at.
+
+use backtracetk::{
+    config::Config,
+    parse::{parse_bytes_parallel, parse_file_with_config, Parser},
+};
+
+/// A log with a few panics per test, diagnostics and spans printed before them, and lines after
+/// them, long enough to be split in several chunks.
+fn log() -> String {
+    let mut log = String::new();
+    let mut i = 0;
+    while log.len() < 4 << 20 {
+        if i % 7 == 0 {
+            log.push_str(&format!("---- tests::test_{i} stdout ----\n"));
+        }
+        if i % 5 == 0 {
+            log.push_str(&format!("warning: unused variable `x{i}`\n"));
+            log.push_str(&format!(" --> src/lib.rs:{i}:9\n"));
+        }
+        if i % 3 == 0 {
+            log.push_str(&format!(
+                "2024-06-01T12:00:00.123456Z ERROR request{{id={i}}}: app::api: failed\n"
+            ));
+        }
+        log.push_str(&format!(
+            "thread 'main' panicked at src/main.rs:{i}:5:\n\
+             boom {i}\n\
+             stack backtrace:\n   \
+             0: app::f{i}\n             \
+             at ./src/main.rs:{i}:5\n   \
+             1: app::main\n             \
+             at ./src/main.rs:1:1\n\
+             note: Some details are omitted, run with `RUST_BACKTRACE=full` for a verbose backtrace.\n\
+             done {i}\n"
+        ));
+        if i % 11 == 0 {
+            log.push_str("test result: FAILED. 0 passed; 1 failed\n");
+        }
+        i += 1;
+    }
+    log
+}
+
+#[test]
+fn parallel_matches_serial() {
+    let log = log();
+    let mut parser = Parser::new();
+    parser.parse_bytes(log.as_bytes());
+    let serial = parser.into_backtraces();
+    let parallel = parse_bytes_parallel(log.as_bytes());
+    assert_eq!(parallel.len(), serial.len());
+    for (i, (parallel, serial)) in parallel.iter().zip(&serial).enumerate() {
+        assert_eq!(
+            serde_json::to_string(parallel).unwrap(),
+            serde_json::to_string(serial).unwrap(),
+            "backtrace {i}"
+        );
+    }
+    // The lines after a backtrace are only its trailer if it's the last one
+    assert!(serial[..serial.len() - 1]
+        .iter()
+        .all(|backtrace| backtrace.trailer.is_empty()));
+}
+
+/// With `log_prefix`, the log is split at the panic lines after their prefix.
+#[test]
+fn parallel_with_log_prefix() {
+    let config = Config::from_toml(r"log_prefix = '^web-\d \| '").unwrap();
+    let log: String = log()
+        .lines()
+        .enumerate()
+        .map(|(i, line)| format!("web-{} | {line}\n", i % 3))
+        .collect();
+    let path =
+        std::env::temp_dir().join(format!("backtracetk-parallel-{}.log", std::process::id()));
+    std::fs::write(&path, &log).unwrap();
+    let parallel = parse_file_with_config(&path, &config).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let mut parser = Parser::from_config(&config);
+    parser.parse_bytes(log.as_bytes());
+    let serial = parser.into_backtraces();
+    assert_eq!(parallel.len(), serial.len());
+    for (i, (parallel, serial)) in parallel.iter().zip(&serial).enumerate() {
+        assert_eq!(
+            serde_json::to_string(parallel).unwrap(),
+            serde_json::to_string(serial).unwrap(),
+            "backtrace {i}"
+        );
+    }
+}